@@ -5,6 +5,7 @@ cargo test \
     --features wasi-http \
     --features component-model \
     --features serve \
+    --features objdump \
     --workspace \
     --exclude test-programs \
     $@
@@ -1,6 +1,6 @@
 //! Module for configuring the cache system.
 
-use super::Worker;
+use super::{CacheStatistics, CacheStore, DiskCacheStore, Worker};
 use anyhow::{anyhow, bail, Context, Result};
 use directories_next::ProjectDirs;
 use log::{trace, warn};
@@ -91,6 +91,8 @@ pub struct CacheConfig {
     #[serde(skip)]
     worker: Option<Worker>,
     #[serde(skip)]
+    store: Option<Arc<dyn CacheStore>>,
+    #[serde(skip)]
     state: Arc<CacheState>,
 }
 
@@ -308,13 +310,19 @@ impl CacheConfig {
 
     /// Returns path to the cache directory.
     ///
-    /// Panics if the cache is disabled.
+    /// Panics if the cache is disabled or uses a custom [`CacheStore`].
     pub fn directory(&self) -> &PathBuf {
         self.directory
             .as_ref()
             .expect(CACHE_IMPROPER_CONFIG_ERROR_MSG)
     }
 
+    /// Returns path to the cache directory, if the cache is enabled and keeps
+    /// artifacts in one.
+    pub(crate) fn directory_if_enabled(&self) -> Option<&Path> {
+        self.directory.as_deref().filter(|_| self.enabled)
+    }
+
     /// Creates a new set of configuration which represents a disabled cache
     pub fn new_cache_disabled() -> Self {
         Self {
@@ -332,21 +340,63 @@ impl CacheConfig {
             file_count_limit_percent_if_deleting: None,
             files_total_size_limit_percent_if_deleting: None,
             worker: None,
+            store: None,
             state: Arc::new(CacheState::default()),
         }
     }
 
+    /// Creates a new set of configuration which keeps artifacts in `store`
+    /// instead of the cache directory.
+    ///
+    /// None of the settings of the cache directory apply to such a
+    /// configuration: the store is responsible for deciding what to keep.
+    pub fn new_cache_with_store(store: Arc<dyn CacheStore>) -> Self {
+        let mut conf = Self::new_cache_enabled_template();
+        conf.store = Some(store);
+        conf
+    }
+
     fn new_cache_enabled_template() -> Self {
         let mut conf = Self::new_cache_disabled();
         conf.enabled = true;
         conf
     }
 
+    /// Creates a builder for an enabled cache configuration, which uses the
+    /// same defaults as a configuration file that only enables the cache.
+    pub fn builder() -> CacheConfigBuilder {
+        CacheConfigBuilder {
+            config: Self::new_cache_enabled_template(),
+        }
+    }
+
     /// Parses cache configuration from the file specified
     pub fn from_file(config_file: Option<&Path>) -> Result<Self> {
+        Self::load_and_parse_file(config_file)?.validate()
+    }
+
+    /// Parses cache configuration from the file specified, like
+    /// [`CacheConfig::from_file`], but without starting the cache worker.
+    ///
+    /// This is meant for inspecting and managing the cache directory, e.g.
+    /// with [`CacheConfig::clean_up`]. Nothing is cached with such a
+    /// configuration: compilation behaves as if the cache were disabled.
+    pub fn from_file_without_worker(config_file: Option<&Path>) -> Result<Self> {
         let mut config = Self::load_and_parse_file(config_file)?;
+        config.validate_settings()?;
+        Ok(config)
+    }
+
+    /// Validates the settings of a configuration, fills in defaults and, if
+    /// the cache is enabled, starts its worker.
+    fn validate(mut self) -> Result<Self> {
+        self.validate_settings()?;
+        self.spawn_worker();
+        Ok(self)
+    }
 
-        // validate values and fill in defaults
+    fn validate_settings(&mut self) -> Result<()> {
+        let config = self;
         config.validate_directory_or_default()?;
         config.validate_worker_event_queue_size_or_default();
         config.validate_baseline_compression_level_or_default()?;
@@ -359,17 +409,22 @@ impl CacheConfig {
         config.validate_files_total_size_soft_limit_or_default();
         config.validate_file_count_limit_percent_if_deleting_or_default()?;
         config.validate_files_total_size_limit_percent_if_deleting_or_default()?;
-        config.spawn_worker();
-
-        Ok(config)
+        Ok(())
     }
 
     fn spawn_worker(&mut self) {
         if self.enabled {
-            self.worker = Some(Worker::start_new(self));
+            let worker = Worker::start_new(self);
+            self.store = Some(Arc::new(DiskCacheStore::new(
+                self.directory().join("modules"),
+                self.baseline_compression_level(),
+                worker.clone(),
+            )));
+            self.worker = Some(worker);
         }
     }
 
+    #[cfg(test)]
     pub(super) fn worker(&self) -> &Worker {
         assert!(self.enabled);
         self.worker.as_ref().unwrap()
@@ -385,14 +440,28 @@ impl CacheConfig {
         self.state.misses.load(SeqCst)
     }
 
-    pub(crate) fn on_cache_get_async(&self, path: impl AsRef<Path>) {
+    /// Returns the store artifacts are kept in, if the cache is enabled and
+    /// has one.
+    pub(crate) fn store(&self) -> Option<&dyn CacheStore> {
+        self.store.as_deref().filter(|_| self.enabled)
+    }
+
+    pub(crate) fn on_cache_hit(&self) {
         self.state.hits.fetch_add(1, SeqCst);
-        self.worker().on_cache_get_async(path)
+        self.record_statistics(CacheStatistics { hits: 1, misses: 0 });
     }
 
-    pub(crate) fn on_cache_update_async(&self, path: impl AsRef<Path>) {
+    pub(crate) fn on_cache_miss(&self) {
         self.state.misses.fetch_add(1, SeqCst);
-        self.worker().on_cache_update_async(path)
+        self.record_statistics(CacheStatistics { hits: 0, misses: 1 });
+    }
+
+    fn record_statistics(&self, statistics: CacheStatistics) {
+        // The statistics file is shared with other processes, so it's updated
+        // by the worker rather than on the compilation path.
+        if let Some(worker) = &self.worker {
+            worker.on_cache_statistics_async(statistics);
+        }
     }
 
     fn load_and_parse_file(config_file: Option<&Path>) -> Result<Self> {
@@ -579,6 +648,99 @@ impl CacheConfig {
     }
 }
 
+macro_rules! generate_setting_setter {
+    ($(#[$attr:meta])* $setting:ident: $setting_type:ty) => {
+        $(#[$attr])*
+        pub fn $setting(&mut self, $setting: $setting_type) -> &mut Self {
+            self.config.$setting = Some($setting);
+            self
+        }
+    };
+}
+
+/// Builder for a [`CacheConfig`], for embedders which configure the cache
+/// without a configuration file.
+///
+/// Each setting corresponds to the setting of the same name in the
+/// [configuration file][docs] and has the same default. Settings are
+/// validated by [`CacheConfigBuilder::build`].
+///
+/// [docs]: https://bytecodealliance.github.io/wasmtime/cli-cache.html
+#[derive(Debug, Clone)]
+pub struct CacheConfigBuilder {
+    config: CacheConfig,
+}
+
+impl CacheConfigBuilder {
+    /// Sets the directory in which compiled modules are cached; it has to be
+    /// an absolute path and is created if it doesn't exist.
+    pub fn directory(&mut self, directory: impl Into<PathBuf>) -> &mut Self {
+        self.config.directory = Some(directory.into());
+        self
+    }
+
+    generate_setting_setter!(
+        /// Sets the size of the queue of events sent to the cache worker.
+        worker_event_queue_size: u64
+    );
+    generate_setting_setter!(
+        /// Sets the zstd compression level used when a module is first cached.
+        baseline_compression_level: i32
+    );
+    generate_setting_setter!(
+        /// Sets the zstd compression level used when a frequently used module
+        /// is recompressed.
+        optimized_compression_level: i32
+    );
+    generate_setting_setter!(
+        /// Sets how many times a module has to be used before it is
+        /// recompressed with the optimized compression level.
+        optimized_compression_usage_counter_threshold: u64
+    );
+    generate_setting_setter!(
+        /// Sets how often the cache directory is cleaned up.
+        cleanup_interval: Duration
+    );
+    generate_setting_setter!(
+        /// Sets how long a recompression task may take before another process
+        /// may take it over.
+        optimizing_compression_task_timeout: Duration
+    );
+    generate_setting_setter!(
+        /// Sets how far in the future a file's modification time may be before
+        /// it is considered invalid.
+        allowed_clock_drift_for_files_from_future: Duration
+    );
+    generate_setting_setter!(
+        /// Sets the number of cached files above which a clean up deletes
+        /// files.
+        file_count_soft_limit: u64
+    );
+    generate_setting_setter!(
+        /// Sets the total size in bytes of cached files above which a clean up
+        /// deletes files.
+        files_total_size_soft_limit: u64
+    );
+    generate_setting_setter!(
+        /// Sets the percentage of `file_count_soft_limit` which a clean up
+        /// keeps once it deletes files.
+        file_count_limit_percent_if_deleting: u8
+    );
+    generate_setting_setter!(
+        /// Sets the percentage of `files_total_size_soft_limit` which a clean
+        /// up keeps once it deletes files.
+        files_total_size_limit_percent_if_deleting: u8
+    );
+
+    /// Validates the settings and creates the configuration, creating the
+    /// cache directory and starting the cache worker.
+    pub fn build(&self) -> Result<CacheConfig> {
+        let mut config = self.config.clone();
+        config.state = Default::default();
+        config.validate()
+    }
+}
+
 #[cfg(test)]
 #[macro_use]
 pub mod tests;
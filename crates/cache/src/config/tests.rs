@@ -523,3 +523,45 @@ fn test_percent_settings() {
         cd
     );
 }
+
+#[test]
+fn test_builder() {
+    let (_td, cd, _cp) = test_prolog();
+    let conf = CacheConfig::builder()
+        .directory(&cd)
+        .baseline_compression_level(1)
+        .optimized_compression_level(21)
+        .cleanup_interval(Duration::from_secs(60))
+        .files_total_size_soft_limit(1 << 20)
+        .file_count_limit_percent_if_deleting(50)
+        .build()
+        .unwrap();
+    assert!(conf.enabled());
+    assert_eq!(
+        conf.directory(),
+        &fs::canonicalize(&cd).expect("canonicalize failed")
+    );
+    assert_eq!(conf.baseline_compression_level(), 1);
+    assert_eq!(conf.optimized_compression_level(), 21);
+    assert_eq!(conf.cleanup_interval(), Duration::from_secs(60));
+    assert_eq!(conf.files_total_size_soft_limit(), 1 << 20);
+    assert_eq!(conf.file_count_limit_percent_if_deleting(), 50);
+    // unset settings use the same defaults as a configuration file
+    assert_eq!(conf.worker_event_queue_size(), 0x10);
+    assert_eq!(conf.file_count_soft_limit(), 0x10_000);
+
+    // settings are validated like the ones of a configuration file
+    let mut builder = CacheConfig::builder();
+    builder.directory(&cd).baseline_compression_level(22);
+    assert!(builder.build().is_err());
+    builder
+        .baseline_compression_level(10)
+        .optimized_compression_level(5);
+    assert!(builder.build().is_err());
+    builder.optimized_compression_level(10);
+    assert!(builder.build().is_ok());
+    assert!(CacheConfig::builder()
+        .directory("relative/dir")
+        .build()
+        .is_err());
+}
@@ -0,0 +1,155 @@
+//! Inspection and maintenance of the cache directory, e.g. for the
+//! `wasmtime cache` command.
+
+use super::{worker, CacheConfig};
+use anyhow::{anyhow, Context, Result};
+use serde_derive::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The name of the file, in the cache directory, with the hit and miss
+/// counters of all processes using the cache.
+pub(super) const STATISTICS_FILE: &str = "statistics.toml";
+
+/// Hit and miss counters of all processes using a cache directory.
+///
+/// These are approximate: they are written in the background by the cache
+/// worker, or when the cache configuration is dropped, and are lost if the
+/// statistics file stays locked by another process until then.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStatistics {
+    /// The number of modules loaded from the cache.
+    pub hits: u64,
+    /// The number of modules which had to be compiled.
+    pub misses: u64,
+}
+
+/// A compiled module in the cache directory.
+#[derive(Debug, Clone)]
+pub struct CachedModule {
+    /// The compiler which produced the module, including its version.
+    pub compiler: String,
+    /// The hash of the module and of the settings it was compiled with.
+    pub hash: String,
+    /// The path of the compressed module.
+    pub path: PathBuf,
+    /// The size in bytes of the compressed module.
+    pub size: u64,
+    /// The last time the module was used, which is what the cleanup of the
+    /// cache goes by.
+    pub last_used: SystemTime,
+    /// How many times the module was used, if known.
+    pub usages: Option<u64>,
+    /// The zstd compression level of the module, if known.
+    pub compression_level: Option<i32>,
+}
+
+impl CachedModule {
+    /// Reads and decompresses the module, which is a precompiled artifact as
+    /// produced by `wasmtime compile`.
+    pub fn read(&self) -> Result<Vec<u8>> {
+        let compressed = fs::read(&self.path)
+            .with_context(|| format!("failed to read {}", self.path.display()))?;
+        zstd::decode_all(&compressed[..])
+            .with_context(|| format!("failed to decompress {}", self.path.display()))
+    }
+}
+
+impl CacheConfig {
+    fn cache_directory(&self) -> Result<&Path> {
+        self.directory_if_enabled()
+            .ok_or_else(|| anyhow!("the cache is disabled or doesn't use a directory"))
+    }
+
+    /// Returns the hit and miss counters of all processes which used the cache
+    /// directory.
+    ///
+    /// Unlike [`CacheConfig::cache_hits`] and [`CacheConfig::cache_misses`],
+    /// these are kept in the cache directory.
+    pub fn statistics(&self) -> Result<CacheStatistics> {
+        self.cache_directory()?;
+        Ok(read_cache_statistics(self.directory()))
+    }
+
+    /// Lists the compiled modules in the cache directory.
+    pub fn cached_modules(&self) -> Result<Vec<CachedModule>> {
+        let modules_dir = self.cache_directory()?.join("modules");
+        let mut modules = Vec::new();
+        if !modules_dir.exists() {
+            return Ok(modules);
+        }
+        for compiler_dir in read_dir(&modules_dir)? {
+            if !compiler_dir.is_dir() {
+                continue;
+            }
+            let compiler = file_name(&compiler_dir);
+            for path in read_dir(&compiler_dir)? {
+                // Skip the `.stats` files and locks of the modules.
+                if path.extension().is_some() || !path.is_file() {
+                    continue;
+                }
+                let metadata = fs::metadata(&path)
+                    .with_context(|| format!("failed to read metadata of {}", path.display()))?;
+                let stats_path = path.with_extension("stats");
+                let stats = worker::read_stats_file(&stats_path);
+                let last_used = fs::metadata(&stats_path)
+                    .and_then(|m| m.modified())
+                    .or_else(|_| metadata.modified())?;
+                modules.push(CachedModule {
+                    compiler: compiler.clone(),
+                    hash: file_name(&path),
+                    size: metadata.len(),
+                    last_used,
+                    usages: stats.as_ref().map(|s| s.usages),
+                    compression_level: stats.as_ref().map(|s| s.compression_level),
+                    path,
+                });
+            }
+        }
+        modules.sort_by(|a, b| b.last_used.cmp(&a.last_used));
+        Ok(modules)
+    }
+
+    /// Cleans up the cache directory now, as the cache worker periodically
+    /// does: unrecognized files are deleted and, if the cache exceeds one of
+    /// its soft limits, so are the least recently used modules.
+    pub fn clean_up(&self) -> Result<()> {
+        self.cache_directory()?;
+        worker::clean_up_now(self);
+        Ok(())
+    }
+
+    /// Deletes all modules and statistics in the cache directory.
+    pub fn clear(&self) -> Result<()> {
+        let directory = self.cache_directory()?;
+        let modules_dir = directory.join("modules");
+        if modules_dir.exists() {
+            fs::remove_dir_all(&modules_dir)
+                .with_context(|| format!("failed to remove {}", modules_dir.display()))?;
+        }
+        let statistics = directory.join(STATISTICS_FILE);
+        if statistics.exists() {
+            fs::remove_file(&statistics)
+                .with_context(|| format!("failed to remove {}", statistics.display()))?;
+        }
+        Ok(())
+    }
+}
+
+pub(super) fn read_cache_statistics(directory: &Path) -> CacheStatistics {
+    fs::read(directory.join(STATISTICS_FILE))
+        .ok()
+        .and_then(|bytes| toml::from_slice(&bytes[..]).ok())
+        .unwrap_or_default()
+}
+
+fn read_dir(dir: &Path) -> Result<Vec<PathBuf>> {
+    fs::read_dir(dir)
+        .and_then(|entries| entries.map(|e| e.map(|e| e.path())).collect())
+        .with_context(|| format!("failed to list {}", dir.display()))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap().to_string_lossy().into_owned()
+}
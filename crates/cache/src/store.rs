@@ -0,0 +1,221 @@
+//! Storage backends for cached compilation artifacts.
+
+use super::{fs_write_atomic, Worker};
+use log::{debug, trace, warn};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Implementation of a storage backend for the module cache.
+///
+/// Keys are made of characters which are valid in file names, separated by
+/// `/`: the first component identifies the compiler and its version and the
+/// second one is a hash of the module and of the compilation settings. Values
+/// are the serialized artifacts; stores may compress them.
+///
+/// Failing to store or retrieve an artifact is not an error: the module is
+/// simply compiled again.
+pub trait CacheStore: Send + Sync + std::fmt::Debug {
+    /// Try to retrieve the bytes which were inserted for `key`.
+    fn get(&self, key: &str) -> Option<Vec<u8>>;
+
+    /// Given a key and bytes, stores them in the cache.
+    ///
+    /// Returns false when insertion in the cache failed.
+    fn insert(&self, key: &str, value: Vec<u8>) -> bool;
+
+    /// Removes the entry for `key`, if any.
+    ///
+    /// This is called when the stored bytes could not be used, e.g. because
+    /// they are corrupt.
+    fn evict(&self, key: &str);
+
+    /// Called once the bytes retrieved for `key` were successfully used, e.g.
+    /// to keep track of how often an entry is used.
+    ///
+    /// This does nothing by default.
+    fn on_hit(&self, _key: &str) {}
+}
+
+/// The default store, which keeps zstd-compressed artifacts in the cache
+/// directory and lets the cache worker clean them up.
+#[derive(Debug)]
+pub(crate) struct DiskCacheStore {
+    root: PathBuf,
+    compression_level: i32,
+    worker: Worker,
+}
+
+impl DiskCacheStore {
+    pub(crate) fn new(root: PathBuf, compression_level: i32, worker: Worker) -> Self {
+        Self {
+            root,
+            compression_level,
+            worker,
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        key.split('/')
+            .fold(self.root.clone(), |path, c| path.join(c))
+    }
+}
+
+impl CacheStore for DiskCacheStore {
+    fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let mod_cache_path = self.path(key);
+        trace!("get_data() for path: {}", mod_cache_path.display());
+        let compressed_cache_bytes = fs::read(&mod_cache_path).ok()?;
+        let cache_bytes = zstd::decode_all(&compressed_cache_bytes[..])
+            .map_err(|err| warn!("Failed to decompress cached code: {}", err))
+            .ok()?;
+        Some(cache_bytes)
+    }
+
+    fn insert(&self, key: &str, value: Vec<u8>) -> bool {
+        let mod_cache_path = self.path(key);
+        trace!("update_data() for path: {}", mod_cache_path.display());
+        let compressed_data = match zstd::encode_all(&value[..], self.compression_level) {
+            Ok(data) => data,
+            Err(err) => {
+                warn!("Failed to compress cached code: {}", err);
+                return false;
+            }
+        };
+
+        // Optimize syscalls: first, try writing to disk. It should succeed in most cases.
+        // Otherwise, try creating the cache directory and retry writing to the file.
+        if fs_write_atomic(&mod_cache_path, "mod", &compressed_data).is_ok() {
+            self.worker.on_cache_update_async(&mod_cache_path);
+            return true;
+        }
+
+        debug!(
+            "Attempting to create the cache directory, because \
+             failed to write cached code to disk, path: {}",
+            mod_cache_path.display(),
+        );
+
+        let cache_dir = mod_cache_path.parent().unwrap();
+        if let Err(err) = fs::create_dir_all(cache_dir) {
+            warn!(
+                "Failed to create cache directory, path: {}, message: {}",
+                cache_dir.display(),
+                err
+            );
+            return false;
+        }
+
+        match fs_write_atomic(&mod_cache_path, "mod", &compressed_data) {
+            Ok(_) => {
+                self.worker.on_cache_update_async(&mod_cache_path);
+                true
+            }
+            Err(err) => {
+                warn!(
+                    "Failed to write file with rename, target path: {}, err: {}",
+                    mod_cache_path.display(),
+                    err
+                );
+                false
+            }
+        }
+    }
+
+    fn evict(&self, key: &str) {
+        let mod_cache_path = self.path(key);
+        if let Err(err) = fs::remove_file(&mod_cache_path) {
+            debug!(
+                "Failed to remove cached code, path: {}, err: {}",
+                mod_cache_path.display(),
+                err
+            );
+        }
+    }
+
+    fn on_hit(&self, key: &str) {
+        // Usages are only counted for entries which could be deserialized,
+        // so that corrupt ones don't get recompressed as if they were hot.
+        self.worker.on_cache_get_async(self.path(key));
+    }
+}
+
+/// A [`CacheStore`] which keeps artifacts in memory, evicting the least
+/// recently used ones once their total size exceeds a limit.
+#[derive(Debug)]
+pub struct InMemoryCacheStore {
+    max_size: usize,
+    state: Mutex<InMemoryState>,
+}
+
+#[derive(Debug, Default)]
+struct InMemoryState {
+    clock: u64,
+    size: usize,
+    /// Each entry is tagged with the last time it was used.
+    entries: HashMap<String, (Vec<u8>, u64)>,
+}
+
+impl InMemoryCacheStore {
+    /// Creates an empty store which holds at most `max_size` bytes of
+    /// artifacts.
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            max_size,
+            state: Mutex::new(InMemoryState::default()),
+        }
+    }
+
+    /// Returns the number of entries in the store.
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().entries.len()
+    }
+
+    /// Returns the total size in bytes of the entries in the store.
+    pub fn size(&self) -> usize {
+        self.state.lock().unwrap().size
+    }
+}
+
+impl CacheStore for InMemoryCacheStore {
+    fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        let clock = state.clock;
+        let (value, last_used) = state.entries.get_mut(key)?;
+        *last_used = clock;
+        Some(value.clone())
+    }
+
+    fn insert(&self, key: &str, value: Vec<u8>) -> bool {
+        if value.len() > self.max_size {
+            return false;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        state.size += value.len();
+        let entry = (value, state.clock);
+        if let Some((old, _)) = state.entries.insert(key.to_string(), entry) {
+            state.size -= old.len();
+        }
+        while state.size > self.max_size {
+            let oldest = state
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(key, _)| key.clone())
+                .unwrap();
+            let (value, _) = state.entries.remove(&oldest).unwrap();
+            state.size -= value.len();
+        }
+        true
+    }
+
+    fn evict(&self, key: &str) {
+        let mut state = self.state.lock().unwrap();
+        if let Some((value, _)) = state.entries.remove(key) {
+            state.size -= value.len();
+        }
+    }
+}
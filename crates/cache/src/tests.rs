@@ -1,6 +1,7 @@
 use super::config::tests::test_prolog;
 use super::*;
 use std::fs;
+use std::sync::{Arc, Mutex};
 
 // Since cache system is a global thing, each test needs to be run in seperate process.
 // So, init() tests are run as integration tests.
@@ -90,3 +91,143 @@ fn test_write_read_cache() {
     entry1.get_data::<_, i32, i32>(4, |_| panic!()).unwrap();
     entry2.get_data::<_, i32, i32>(1, |_| panic!()).unwrap();
 }
+
+#[test]
+fn test_custom_store() {
+    let store = Arc::new(InMemoryCacheStore::new(1 << 20));
+    let cache_config = CacheConfig::new_cache_with_store(store.clone());
+    let entry = ModuleCacheEntry::new("test", &cache_config);
+
+    entry.get_data::<_, i32, i32>(1, |_| Ok(100)).unwrap();
+    entry.get_data::<_, i32, i32>(1, |_| panic!()).unwrap();
+    entry.get_data::<_, i32, i32>(2, |_| Ok(100)).unwrap();
+    assert_eq!(store.len(), 2);
+    assert_eq!(cache_config.cache_hits(), 1);
+    assert_eq!(cache_config.cache_misses(), 2);
+}
+
+#[test]
+fn test_custom_store_evicts_unusable_entries() {
+    /// A store whose entries can never be deserialized.
+    #[derive(Debug, Default)]
+    struct GarbageStore(Mutex<Vec<String>>);
+
+    impl CacheStore for GarbageStore {
+        fn get(&self, _key: &str) -> Option<Vec<u8>> {
+            Some(b"garbage".to_vec())
+        }
+        fn insert(&self, _key: &str, _value: Vec<u8>) -> bool {
+            true
+        }
+        fn evict(&self, key: &str) {
+            self.0.lock().unwrap().push(key.to_string());
+        }
+        fn on_hit(&self, _key: &str) {
+            panic!("unusable entries aren't hits");
+        }
+    }
+
+    let store = Arc::new(GarbageStore::default());
+    let cache_config = CacheConfig::new_cache_with_store(store.clone());
+    let entry = ModuleCacheEntry::new("test", &cache_config);
+    assert_eq!(
+        entry.get_data::<_, String, i32>(1, |_| Ok("ok".into())),
+        Ok("ok".into())
+    );
+    assert_eq!(store.0.lock().unwrap().len(), 1);
+    assert_eq!(cache_config.cache_hits(), 0);
+    assert_eq!(cache_config.cache_misses(), 1);
+}
+
+#[test]
+fn test_in_memory_store_evicts_least_recently_used() {
+    let store = InMemoryCacheStore::new(8);
+    assert!(store.insert("a", vec![0; 4]));
+    assert!(store.insert("b", vec![0; 4]));
+    assert!(store.get("a").is_some());
+    assert!(store.insert("c", vec![0; 4]));
+    assert!(store.get("b").is_none());
+    assert!(store.get("a").is_some());
+    assert_eq!(store.size(), 8);
+
+    assert!(!store.insert("d", vec![0; 9]));
+    store.evict("a");
+    assert_eq!(store.len(), 1);
+    assert_eq!(store.size(), 4);
+}
+
+#[test]
+fn test_manage_cache_directory() {
+    let (_tempdir, cache_dir, config_path) = test_prolog();
+    let cache_config = load_config!(
+        config_path,
+        "[cache]\n\
+         enabled = true\n\
+         directory = {cache_dir}\n",
+        cache_dir
+    );
+    let entry1 = ModuleCacheEntry::new("test-1", &cache_config);
+    let entry2 = ModuleCacheEntry::new("test-2", &cache_config);
+    entry1.get_data::<_, i32, i32>(1, |_| Ok(100)).unwrap();
+    entry1.get_data::<_, i32, i32>(1, |_| panic!()).unwrap();
+    entry2.get_data::<_, i32, i32>(1, |_| Ok(100)).unwrap();
+    cache_config.worker().wait_for_all_events_handled();
+
+    let modules = cache_config.cached_modules().unwrap();
+    let mut compilers = modules.iter().map(|m| &m.compiler[..]).collect::<Vec<_>>();
+    compilers.sort();
+    assert_eq!(compilers.len(), 2);
+    assert!(compilers[0].starts_with("test-1"));
+    assert!(compilers[1].starts_with("test-2"));
+    for module in &modules {
+        let value: i32 = bincode::deserialize(&module.read().unwrap()).unwrap();
+        assert_eq!(value, 100);
+    }
+    let statistics = cache_config.statistics().unwrap();
+    assert_eq!(statistics, CacheStatistics { hits: 1, misses: 2 });
+
+    cache_config.clear().unwrap();
+    assert!(cache_config.cached_modules().unwrap().is_empty());
+    assert_eq!(
+        cache_config.statistics().unwrap(),
+        CacheStatistics::default()
+    );
+
+    let custom = CacheConfig::new_cache_with_store(Arc::new(InMemoryCacheStore::new(1)));
+    assert!(custom.cached_modules().is_err());
+}
+
+#[test]
+fn test_manage_cache_directory_without_worker() {
+    let (_tempdir, cache_dir, config_path) = test_prolog();
+    let config_content = format!(
+        "[cache]\n\
+         enabled = true\n\
+         directory = {}\n",
+        toml::to_string_pretty(&format!("{}", cache_dir.display())).unwrap(),
+    );
+    fs::write(&config_path, config_content).expect("Failed to write test config file");
+
+    let cache_config = CacheConfig::from_file_without_worker(Some(&config_path)).unwrap();
+    assert!(cache_config.enabled());
+
+    // nothing is cached without a worker
+    let entry = ModuleCacheEntry::new("test", &cache_config);
+    entry.get_data::<_, i32, i32>(1, |_| Ok(100)).unwrap();
+    entry.get_data::<_, i32, i32>(1, |_| Ok(100)).unwrap();
+    assert_eq!(cache_config.cache_misses(), 0);
+    assert!(cache_config.cached_modules().unwrap().is_empty());
+
+    // cleaning up takes the lock of the periodic cleanup
+    cache_config.clean_up().unwrap();
+    let has_cleanup_lock = fs::read_dir(cache_config.directory())
+        .unwrap()
+        .any(|entry| {
+            entry
+                .unwrap()
+                .file_name()
+                .to_string_lossy()
+                .starts_with(".cleanup.wip-")
+        });
+    assert!(has_cleanup_lock);
+}
@@ -5,6 +5,7 @@
 //! but we guarantee eventual consistency and fault tolerancy.
 //! Background tasks can be CPU intensive, but the worker thread has low priority.
 
+use super::manage::{read_cache_statistics, CacheStatistics, STATISTICS_FILE};
 use super::{fs_write_atomic, CacheConfig};
 use log::{debug, info, trace, warn};
 use serde_derive::{Deserialize, Serialize};
@@ -16,7 +17,8 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
 #[cfg(test)]
-use std::sync::{Arc, Condvar, Mutex};
+use std::sync::Condvar;
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 #[cfg(not(test))]
@@ -27,6 +29,7 @@ use tests::system_time_stub::SystemTimeStub as SystemTime;
 #[derive(Clone)]
 pub(super) struct Worker {
     sender: SyncSender<CacheEvent>,
+    statistics: Arc<PendingStatistics>,
     #[cfg(test)]
     stats: Arc<(Mutex<WorkerStats>, Condvar)>,
 }
@@ -34,10 +37,28 @@ pub(super) struct Worker {
 struct WorkerThread {
     receiver: Receiver<CacheEvent>,
     cache_config: CacheConfig,
+    statistics: Arc<Mutex<CacheStatistics>>,
     #[cfg(test)]
     stats: Arc<(Mutex<WorkerStats>, Condvar)>,
 }
 
+/// Hits and misses which weren't added to the statistics file yet.
+///
+/// They are written by the worker thread, and the ones left when the last
+/// handle to the worker is dropped, e.g. at the end of a short-lived process,
+/// are written on drop.
+struct PendingStatistics {
+    counters: Arc<Mutex<CacheStatistics>>,
+    directory: PathBuf,
+    allowed_future_drift: Duration,
+}
+
+impl Drop for PendingStatistics {
+    fn drop(&mut self) {
+        flush_statistics(&self.directory, self.allowed_future_drift, &self.counters);
+    }
+}
+
 #[cfg(test)]
 #[derive(Default)]
 struct WorkerStats {
@@ -50,6 +71,7 @@ struct WorkerStats {
 enum CacheEvent {
     OnCacheGet(PathBuf),
     OnCacheUpdate(PathBuf),
+    OnCacheStatistics,
 }
 
 impl Worker {
@@ -63,9 +85,16 @@ impl Worker {
         #[cfg(test)]
         let stats = Arc::new((Mutex::new(WorkerStats::default()), Condvar::new()));
 
+        let statistics = PendingStatistics {
+            counters: Arc::default(),
+            directory: cache_config.directory().to_path_buf(),
+            allowed_future_drift: cache_config.allowed_clock_drift_for_files_from_future(),
+        };
+
         let worker_thread = WorkerThread {
             receiver: rx,
             cache_config: cache_config.clone(),
+            statistics: statistics.counters.clone(),
             #[cfg(test)]
             stats: stats.clone(),
         };
@@ -77,6 +106,7 @@ impl Worker {
 
         Self {
             sender: tx,
+            statistics: Arc::new(statistics),
             #[cfg(test)]
             stats,
         }
@@ -92,6 +122,15 @@ impl Worker {
         self.send_cache_event(event);
     }
 
+    pub(super) fn on_cache_statistics_async(&self, statistics: CacheStatistics) {
+        {
+            let mut pending = self.statistics.counters.lock().unwrap();
+            pending.hits += statistics.hits;
+            pending.misses += statistics.misses;
+        }
+        self.send_cache_event(CacheEvent::OnCacheStatistics);
+    }
+
     #[inline]
     fn send_cache_event(&self, event: CacheEvent) {
         let sent_event = self.sender.try_send(event.clone());
@@ -149,7 +188,7 @@ impl fmt::Debug for Worker {
 }
 
 #[derive(Serialize, Deserialize)]
-struct ModuleCacheStatistics {
+pub(super) struct ModuleCacheStatistics {
     pub usages: u64,
     #[serde(rename = "optimized-compression")]
     pub compression_level: i32,
@@ -201,6 +240,7 @@ impl WorkerThread {
             match event {
                 CacheEvent::OnCacheGet(path) => self.handle_on_cache_get(path),
                 CacheEvent::OnCacheUpdate(path) => self.handle_on_cache_update(path),
+                CacheEvent::OnCacheStatistics => self.handle_on_cache_statistics(),
             }
 
             #[cfg(test)]
@@ -423,311 +463,410 @@ impl WorkerThread {
             return;
         }
 
-        trace!("Trying to clean up cache");
+        clean_up(&self.cache_config);
+    }
+}
 
-        let mut cache_index = self.list_cache_contents();
-        let future_tolerance = SystemTime::now()
-            .checked_add(
-                self.cache_config
-                    .allowed_clock_drift_for_files_from_future(),
-            )
-            .expect("Brace your cache, the next Big Bang is coming (time overflow)");
-        cache_index.sort_unstable_by(|lhs, rhs| {
-            // sort by age
-            use CacheEntry::*;
-            match (lhs, rhs) {
-                (Recognized { mtime: lhs_mt, .. }, Recognized { mtime: rhs_mt, .. }) => {
-                    match (*lhs_mt > future_tolerance, *rhs_mt > future_tolerance) {
-                        // later == younger
-                        (false, false) => rhs_mt.cmp(lhs_mt),
-                        // files from far future are treated as oldest recognized files
-                        // we want to delete them, so the cache keeps track of recent files
-                        // however, we don't delete them uncodintionally,
-                        // because .stats file can be overwritten with a meaningful mtime
-                        (true, false) => cmp::Ordering::Greater,
-                        (false, true) => cmp::Ordering::Less,
-                        (true, true) => cmp::Ordering::Equal,
-                    }
+impl WorkerThread {
+    fn handle_on_cache_statistics(&self) {
+        trace!("handle_on_cache_statistics()");
+
+        flush_statistics(
+            self.cache_config.directory(),
+            self.cache_config
+                .allowed_clock_drift_for_files_from_future(),
+            &self.statistics,
+        );
+    }
+}
+
+/// Adds the pending hits and misses to the statistics file of the cache
+/// directory.
+///
+/// The file is shared by all processes using the cache, so it is only updated
+/// while holding a lock; if another process holds it, the counters are kept
+/// pending.
+fn flush_statistics(
+    directory: &Path,
+    allowed_future_drift: Duration,
+    pending: &Mutex<CacheStatistics>,
+) {
+    let mut pending = pending.lock().unwrap();
+    if *pending == CacheStatistics::default() {
+        return;
+    }
+
+    let lock_path = match acquire_task_fs_lock(
+        &directory.join(STATISTICS_LOCK),
+        STATISTICS_LOCK_TIMEOUT,
+        allowed_future_drift,
+    ) {
+        Some(path) => path,
+        None => return,
+    };
+
+    let mut statistics = read_cache_statistics(directory);
+    statistics.hits += pending.hits;
+    statistics.misses += pending.misses;
+    let path = directory.join(STATISTICS_FILE);
+    match toml::to_string_pretty(&statistics)
+        .map_err(anyhow::Error::from)
+        .and_then(|s| Ok(fs_write_atomic(&path, "stats", s.as_bytes())?))
+    {
+        Ok(()) => *pending = CacheStatistics::default(),
+        Err(err) => warn!(
+            "Failed to write cache statistics, path: {}, err: {}",
+            path.display(),
+            err
+        ),
+    }
+
+    if let Err(err) = fs::remove_file(&lock_path) {
+        warn!(
+            "Failed to remove lock file, path: {}, err: {}",
+            lock_path.display(),
+            err
+        );
+    }
+}
+
+/// The name of the lock of the statistics file, without its extension.
+const STATISTICS_LOCK: &str = "statistics";
+
+/// How long a lock of the statistics file is honoured, in case the process
+/// holding it died before removing it.
+const STATISTICS_LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Cleans up the cache right away, regardless of its cleanup interval.
+///
+/// The cleanup lock is taken as a worker would, so workers skip their next
+/// periodic cleanup. The lock only records when the last cleanup started
+/// though, so a cleanup that's already running in another process can't be
+/// detected and may overlap with this one. That's harmless: both only delete
+/// files, and files which are already gone are skipped.
+pub(super) fn clean_up_now(cache_config: &CacheConfig) {
+    let cleanup_file = cache_config.directory().join(".cleanup");
+    // Not getting the lock means this process already holds it, which doesn't
+    // matter since the cleanup is forced anyway.
+    let _ = acquire_task_fs_lock(
+        &cleanup_file,
+        Duration::ZERO,
+        cache_config.allowed_clock_drift_for_files_from_future(),
+    );
+    clean_up(cache_config);
+}
+
+/// Deletes unrecognized files and, if the cache exceeds one of its soft
+/// limits, the least recently used modules.
+pub(super) fn clean_up(cache_config: &CacheConfig) {
+    trace!("Trying to clean up cache");
+
+    let mut cache_index = list_cache_contents(cache_config);
+    let future_tolerance = SystemTime::now()
+        .checked_add(cache_config.allowed_clock_drift_for_files_from_future())
+        .expect("Brace your cache, the next Big Bang is coming (time overflow)");
+    cache_index.sort_unstable_by(|lhs, rhs| {
+        // sort by age
+        use CacheEntry::*;
+        match (lhs, rhs) {
+            (Recognized { mtime: lhs_mt, .. }, Recognized { mtime: rhs_mt, .. }) => {
+                match (*lhs_mt > future_tolerance, *rhs_mt > future_tolerance) {
+                    // later == younger
+                    (false, false) => rhs_mt.cmp(lhs_mt),
+                    // files from far future are treated as oldest recognized files
+                    // we want to delete them, so the cache keeps track of recent files
+                    // however, we don't delete them uncodintionally,
+                    // because .stats file can be overwritten with a meaningful mtime
+                    (true, false) => cmp::Ordering::Greater,
+                    (false, true) => cmp::Ordering::Less,
+                    (true, true) => cmp::Ordering::Equal,
                 }
-                // unrecognized is kind of infinity
-                (Recognized { .. }, Unrecognized { .. }) => cmp::Ordering::Less,
-                (Unrecognized { .. }, Recognized { .. }) => cmp::Ordering::Greater,
-                (Unrecognized { .. }, Unrecognized { .. }) => cmp::Ordering::Equal,
             }
-        });
-
-        // find "cut" boundary:
-        // - remove unrecognized files anyway,
-        // - remove some cache files if some quota has been exceeded
-        let mut total_size = 0u64;
-        let mut start_delete_idx = None;
-        let mut start_delete_idx_if_deleting_recognized_items: Option<usize> = None;
-
-        let total_size_limit = self.cache_config.files_total_size_soft_limit();
-        let file_count_limit = self.cache_config.file_count_soft_limit();
-        let tsl_if_deleting = total_size_limit
-            .checked_mul(
-                self.cache_config
-                    .files_total_size_limit_percent_if_deleting() as u64,
-            )
-            .unwrap()
-            / 100;
-        let fcl_if_deleting = file_count_limit
-            .checked_mul(self.cache_config.file_count_limit_percent_if_deleting() as u64)
-            .unwrap()
-            / 100;
-
-        for (idx, item) in cache_index.iter().enumerate() {
-            let size = if let CacheEntry::Recognized { size, .. } = item {
-                size
-            } else {
-                start_delete_idx = Some(idx);
-                break;
-            };
+            // unrecognized is kind of infinity
+            (Recognized { .. }, Unrecognized { .. }) => cmp::Ordering::Less,
+            (Unrecognized { .. }, Recognized { .. }) => cmp::Ordering::Greater,
+            (Unrecognized { .. }, Unrecognized { .. }) => cmp::Ordering::Equal,
+        }
+    });
+
+    // find "cut" boundary:
+    // - remove unrecognized files anyway,
+    // - remove some cache files if some quota has been exceeded
+    let mut total_size = 0u64;
+    let mut start_delete_idx = None;
+    let mut start_delete_idx_if_deleting_recognized_items: Option<usize> = None;
+
+    let total_size_limit = cache_config.files_total_size_soft_limit();
+    let file_count_limit = cache_config.file_count_soft_limit();
+    let tsl_if_deleting = total_size_limit
+        .checked_mul(cache_config.files_total_size_limit_percent_if_deleting() as u64)
+        .unwrap()
+        / 100;
+    let fcl_if_deleting = file_count_limit
+        .checked_mul(cache_config.file_count_limit_percent_if_deleting() as u64)
+        .unwrap()
+        / 100;
+
+    for (idx, item) in cache_index.iter().enumerate() {
+        let size = if let CacheEntry::Recognized { size, .. } = item {
+            size
+        } else {
+            start_delete_idx = Some(idx);
+            break;
+        };
 
-            total_size += size;
-            if start_delete_idx_if_deleting_recognized_items.is_none()
-                && (total_size > tsl_if_deleting || (idx + 1) as u64 > fcl_if_deleting)
-            {
-                start_delete_idx_if_deleting_recognized_items = Some(idx);
-            }
+        total_size += size;
+        if start_delete_idx_if_deleting_recognized_items.is_none()
+            && (total_size > tsl_if_deleting || (idx + 1) as u64 > fcl_if_deleting)
+        {
+            start_delete_idx_if_deleting_recognized_items = Some(idx);
+        }
 
-            if total_size > total_size_limit || (idx + 1) as u64 > file_count_limit {
-                start_delete_idx = start_delete_idx_if_deleting_recognized_items;
-                break;
-            }
+        if total_size > total_size_limit || (idx + 1) as u64 > file_count_limit {
+            start_delete_idx = start_delete_idx_if_deleting_recognized_items;
+            break;
         }
+    }
 
-        if let Some(idx) = start_delete_idx {
-            for item in &cache_index[idx..] {
-                let (result, path, entity) = match item {
-                    CacheEntry::Recognized { path, .. }
-                    | CacheEntry::Unrecognized {
-                        path,
-                        is_dir: false,
-                    } => (fs::remove_file(path), path, "file"),
-                    CacheEntry::Unrecognized { path, is_dir: true } => {
-                        (fs::remove_dir_all(path), path, "directory")
-                    }
-                };
-                if let Err(err) = result {
-                    warn!(
-                        "Failed to remove {} during cleanup, path: {}, err: {}",
-                        entity,
-                        path.display(),
-                        err
-                    );
+    if let Some(idx) = start_delete_idx {
+        for item in &cache_index[idx..] {
+            let (result, path, entity) = match item {
+                CacheEntry::Recognized { path, .. }
+                | CacheEntry::Unrecognized {
+                    path,
+                    is_dir: false,
+                } => (fs::remove_file(path), path, "file"),
+                CacheEntry::Unrecognized { path, is_dir: true } => {
+                    (fs::remove_dir_all(path), path, "directory")
                 }
+            };
+            if let Err(err) = result {
+                warn!(
+                    "Failed to remove {} during cleanup, path: {}, err: {}",
+                    entity,
+                    path.display(),
+                    err
+                );
             }
         }
-
-        trace!("Task finished: clean up cache");
     }
 
-    // Be fault tolerant: list as much as you can, and ignore the rest
-    fn list_cache_contents(&self) -> Vec<CacheEntry> {
-        fn enter_dir(
-            vec: &mut Vec<CacheEntry>,
-            dir_path: &Path,
-            level: u8,
-            cache_config: &CacheConfig,
-        ) {
-            macro_rules! add_unrecognized {
-                (file: $path:expr) => {
-                    add_unrecognized!(false, $path)
-                };
-                (dir: $path:expr) => {
-                    add_unrecognized!(true, $path)
-                };
-                ($is_dir:expr, $path:expr) => {
-                    vec.push(CacheEntry::Unrecognized {
-                        path: $path.to_path_buf(),
-                        is_dir: $is_dir,
-                    })
-                };
-            }
-            macro_rules! add_unrecognized_and {
-                ([ $( $ty:ident: $path:expr ),* ], $cont:stmt) => {{
-                    $( add_unrecognized!($ty: $path); )*
-                        $cont
-                }};
-            }
+    trace!("Task finished: clean up cache");
+}
 
-            macro_rules! unwrap_or {
-                ($result:expr, $cont:stmt, $err_msg:expr) => {
-                    unwrap_or!($result, $cont, $err_msg, dir_path)
-                };
-                ($result:expr, $cont:stmt, $err_msg:expr, $path:expr) => {
-                    unwrap_or_warn!(
-                        $result,
-                        $cont,
-                        format!("{}, level: {}", $err_msg, level),
-                        $path
-                    )
-                };
-            }
+// Be fault tolerant: list as much as you can, and ignore the rest
+fn list_cache_contents(cache_config: &CacheConfig) -> Vec<CacheEntry> {
+    fn enter_dir(
+        vec: &mut Vec<CacheEntry>,
+        dir_path: &Path,
+        level: u8,
+        cache_config: &CacheConfig,
+    ) {
+        macro_rules! add_unrecognized {
+            (file: $path:expr) => {
+                add_unrecognized!(false, $path)
+            };
+            (dir: $path:expr) => {
+                add_unrecognized!(true, $path)
+            };
+            ($is_dir:expr, $path:expr) => {
+                vec.push(CacheEntry::Unrecognized {
+                    path: $path.to_path_buf(),
+                    is_dir: $is_dir,
+                })
+            };
+        }
+        macro_rules! add_unrecognized_and {
+            ([ $( $ty:ident: $path:expr ),* ], $cont:stmt) => {{
+                $( add_unrecognized!($ty: $path); )*
+                    $cont
+            }};
+        }
+
+        macro_rules! unwrap_or {
+            ($result:expr, $cont:stmt, $err_msg:expr) => {
+                unwrap_or!($result, $cont, $err_msg, dir_path)
+            };
+            ($result:expr, $cont:stmt, $err_msg:expr, $path:expr) => {
+                unwrap_or_warn!(
+                    $result,
+                    $cont,
+                    format!("{}, level: {}", $err_msg, level),
+                    $path
+                )
+            };
+        }
+
+        // If we fail to list a directory, something bad is happening anyway
+        // (something touches our cache or we have disk failure)
+        // Try to delete it, so we can stay within soft limits of the cache size.
+        // This comment applies later in this function, too.
+        let it = unwrap_or!(
+            fs::read_dir(dir_path),
+            add_unrecognized_and!([dir: dir_path], return),
+            "Failed to list cache directory, deleting it"
+        );
 
-            // If we fail to list a directory, something bad is happening anyway
-            // (something touches our cache or we have disk failure)
-            // Try to delete it, so we can stay within soft limits of the cache size.
-            // This comment applies later in this function, too.
-            let it = unwrap_or!(
-                fs::read_dir(dir_path),
-                add_unrecognized_and!([dir: dir_path], return),
-                "Failed to list cache directory, deleting it"
+        let mut cache_files = HashMap::new();
+        for entry in it {
+            // read_dir() returns an iterator over results - in case some of them are errors
+            // we don't know their names, so we can't delete them. We don't want to delete
+            // the whole directory with good entries too, so we just ignore the erroneous entries.
+            let entry = unwrap_or!(
+                entry,
+                continue,
+                "Failed to read a cache dir entry (NOT deleting it, it still occupies space)"
             );
+            let path = entry.path();
+            match (level, path.is_dir()) {
+                (0..=1, true) => enter_dir(vec, &path, level + 1, cache_config),
+                (0..=1, false) => {
+                    if level == 0 && path.file_name() == Some(OsStr::new(STATISTICS_FILE)) {
+                        continue; // hit and miss counters
+                    }
+                    if level == 0
+                        && path.file_stem() == Some(OsStr::new(STATISTICS_LOCK))
+                        && path.extension().is_some()
+                        && !is_fs_lock_expired(
+                            Some(&entry),
+                            &path,
+                            STATISTICS_LOCK_TIMEOUT,
+                            cache_config.allowed_clock_drift_for_files_from_future(),
+                        )
+                    {
+                        continue; // skip active lock
+                    }
+                    if level == 0
+                        && path.file_stem() == Some(OsStr::new(".cleanup"))
+                            && path.extension().is_some()
+                            // assume it's cleanup lock
+                            && !is_fs_lock_expired(
+                                Some(&entry),
+                                &path,
+                                cache_config.cleanup_interval(),
+                                cache_config.allowed_clock_drift_for_files_from_future(),
+                            )
+                    {
+                        continue; // skip active lock
+                    }
+                    add_unrecognized!(file: path);
+                }
+                (2, false) => {
+                    match path.extension().and_then(OsStr::to_str) {
+                        // mod or stats file
+                        None | Some("stats") => {
+                            cache_files.insert(path, entry);
+                        }
 
-            let mut cache_files = HashMap::new();
-            for entry in it {
-                // read_dir() returns an iterator over results - in case some of them are errors
-                // we don't know their names, so we can't delete them. We don't want to delete
-                // the whole directory with good entries too, so we just ignore the erroneous entries.
-                let entry = unwrap_or!(
-                    entry,
-                    continue,
-                    "Failed to read a cache dir entry (NOT deleting it, it still occupies space)"
-                );
-                let path = entry.path();
-                match (level, path.is_dir()) {
-                    (0..=1, true) => enter_dir(vec, &path, level + 1, cache_config),
-                    (0..=1, false) => {
-                        if level == 0
-                            && path.file_stem() == Some(OsStr::new(".cleanup"))
-                                && path.extension().is_some()
-                                // assume it's cleanup lock
+                        Some(ext) => {
+                            // check if valid lock
+                            let recognized = ext.starts_with("wip-")
                                 && !is_fs_lock_expired(
                                     Some(&entry),
                                     &path,
-                                    cache_config.cleanup_interval(),
+                                    cache_config.optimizing_compression_task_timeout(),
                                     cache_config.allowed_clock_drift_for_files_from_future(),
-                                )
-                        {
-                            continue; // skip active lock
-                        }
-                        add_unrecognized!(file: path);
-                    }
-                    (2, false) => {
-                        match path.extension().and_then(OsStr::to_str) {
-                            // mod or stats file
-                            None | Some("stats") => {
-                                cache_files.insert(path, entry);
-                            }
+                                );
 
-                            Some(ext) => {
-                                // check if valid lock
-                                let recognized = ext.starts_with("wip-")
-                                    && !is_fs_lock_expired(
-                                        Some(&entry),
-                                        &path,
-                                        cache_config.optimizing_compression_task_timeout(),
-                                        cache_config.allowed_clock_drift_for_files_from_future(),
-                                    );
-
-                                if !recognized {
-                                    add_unrecognized!(file: path);
-                                }
+                            if !recognized {
+                                add_unrecognized!(file: path);
                             }
                         }
                     }
-                    (_, is_dir) => add_unrecognized!(is_dir, path),
                 }
+                (_, is_dir) => add_unrecognized!(is_dir, path),
             }
+        }
 
-            // associate module with its stats & handle them
-            // assumption: just mods and stats
-            for (path, entry) in cache_files.iter() {
-                let path_buf: PathBuf;
-                let (mod_, stats_, is_mod) = match path.extension() {
-                    Some(_) => {
-                        path_buf = path.with_extension("");
-                        (
-                            cache_files.get(&path_buf).map(|v| (&path_buf, v)),
-                            Some((path, entry)),
-                            false,
-                        )
-                    }
-                    None => {
-                        path_buf = path.with_extension("stats");
-                        (
-                            Some((path, entry)),
-                            cache_files.get(&path_buf).map(|v| (&path_buf, v)),
-                            true,
-                        )
-                    }
-                };
-
-                // construct a cache entry
-                match (mod_, stats_, is_mod) {
-                    (Some((mod_path, mod_entry)), Some((stats_path, stats_entry)), true) => {
-                        let mod_metadata = unwrap_or!(
-                            mod_entry.metadata(),
-                            add_unrecognized_and!([file: stats_path, file: mod_path], continue),
-                            "Failed to get metadata, deleting BOTH module cache and stats files",
-                            mod_path
-                        );
-                        let stats_mtime = unwrap_or!(
-                            stats_entry.metadata().and_then(|m| m.modified()),
-                            add_unrecognized_and!(
-                                [file: stats_path],
-                                unwrap_or!(
-                                    mod_metadata.modified(),
-                                    add_unrecognized_and!(
-                                        [file: stats_path, file: mod_path],
-                                        continue
-                                    ),
-                                    "Failed to get mtime, deleting BOTH module cache and stats \
-                                     files",
-                                    mod_path
-                                )
-                            ),
-                            "Failed to get metadata/mtime, deleting the file",
-                            stats_path
-                        );
-                        // .into() called for the SystemTimeStub if cfg(test)
-                        vec.push(CacheEntry::Recognized {
-                            path: mod_path.to_path_buf(),
-                            mtime: stats_mtime.into(),
-                            size: mod_metadata.len(),
-                        })
-                    }
-                    (Some(_), Some(_), false) => (), // was or will be handled by previous branch
-                    (Some((mod_path, mod_entry)), None, _) => {
-                        let (mod_metadata, mod_mtime) = unwrap_or!(
-                            mod_entry
-                                .metadata()
-                                .and_then(|md| md.modified().map(|mt| (md, mt))),
-                            add_unrecognized_and!([file: mod_path], continue),
-                            "Failed to get metadata/mtime, deleting the file",
-                            mod_path
-                        );
-                        // .into() called for the SystemTimeStub if cfg(test)
-                        vec.push(CacheEntry::Recognized {
-                            path: mod_path.to_path_buf(),
-                            mtime: mod_mtime.into(),
-                            size: mod_metadata.len(),
-                        })
-                    }
-                    (None, Some((stats_path, _stats_entry)), _) => {
-                        debug!("Found orphaned stats file: {}", stats_path.display());
-                        add_unrecognized!(file: stats_path);
-                    }
-                    _ => unreachable!(),
+        // associate module with its stats & handle them
+        // assumption: just mods and stats
+        for (path, entry) in cache_files.iter() {
+            let path_buf: PathBuf;
+            let (mod_, stats_, is_mod) = match path.extension() {
+                Some(_) => {
+                    path_buf = path.with_extension("");
+                    (
+                        cache_files.get(&path_buf).map(|v| (&path_buf, v)),
+                        Some((path, entry)),
+                        false,
+                    )
+                }
+                None => {
+                    path_buf = path.with_extension("stats");
+                    (
+                        Some((path, entry)),
+                        cache_files.get(&path_buf).map(|v| (&path_buf, v)),
+                        true,
+                    )
+                }
+            };
+
+            // construct a cache entry
+            match (mod_, stats_, is_mod) {
+                (Some((mod_path, mod_entry)), Some((stats_path, stats_entry)), true) => {
+                    let mod_metadata = unwrap_or!(
+                        mod_entry.metadata(),
+                        add_unrecognized_and!([file: stats_path, file: mod_path], continue),
+                        "Failed to get metadata, deleting BOTH module cache and stats files",
+                        mod_path
+                    );
+                    let stats_mtime = unwrap_or!(
+                        stats_entry.metadata().and_then(|m| m.modified()),
+                        add_unrecognized_and!(
+                            [file: stats_path],
+                            unwrap_or!(
+                                mod_metadata.modified(),
+                                add_unrecognized_and!(
+                                    [file: stats_path, file: mod_path],
+                                    continue
+                                ),
+                                "Failed to get mtime, deleting BOTH module cache and stats \
+                                 files",
+                                mod_path
+                            )
+                        ),
+                        "Failed to get metadata/mtime, deleting the file",
+                        stats_path
+                    );
+                    // .into() called for the SystemTimeStub if cfg(test)
+                    vec.push(CacheEntry::Recognized {
+                        path: mod_path.to_path_buf(),
+                        mtime: stats_mtime.into(),
+                        size: mod_metadata.len(),
+                    })
                 }
+                (Some(_), Some(_), false) => (), // was or will be handled by previous branch
+                (Some((mod_path, mod_entry)), None, _) => {
+                    let (mod_metadata, mod_mtime) = unwrap_or!(
+                        mod_entry
+                            .metadata()
+                            .and_then(|md| md.modified().map(|mt| (md, mt))),
+                        add_unrecognized_and!([file: mod_path], continue),
+                        "Failed to get metadata/mtime, deleting the file",
+                        mod_path
+                    );
+                    // .into() called for the SystemTimeStub if cfg(test)
+                    vec.push(CacheEntry::Recognized {
+                        path: mod_path.to_path_buf(),
+                        mtime: mod_mtime.into(),
+                        size: mod_metadata.len(),
+                    })
+                }
+                (None, Some((stats_path, _stats_entry)), _) => {
+                    debug!("Found orphaned stats file: {}", stats_path.display());
+                    add_unrecognized!(file: stats_path);
+                }
+                _ => unreachable!(),
             }
         }
-
-        let mut vec = Vec::new();
-        enter_dir(
-            &mut vec,
-            self.cache_config.directory(),
-            0,
-            &self.cache_config,
-        );
-        vec
     }
+
+    let mut vec = Vec::new();
+    enter_dir(&mut vec, cache_config.directory(), 0, cache_config);
+    vec
 }
 
-fn read_stats_file(path: &Path) -> Option<ModuleCacheStatistics> {
+pub(super) fn read_stats_file(path: &Path) -> Option<ModuleCacheStatistics> {
     fs::read(path)
         .map_err(|err| {
             trace!(
@@ -743,6 +743,55 @@ fn test_on_update_cleanup_self_lock() {
     }
 }
 
+#[test]
+fn test_on_statistics_lock() {
+    let (_tempdir, cache_dir, config_path) = test_prolog();
+    let cache_config = load_config!(
+        config_path,
+        "[cache]\n\
+         enabled = true\n\
+         directory = {cache_dir}",
+        cache_dir
+    );
+    assert!(cache_config.enabled());
+    let worker = Worker::start_new(&cache_config);
+    let hit = CacheStatistics { hits: 1, misses: 0 };
+    let miss = CacheStatistics { hits: 0, misses: 1 };
+
+    worker.on_cache_statistics_async(hit);
+    worker.on_cache_statistics_async(miss);
+    worker.wait_for_all_events_handled();
+    assert_eq!(worker.events_dropped(), 0);
+    let expected = CacheStatistics { hits: 1, misses: 1 };
+    assert_eq!(read_cache_statistics(&cache_dir), expected);
+    assert!(!cache_dir
+        .join(format!("statistics.wip-{}", process::id()))
+        .exists());
+
+    // While another process holds the lock, the counters are kept until the
+    // next event.
+    let lock_file = cache_dir.join("statistics.wip-lock");
+    create_file_with_mtime(&lock_file, "", "past", &Duration::from_secs(1));
+    worker.on_cache_statistics_async(hit);
+    worker.wait_for_all_events_handled();
+    assert_eq!(read_cache_statistics(&cache_dir), expected);
+
+    fs::remove_file(&lock_file).expect("Failed to remove lock file");
+    worker.on_cache_statistics_async(miss);
+    worker.wait_for_all_events_handled();
+    let expected = CacheStatistics { hits: 2, misses: 2 };
+    assert_eq!(read_cache_statistics(&cache_dir), expected);
+
+    // The counters left pending are written when the worker is dropped.
+    create_file_with_mtime(&lock_file, "", "past", &Duration::from_secs(1));
+    worker.on_cache_statistics_async(hit);
+    worker.wait_for_all_events_handled();
+    fs::remove_file(&lock_file).expect("Failed to remove lock file");
+    drop(worker);
+    let expected = CacheStatistics { hits: 3, misses: 2 };
+    assert_eq!(read_cache_statistics(&cache_dir), expected);
+}
+
 fn create_file_with_mtime(filename: &Path, contents: &str, offset_sign: &str, offset: &Duration) {
     fs::write(filename, contents).expect("Failed to create a file");
     let mtime = match offset_sign {
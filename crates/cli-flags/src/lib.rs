@@ -228,10 +228,26 @@ wasmtime_option_group! {
         pub common: Option<bool>,
         /// Enable suport for WASI neural network API (experimental)
         pub nn: Option<bool>,
-        /// Enable suport for WASI threading API (experimental)
+        /// Enable suport for WASI threading API (experimental, core modules
+        /// only)
         pub threads: Option<bool>,
+        /// The maximum number of wasi-threads which may be running at once;
+        /// further spawns fail until one of them exits (unlimited by default)
+        pub max_threads: Option<usize>,
         /// Enable suport for WASI HTTP API (experimental)
         pub http: Option<bool>,
+        /// Enable support for WASI key-value store API, backed by an in-memory
+        /// store (experimental)
+        pub keyvalue: Option<bool>,
+        /// Enable support for WASI logging API (experimental)
+        ///
+        /// Guest messages are emitted through Wasmtime's own logging under the
+        /// `wasi_logging` target, e.g. `WASMTIME_LOG=wasi_logging=info`.
+        pub logging: Option<bool>,
+        /// Enable support for WASI runtime configuration API (experimental)
+        ///
+        /// This is implied by `--config` and `--config-file`.
+        pub runtime_config: Option<bool>,
         /// Inherit environment variables and file descriptors following the
         /// systemd listen fd specification (UNIX only)
         pub listenfd: Option<bool>,
@@ -254,6 +270,13 @@ wasmtime_option_group! {
         /// available is dependent on the backends implemented in the
         /// `wasmtime_wasi_nn` crate.
         pub nn_graph: Vec<WasiNnGraph>,
+        /// Load machine learning graphs for wasi-nn from a manifest file.
+        ///
+        /// The manifest lists models by name along with their encoding,
+        /// execution target and files. Models are only loaded once requested by
+        /// name and the least recently used ones are unloaded when too many are
+        /// loaded. This cannot be combined with `nn-graph`.
+        pub nn_manifest: Option<String>,
         /// Flag for WASI preview2 to inherit the host's network within the
         /// guest so it has full access to all addresses/ports/etc.
         pub inherit_network: Option<bool>,
@@ -360,6 +383,23 @@ impl CommonOptions {
         self.wasi.configure_with(&self.wasi_raw);
     }
 
+    /// Inserts the options of `defaults` before the ones of `self`, so that
+    /// they only take effect where `self` doesn't specify them.
+    ///
+    /// This must be called before any options are used.
+    pub fn prepend(&mut self, defaults: CommonOptions) {
+        assert!(!self.configured && !defaults.configured);
+        fn prepend<T>(dst: &mut Vec<T>, mut defaults: Vec<T>) {
+            defaults.append(dst);
+            *dst = defaults;
+        }
+        prepend(&mut self.opts_raw, defaults.opts_raw);
+        prepend(&mut self.codegen_raw, defaults.codegen_raw);
+        prepend(&mut self.debug_raw, defaults.debug_raw);
+        prepend(&mut self.wasm_raw, defaults.wasm_raw);
+        prepend(&mut self.wasi_raw, defaults.wasi_raw);
+    }
+
     pub fn init_logging(&mut self) -> Result<()> {
         self.configure();
         if self.debug.logging == Some(false) {
@@ -185,13 +185,7 @@ impl TrapEncodingBuilder {
 /// `TrapEncodingBuilder` above. Additionally the `offset` should be a relative
 /// offset within the text section of the compilation image.
 pub fn lookup_trap_code(section: &[u8], offset: usize) -> Option<Trap> {
-    let mut section = Bytes(section);
-    // NB: this matches the encoding written by `append_to` above.
-    let count = section.read::<U32Bytes<LittleEndian>>().ok()?;
-    let count = usize::try_from(count.get(LittleEndian)).ok()?;
-    let (offsets, traps) =
-        object::slice_from_bytes::<U32Bytes<LittleEndian>>(section.0, count).ok()?;
-    debug_assert_eq!(traps.len(), count);
+    let (offsets, traps) = parse(section)?;
 
     // The `offsets` table is sorted in the trap section so perform a binary
     // search of the contents of this section to find whether `offset` is an
@@ -208,7 +202,36 @@ pub fn lookup_trap_code(section: &[u8], offset: usize) -> Option<Trap> {
         .ok()?;
     debug_assert!(index < traps.len());
     let trap = *traps.get(index)?;
+    decode_trap(trap)
+}
+
+/// Iterate over the trap information contained in the given trap section.
+///
+/// The `section` provided is expected to have been built by
+/// `TrapEncodingBuilder` above. The yielded offsets are relative to the start
+/// of the text section of the compilation image.
+pub fn iterate_traps(section: &[u8]) -> Option<impl Iterator<Item = (u32, Trap)> + '_> {
+    let (offsets, traps) = parse(section)?;
+    Some(
+        offsets
+            .iter()
+            .zip(traps)
+            .filter_map(|(offset, trap)| Some((offset.get(LittleEndian), decode_trap(*trap)?))),
+    )
+}
+
+fn parse(section: &[u8]) -> Option<(&[U32Bytes<LittleEndian>], &[u8])> {
+    let mut section = Bytes(section);
+    // NB: this matches the encoding written by `append_to` above.
+    let count = section.read::<U32Bytes<LittleEndian>>().ok()?;
+    let count = usize::try_from(count.get(LittleEndian)).ok()?;
+    let (offsets, traps) =
+        object::slice_from_bytes::<U32Bytes<LittleEndian>>(section.0, count).ok()?;
+    debug_assert_eq!(traps.len(), count);
+    Some((offsets, traps))
+}
 
+fn decode_trap(trap: u8) -> Option<Trap> {
     // FIXME: this could use some sort of derive-like thing to avoid having to
     // deduplicate the names here.
     //
@@ -1,13 +1,23 @@
 /*** State *********************************************************************/
 
 class State {
-  constructor(wat, asm) {
-    this.wat = wat;
-    this.asm = asm;
+  constructor(modules) {
+    // Each module has a `name` along with its `wat` and `asm`; there is more
+    // than one when exploring a component.
+    this.modules = modules;
+    this.current = 0;
+  }
+
+  get wat() {
+    return this.modules[this.current].wat;
+  }
+
+  get asm() {
+    return this.modules[this.current].asm;
   }
 }
 
-const state = window.STATE = new State(window.WAT, window.ASM);
+const state = window.STATE = new State(window.MODULES);
 
 /*** Hues for Offsets **********************************************************/
 
@@ -189,50 +199,90 @@ const renderInst = (mnemonic, operands) => {
   }
 };
 
-// Render the ASM.
-
-let nthFunc = 0;
-for (const func of state.asm.functions) {
-  const funcElem = document.createElement("div");
-
-  const funcHeader = document.createElement("h3");
-  funcHeader.textContent = `Defined Function ${nthFunc}`;
-  funcElem.appendChild(funcHeader);
-
-  const bodyElem = document.createElement("pre");
-  for (const inst of func.instructions) {
-    const instElem = document.createElement("span");
-    instElem.textContent = `${renderAddress(inst.address)}    ${renderBytes(inst.bytes)}    ${renderInst(inst.mnemonic, inst.operands)}\n`;
-    if (inst.wasm_offset != null) {
-      instElem.setAttribute("data-wasm-offset", inst.wasm_offset);
-      const hue = hueForOffset(inst.wasm_offset);
-      instElem.style.backgroundColor = `hsl(${hue} 50% 90%)`;
-      instElem.addEventListener("mouseenter", onMouseEnter);
-      instElem.addEventListener("mouseleave", onMouseLeave);
-      addAsmElem(inst.wasm_offset, instElem);
+const renderAsm = () => {
+  let nthFunc = 0;
+  for (const func of state.asm.functions) {
+    const funcElem = document.createElement("div");
+
+    const funcHeader = document.createElement("h3");
+    funcHeader.textContent = `Defined Function ${nthFunc}`;
+    funcElem.appendChild(funcHeader);
+
+    const bodyElem = document.createElement("pre");
+    for (const inst of func.instructions) {
+      const instElem = document.createElement("span");
+      instElem.textContent = `${renderAddress(inst.address)}    ${renderBytes(inst.bytes)}    ${renderInst(inst.mnemonic, inst.operands)}\n`;
+      if (inst.wasm_offset != null) {
+        instElem.setAttribute("data-wasm-offset", inst.wasm_offset);
+        const hue = hueForOffset(inst.wasm_offset);
+        instElem.style.backgroundColor = `hsl(${hue} 50% 90%)`;
+        instElem.addEventListener("mouseenter", onMouseEnter);
+        instElem.addEventListener("mouseleave", onMouseLeave);
+        addAsmElem(inst.wasm_offset, instElem);
+      }
+      bodyElem.appendChild(instElem);
     }
-    bodyElem.appendChild(instElem);
-  }
-  funcElem.appendChild(bodyElem);
+    funcElem.appendChild(bodyElem);
 
-  asmElem.appendChild(funcElem);
-  nthFunc++;
-}
+    asmElem.appendChild(funcElem);
+    nthFunc++;
+  }
+};
 
-// Render the WAT.
-
-for (const chunk of state.wat.chunks) {
-  const chunkElem = document.createElement("span");
-  if (chunk.wasm_offset != null) {
-    chunkElem.dataset.wasmOffset = chunk.wasm_offset;
-    const hue = existingHueForOffset(chunk.wasm_offset);
-    if (hue) {
-      chunkElem.style.backgroundColor = `hsl(${hue} 50% 95%)`;
-      chunkElem.addEventListener("mouseenter", onMouseEnter);
-      chunkElem.addEventListener("mouseleave", onMouseLeave);
-      addWatElem(chunk.wasm_offset, chunkElem);
+const renderWat = () => {
+  for (const chunk of state.wat.chunks) {
+    const chunkElem = document.createElement("span");
+    if (chunk.wasm_offset != null) {
+      chunkElem.dataset.wasmOffset = chunk.wasm_offset;
+      const hue = existingHueForOffset(chunk.wasm_offset);
+      if (hue) {
+        chunkElem.style.backgroundColor = `hsl(${hue} 50% 95%)`;
+        chunkElem.addEventListener("mouseenter", onMouseEnter);
+        chunkElem.addEventListener("mouseleave", onMouseLeave);
+        addWatElem(chunk.wasm_offset, chunkElem);
+      }
     }
+    chunkElem.textContent = chunk.wat;
+    watElem.appendChild(chunkElem);
   }
-  chunkElem.textContent = chunk.wat;
-  watElem.appendChild(chunkElem);
+};
+
+// Render the module at the given index, replacing whichever module was
+// rendered before.
+const render = index => {
+  state.current = index;
+
+  watByOffset.clear();
+  asmByOffset.clear();
+  anyByOffset.clear();
+  watElem.replaceChildren();
+  asmElem.replaceChildren();
+
+  for (const button of modulesElem.children) {
+    button.classList.toggle("current", button.dataset.index == index);
+  }
+
+  // The ASM has to be rendered first since WAT chunks are only highlighted
+  // when they have a hue assigned by their instructions.
+  renderAsm();
+  renderWat();
+  asmElem.scrollTop = 0;
+  watElem.scrollTop = 0;
+};
+
+// Render the navigation between modules, which is only useful for components.
+
+const modulesElem = document.getElementById("modules");
+if (state.modules.length > 1) {
+  state.modules.forEach((module, index) => {
+    const button = document.createElement("button");
+    button.dataset.index = index;
+    button.textContent = module.name;
+    button.addEventListener("click", () => render(index));
+    modulesElem.appendChild(button);
+  });
+} else {
+  modulesElem.style.display = "none";
 }
+
+render(0);
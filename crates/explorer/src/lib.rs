@@ -1,7 +1,9 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use capstone::arch::BuildsCapstone;
 use serde_derive::Serialize;
 use std::{io::Write, str::FromStr};
+use wasmtime_environ::component::{ComponentTypesBuilder, Translator};
+use wasmtime_environ::{ScopeVec, Tunables};
 
 pub fn generate(
     config: &wasmtime::Config,
@@ -9,15 +11,8 @@ pub fn generate(
     wasm: &[u8],
     dest: &mut dyn Write,
 ) -> Result<()> {
-    let target = match target {
-        None => target_lexicon::Triple::host(),
-        Some(target) => target_lexicon::Triple::from_str(target)?,
-    };
-
-    let wat = annotate_wat(wasm)?;
-    let wat_json = serde_json::to_string(&wat)?;
-    let asm = annotate_asm(config, &target, wasm)?;
-    let asm_json = serde_json::to_string(&asm)?;
+    let exploration = Exploration::new(config, target, wasm)?;
+    let modules_json = serde_json::to_string(&exploration.modules())?;
 
     let index_css = include_str!("./index.css");
     let index_js = include_str!("./index.js");
@@ -33,12 +28,14 @@ pub fn generate(
       {index_css}
     </style>
   </head>
-  <body class="hbox">
-    <pre id="wat"></pre>
-    <div id="asm"></div>
+  <body class="vbox">
+    <nav id="modules"></nav>
+    <div class="hbox main">
+      <pre id="wat"></pre>
+      <div id="asm"></div>
+    </div>
     <script>
-      window.WAT = {wat_json};
-      window.ASM = {asm_json};
+      window.MODULES = {modules_json};
     </script>
     <script>
       {index_js}
@@ -50,6 +47,168 @@ pub fn generate(
     Ok(())
 }
 
+/// Like [`generate`], but writes the mapping from wasm offsets to machine code
+/// as JSON instead of an HTML page.
+///
+/// The output is an object with a `target` field, the target triple the module
+/// was compiled for, a `wat` field, the chunks of the text format of the module
+/// along with the wasm offset each one starts at, and an `asm` field, the
+/// instructions of each compiled function along with the wasm offset each one
+/// was compiled from.
+///
+/// For a component, the `wat` and `asm` fields are instead found in each
+/// element of a `modules` field, one for each core module of the component
+/// along with its `name` and whether it is an `adapter` module generated to
+/// fuse the lifting and lowering of component functions.
+pub fn generate_json(
+    config: &wasmtime::Config,
+    target: Option<&str>,
+    wasm: &[u8],
+    dest: &mut dyn Write,
+) -> Result<()> {
+    let exploration = Exploration::new(config, target, wasm)?;
+    serde_json::to_writer_pretty(&mut *dest, &exploration)?;
+    writeln!(dest)?;
+    Ok(())
+}
+
+#[derive(Serialize, Debug)]
+struct Exploration {
+    target: String,
+    #[serde(flatten)]
+    module: Option<ExploredModule>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    modules: Option<Vec<ComponentModule>>,
+}
+
+#[derive(Serialize, Debug)]
+struct ExploredModule {
+    wat: AnnotatedWat,
+    asm: AnnotatedAsm,
+}
+
+#[derive(Serialize, Debug)]
+struct ComponentModule {
+    name: String,
+    adapter: bool,
+    #[serde(flatten)]
+    module: ExploredModule,
+}
+
+impl Exploration {
+    fn new(config: &wasmtime::Config, target: Option<&str>, wasm: &[u8]) -> Result<Exploration> {
+        let target = match target {
+            None => target_lexicon::Triple::host(),
+            Some(target) => target_lexicon::Triple::from_str(target)?,
+        };
+
+        let (module, modules) = if wasmparser::Parser::is_component(wasm) {
+            (None, Some(explore_component(config, &target, wasm)?))
+        } else {
+            (Some(explore_module(config, &target, wasm)?), None)
+        };
+        Ok(Exploration {
+            target: target.to_string(),
+            module,
+            modules,
+        })
+    }
+
+    /// Returns each module to display in the HTML page.
+    fn modules<'a>(&'a self) -> Vec<PageModule<'a>> {
+        let page_module = |name: &'a str, module: &'a ExploredModule| PageModule {
+            name,
+            wat: &module.wat,
+            asm: &module.asm,
+        };
+        match (&self.module, &self.modules) {
+            (Some(module), _) => vec![page_module("module", module)],
+            (None, Some(modules)) => modules
+                .iter()
+                .map(|m| page_module(&m.name, &m.module))
+                .collect(),
+            (None, None) => Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct PageModule<'a> {
+    name: &'a str,
+    wat: &'a AnnotatedWat,
+    asm: &'a AnnotatedAsm,
+}
+
+fn explore_module(
+    config: &wasmtime::Config,
+    target: &target_lexicon::Triple,
+    wasm: &[u8],
+) -> Result<ExploredModule> {
+    Ok(ExploredModule {
+        wat: annotate_wat(wasm)?,
+        asm: annotate_asm(config, target, wasm)?,
+    })
+}
+
+/// Explores each core module of a component, both those defined in the
+/// component and the adapter modules that Wasmtime generates for it.
+///
+/// Each module is compiled on its own, which produces the same code as when it
+/// is compiled as part of the component but keeps the addresses and wasm
+/// offsets relative to the module.
+fn explore_component(
+    config: &wasmtime::Config,
+    target: &target_lexicon::Triple,
+    wasm: &[u8],
+) -> Result<Vec<ComponentModule>> {
+    // Only the core modules are needed here: they are validated again when
+    // compiled with `config` below.
+    let tunables = Tunables::default();
+    let mut validator = wasmparser::Validator::new_with_features(wasmparser::WasmFeatures::all());
+    let mut types = ComponentTypesBuilder::default();
+    let scope = ScopeVec::new();
+    let (_, translations) = Translator::new(&tunables, &mut validator, &mut types, &scope)
+        .translate(wasm)
+        .context("failed to parse WebAssembly component")?;
+
+    // Adapter modules use multiple memories to copy values between the
+    // memories of the modules they adapt.
+    let mut adapter_config = config.clone();
+    adapter_config.wasm_multi_memory(true);
+
+    let component = wasm.as_ptr_range();
+    let mut modules = Vec::new();
+    let (mut core_modules, mut adapter_modules) = (0, 0);
+    for (_, translation) in translations {
+        // The core modules defined in the component are slices of it while
+        // adapter modules are generated during translation.
+        let adapter = !component.contains(&translation.wasm.as_ptr());
+        let (name, config) = if adapter {
+            adapter_modules += 1;
+            (
+                format!("adapter module {}", adapter_modules - 1),
+                &adapter_config,
+            )
+        } else {
+            let offset = translation.wasm.as_ptr() as usize - wasm.as_ptr() as usize;
+            core_modules += 1;
+            let mut name = format!("core module {} (offset {offset:#x})", core_modules - 1);
+            if let Some(module_name) = &translation.module.name {
+                name = format!("{name}: {module_name}");
+            }
+            (name, config)
+        };
+        let module = explore_module(config, target, translation.wasm)
+            .with_context(|| format!("failed to explore {name}"))?;
+        modules.push(ComponentModule {
+            name,
+            adapter,
+            module,
+        });
+    }
+    Ok(modules)
+}
+
 #[derive(Serialize, Clone, Copy, Debug)]
 struct WasmOffset(u32);
 
@@ -69,6 +69,20 @@ pub struct CompiledModuleInfo {
     meta: Metadata,
 }
 
+impl CompiledModuleInfo {
+    /// Returns the stack map information for all functions defined in this
+    /// module.
+    ///
+    /// Unlike [`CompiledModule::stack_maps`] this doesn't require the compiled
+    /// code to be loaded: the location of each function's code is returned
+    /// instead.
+    pub fn stack_maps(&self) -> impl Iterator<Item = (FunctionLoc, &[StackMapInformation])> {
+        self.funcs
+            .values()
+            .map(|f| (f.wasm_func_loc, &f.wasm_func_info.stack_maps[..]))
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 struct FunctionName {
     idx: FuncIndex,
@@ -571,6 +571,13 @@ impl SharedMemory {
         Ok(self.0.spot.notify(ptr, count))
     }
 
+    /// Wakes up all threads blocked in `memory.atomic.wait*` on this shared
+    /// memory, whichever address they wait on.
+    pub fn atomic_notify_all(&self) -> u32 {
+        log::trace!("memory.atomic.notify_all()");
+        self.0.spot.notify_all()
+    }
+
     /// Implementation of `memory.atomic.wait32` for this shared memory.
     pub fn atomic_wait32(
         &self,
@@ -217,6 +217,28 @@ impl ParkingSpot {
         unparked
     }
 
+    /// Notify all threads that are blocked on any address.
+    ///
+    /// Returns the number of threads that were actually unparked.
+    pub fn notify_all(&self) -> u32 {
+        let mut unparked = 0;
+        let mut inner = self
+            .inner
+            .lock()
+            .expect("failed to lock inner parking table");
+        for spot in inner.values_mut() {
+            unsafe {
+                while let Some(mut head) = spot.pop() {
+                    let head = head.as_mut();
+                    head.notified = true;
+                    head.thread.unpark();
+                    unparked += 1;
+                }
+            }
+        }
+        unparked
+    }
+
     fn with_lot<T, F: FnMut(&mut Spot)>(&self, addr: &T, mut f: F) {
         let key = addr as *const _ as u64;
         let mut inner = self
@@ -304,6 +326,7 @@ impl Spot {
 #[cfg(test)]
 mod tests {
     use super::{ParkingSpot, Waiter};
+    use crate::WaitResult;
     use std::sync::atomic::{AtomicU64, Ordering};
     use std::thread;
     use std::time::{Duration, Instant};
@@ -357,6 +380,30 @@ mod tests {
         });
     }
 
+    #[test]
+    fn notify_all() {
+        let parking_spot = ParkingSpot::default();
+        let a = AtomicU64::new(0);
+        let b = AtomicU64::new(0);
+
+        thread::scope(|s| {
+            let wait = |atomic| {
+                let parking_spot = &parking_spot;
+                s.spawn(move || parking_spot.wait64(atomic, 0, None, &mut Waiter::default()))
+            };
+            let threads = [wait(&a), wait(&a), wait(&b)];
+
+            let mut unparked = 0;
+            while unparked < threads.len() {
+                unparked += parking_spot.notify_all() as usize;
+                thread::sleep(Duration::from_millis(1));
+            }
+            for thread in threads {
+                assert_eq!(thread.join().unwrap(), WaitResult::Ok);
+            }
+        });
+    }
+
     mod parking_lot {
         // This is a modified version of the parking_lot_core tests,
         // which are licensed under the MIT and Apache 2.0 licenses.
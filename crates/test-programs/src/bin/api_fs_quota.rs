@@ -0,0 +1,39 @@
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{self, Read, Write},
+};
+
+// The host allows 2 files to be created, 10 bytes to be written and 8 bytes to
+// be read.
+fn is_quota_error(e: &io::Error) -> bool {
+    e.raw_os_error() == Some(wasi::ERRNO_DQUOT.raw().into())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    fs::create_dir("sub")?;
+    // Failed creations don't count against the quota.
+    assert!(fs::create_dir("sub").is_err());
+
+    let mut file = File::create("bar.txt")?;
+    file.write_all(b"0123456789")?;
+    let e = file.write_all(b"!").unwrap_err();
+    assert!(is_quota_error(&e), "{e}");
+    drop(file);
+
+    // Opening an existing file with `CREATE` doesn't create anything.
+    File::options().write(true).create(true).open("bar.txt")?;
+    let e = File::create("baz.txt").unwrap_err();
+    assert!(is_quota_error(&e), "{e}");
+    let e = fs::create_dir("sub2").unwrap_err();
+    assert!(is_quota_error(&e), "{e}");
+
+    let mut file = File::open("bar.txt")?;
+    let mut buf = [0; 8];
+    file.read_exact(&mut buf)?;
+    assert_eq!(&buf, b"01234567");
+    let e = file.read(&mut buf).unwrap_err();
+    assert!(is_quota_error(&e), "{e}");
+
+    Ok(())
+}
@@ -0,0 +1,679 @@
+//! A connection-pooling client used by [`default_send_request`] to send
+//! outgoing requests.
+//!
+//! [`default_send_request`]: crate::types::default_send_request
+
+use crate::io::TokioIo;
+use crate::{
+    bindings::http::types::ErrorCode,
+    body::HyperOutgoingBody,
+    dns_error, hyper_request_error,
+    types::{IncomingResponseInternal, OutgoingRequest},
+};
+use http_body_util::BodyExt;
+use hyper::client::conn::{http1, http2};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+use wasmtime_wasi::preview2;
+
+/// Configuration for an [`HttpClient`].
+#[derive(Clone, Debug)]
+pub struct HttpClientConfig {
+    /// The maximum number of idle HTTP/1.1 connections kept open for each
+    /// authority. Connections beyond this limit are closed once their
+    /// response has been read.
+    pub max_idle_per_authority: usize,
+    /// How long an idle connection is kept before it's closed instead of
+    /// being reused.
+    pub idle_timeout: Duration,
+    /// Whether to offer HTTP/2 through ALPN when connecting over TLS. A
+    /// single HTTP/2 connection is shared by all concurrent requests to the
+    /// same authority.
+    pub http2: bool,
+    /// Whether servers presenting a certificate issued by one of the
+    /// publicly trusted roots in the `webpki-roots` bundle are trusted.
+    pub webpki_roots: bool,
+    /// Additional DER-encoded root certificates to trust, for example a
+    /// private CA used by internal services.
+    pub root_certificates: Vec<Vec<u8>>,
+    /// A certificate to present to servers which request client
+    /// authentication.
+    pub client_certificate: Option<ClientCertificate>,
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        Self {
+            max_idle_per_authority: 32,
+            idle_timeout: Duration::from_secs(90),
+            http2: true,
+            webpki_roots: true,
+            root_certificates: Vec::new(),
+            client_certificate: None,
+        }
+    }
+}
+
+/// A client certificate used for mutual TLS.
+#[derive(Clone)]
+pub struct ClientCertificate {
+    /// The DER-encoded certificate chain, starting with the end-entity
+    /// certificate.
+    pub chain: Vec<Vec<u8>>,
+    /// The DER-encoded PKCS#8, PKCS#1 or SEC1 private key for the end-entity
+    /// certificate.
+    pub private_key: Vec<u8>,
+}
+
+impl std::fmt::Debug for ClientCertificate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClientCertificate")
+            .field("chain", &self.chain.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A client which reuses connections across outgoing requests.
+///
+/// Clones of a client share the same connection pool, so a single client can
+/// be shared between all stores which talk to the same set of servers.
+#[derive(Clone)]
+pub struct HttpClient {
+    inner: Arc<ClientInner>,
+}
+
+struct ClientInner {
+    config: HttpClientConfig,
+    #[cfg(not(any(target_arch = "riscv64", target_arch = "s390x")))]
+    tls: Arc<rustls::ClientConfig>,
+    http1: Mutex<HashMap<PoolKey, Vec<IdleConnection>>>,
+    http2: Mutex<HashMap<PoolKey, http2::SendRequest<HyperOutgoingBody>>>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct PoolKey {
+    use_tls: bool,
+    authority: String,
+}
+
+struct IdleConnection {
+    sender: http1::SendRequest<HyperOutgoingBody>,
+    since: Instant,
+}
+
+enum Connection {
+    Http1(http1::SendRequest<HyperOutgoingBody>),
+    Http2(http2::SendRequest<HyperOutgoingBody>),
+}
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        Self::new(HttpClientConfig::default()).expect("default client configuration is valid")
+    }
+}
+
+impl HttpClient {
+    /// Creates a new client with an empty connection pool.
+    ///
+    /// Returns an error if any of the configured certificates or keys are
+    /// invalid.
+    pub fn new(config: HttpClientConfig) -> anyhow::Result<Self> {
+        Ok(Self {
+            inner: Arc::new(ClientInner {
+                #[cfg(not(any(target_arch = "riscv64", target_arch = "s390x")))]
+                tls: Arc::new(tls_config(&config)?),
+                config,
+                http1: Mutex::new(HashMap::new()),
+                http2: Mutex::new(HashMap::new()),
+            }),
+        })
+    }
+
+    /// Returns the configuration this client was created with.
+    pub fn config(&self) -> &HttpClientConfig {
+        &self.inner.config
+    }
+
+    /// Sends `request`, reusing an existing connection to its authority if
+    /// one is available.
+    ///
+    /// A pooled HTTP/1.1 connection may have been closed by the server while
+    /// it was idle. If that's found out before the request could be sent, the
+    /// request is sent again once on a new connection.
+    pub async fn send_request(
+        &self,
+        OutgoingRequest {
+            use_tls,
+            authority,
+            request,
+            connect_timeout,
+            first_byte_timeout,
+            between_bytes_timeout,
+        }: OutgoingRequest,
+    ) -> Result<IncomingResponseInternal, ErrorCode> {
+        let key = PoolKey { use_tls, authority };
+        let (resp, worker) = match self.checkout(&key) {
+            Some(Connection::Http1(sender)) => {
+                let (parts, body) = request.into_parts();
+                let head = request_head(&parts);
+                let body = ReplayableBody::new(body);
+                let request = hyper::Request::from_parts(parts, body.clone().boxed());
+                match self
+                    .send(
+                        key.clone(),
+                        Connection::Http1(sender),
+                        request,
+                        first_byte_timeout,
+                    )
+                    .await
+                {
+                    Err(SendError::Hyper(e)) if body.can_replay(&e, head.method()) => {
+                        tracing::debug!("retrying request on a new connection: {e}");
+                        let request = head.map(|()| body.take());
+                        let connection = self.connect(&key, connect_timeout).await?;
+                        self.send(key, connection, request, first_byte_timeout)
+                            .await?
+                    }
+                    result => result?,
+                }
+            }
+            Some(connection) => {
+                self.send(key, connection, request, first_byte_timeout)
+                    .await?
+            }
+            None => {
+                let connection = self.connect(&key, connect_timeout).await?;
+                self.send(key, connection, request, first_byte_timeout)
+                    .await?
+            }
+        };
+
+        Ok(IncomingResponseInternal {
+            resp: resp.map(|body| body.map_err(hyper_request_error).boxed()),
+            worker: Arc::new(worker),
+            between_bytes_timeout,
+        })
+    }
+
+    async fn send(
+        &self,
+        key: PoolKey,
+        connection: Connection,
+        request: hyper::Request<HyperOutgoingBody>,
+        first_byte_timeout: Duration,
+    ) -> Result<
+        (
+            hyper::Response<hyper::body::Incoming>,
+            preview2::AbortOnDropJoinHandle<()>,
+        ),
+        SendError,
+    > {
+        match connection {
+            Connection::Http1(mut sender) => {
+                let resp = timeout(first_byte_timeout, sender.send_request(request))
+                    .await
+                    .map_err(|_| SendError::Timeout)?
+                    .map_err(SendError::Hyper)?;
+
+                // The connection becomes ready again once the response body
+                // has been read in its entirety, at which point it's returned
+                // to the pool. If the response is dropped before then this
+                // task is aborted, closing the connection.
+                let client = self.clone();
+                let worker = preview2::spawn(async move {
+                    if sender.ready().await.is_ok() {
+                        client.checkin(key, sender);
+                    }
+                });
+                Ok((resp, worker))
+            }
+            Connection::Http2(mut sender) => {
+                sender.ready().await.map_err(SendError::Hyper)?;
+                let resp = timeout(first_byte_timeout, sender.send_request(request))
+                    .await
+                    .map_err(|_| SendError::Timeout)?
+                    .map_err(SendError::Hyper)?;
+
+                // HTTP/2 connections are shared and driven in the background,
+                // so there's nothing to keep alive for this response.
+                Ok((resp, preview2::spawn(async {})))
+            }
+        }
+    }
+
+    fn checkout(&self, key: &PoolKey) -> Option<Connection> {
+        if key.use_tls && self.inner.config.http2 {
+            let mut http2 = self.inner.http2.lock().unwrap();
+            match http2.get(key) {
+                Some(sender) if !sender.is_closed() => {
+                    return Some(Connection::Http2(sender.clone()));
+                }
+                Some(_) => {
+                    http2.remove(key);
+                }
+                None => {}
+            }
+        }
+
+        let mut http1 = self.inner.http1.lock().unwrap();
+        let idle = http1.get_mut(key)?;
+        let idle_timeout = self.inner.config.idle_timeout;
+        let mut result = None;
+        while let Some(conn) = idle.pop() {
+            if conn.since.elapsed() < idle_timeout && conn.sender.is_ready() {
+                result = Some(Connection::Http1(conn.sender));
+                break;
+            }
+        }
+        if idle.is_empty() {
+            http1.remove(key);
+        }
+        result
+    }
+
+    fn checkin(&self, key: PoolKey, sender: http1::SendRequest<HyperOutgoingBody>) {
+        let config = &self.inner.config;
+        let mut http1 = self.inner.http1.lock().unwrap();
+
+        // Take the opportunity to close any connections which have expired
+        // while sitting in the pool.
+        http1.retain(|_, idle| {
+            idle.retain(|c| c.since.elapsed() < config.idle_timeout && !c.sender.is_closed());
+            !idle.is_empty()
+        });
+
+        let idle = http1.entry(key).or_default();
+        if idle.len() < config.max_idle_per_authority {
+            idle.push(IdleConnection {
+                sender,
+                since: Instant::now(),
+            });
+        }
+    }
+
+    async fn connect(
+        &self,
+        key: &PoolKey,
+        connect_timeout: Duration,
+    ) -> Result<Connection, ErrorCode> {
+        let tcp_stream = TcpStream::connect(key.authority.clone())
+            .await
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::AddrNotAvailable => {
+                    dns_error("address not available".to_string(), 0)
+                }
+
+                _ => {
+                    if e.to_string()
+                        .starts_with("failed to lookup address information")
+                    {
+                        dns_error("address not available".to_string(), 0)
+                    } else {
+                        ErrorCode::ConnectionRefused
+                    }
+                }
+            })?;
+
+        if !key.use_tls {
+            return handshake_http1(TokioIo::new(tcp_stream), connect_timeout).await;
+        }
+
+        #[cfg(any(target_arch = "riscv64", target_arch = "s390x"))]
+        {
+            return Err(crate::bindings::http::types::ErrorCode::InternalError(
+                Some("unsupported architecture for SSL".to_string()),
+            ));
+        }
+
+        #[cfg(not(any(target_arch = "riscv64", target_arch = "s390x")))]
+        {
+            let connector = tokio_rustls::TlsConnector::from(self.inner.tls.clone());
+            let mut parts = key.authority.split(":");
+            let host = parts.next().unwrap_or(&key.authority);
+            let domain = rustls::ServerName::try_from(host).map_err(|e| {
+                tracing::warn!("dns lookup error: {e:?}");
+                dns_error("invalid dns name".to_string(), 0)
+            })?;
+            let stream = connector.connect(domain, tcp_stream).await.map_err(|e| {
+                tracing::warn!("tls protocol error: {e:?}");
+                ErrorCode::TlsProtocolError
+            })?;
+
+            if stream.get_ref().1.alpn_protocol() != Some(b"h2") {
+                return handshake_http1(TokioIo::new(stream), connect_timeout).await;
+            }
+
+            let (sender, conn) = timeout(
+                connect_timeout,
+                http2::handshake(TokioExecutor, TokioIo::new(stream)),
+            )
+            .await
+            .map_err(|_| ErrorCode::ConnectionTimeout)?
+            .map_err(hyper_request_error)?;
+            spawn_connection(conn);
+
+            // Requests racing to the same authority may have each opened a
+            // connection; keep whichever was registered first.
+            let sender = self
+                .inner
+                .http2
+                .lock()
+                .unwrap()
+                .entry(key.clone())
+                .or_insert(sender)
+                .clone();
+            Ok(Connection::Http2(sender))
+        }
+    }
+}
+
+/// An error sending a request on a connection.
+enum SendError {
+    Timeout,
+    Hyper(hyper::Error),
+}
+
+impl From<SendError> for ErrorCode {
+    fn from(e: SendError) -> ErrorCode {
+        match e {
+            SendError::Timeout => ErrorCode::ConnectionReadTimeout,
+            SendError::Hyper(e) => hyper_request_error(e),
+        }
+    }
+}
+
+/// Returns a copy of the method, URI, version and headers of a request.
+fn request_head(parts: &http::request::Parts) -> hyper::Request<()> {
+    let mut head = hyper::Request::new(());
+    *head.method_mut() = parts.method.clone();
+    *head.uri_mut() = parts.uri.clone();
+    *head.version_mut() = parts.version;
+    *head.headers_mut() = parts.headers.clone();
+    head
+}
+
+/// A request body which can be taken back to send the request again, as long
+/// as hyper hasn't started reading it.
+#[derive(Clone)]
+struct ReplayableBody(Arc<Mutex<ReplayableState>>);
+
+struct ReplayableState {
+    body: HyperOutgoingBody,
+    started: bool,
+}
+
+impl ReplayableBody {
+    fn new(body: HyperOutgoingBody) -> Self {
+        Self(Arc::new(Mutex::new(ReplayableState {
+            body,
+            started: false,
+        })))
+    }
+
+    /// Returns whether the request which failed with `error` may be sent
+    /// again: either it wasn't sent at all, or it's idempotent and the
+    /// connection was closed before any of its body was read.
+    fn can_replay(&self, error: &hyper::Error, method: &hyper::Method) -> bool {
+        !self.0.lock().unwrap().started
+            && (error.is_canceled() || (error.is_incomplete_message() && method.is_idempotent()))
+    }
+
+    /// Takes the body back, leaving an empty one in its place.
+    fn take(&self) -> HyperOutgoingBody {
+        let empty = http_body_util::Empty::new()
+            .map_err(|_| unreachable!("Infallible error"))
+            .boxed();
+        std::mem::replace(&mut self.0.lock().unwrap().body, empty)
+    }
+}
+
+impl http_body::Body for ReplayableBody {
+    type Data = bytes::Bytes;
+    type Error = ErrorCode;
+
+    fn poll_frame(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Result<http_body::Frame<Self::Data>, Self::Error>>> {
+        let mut state = self.0.lock().unwrap();
+        state.started = true;
+        std::pin::Pin::new(&mut state.body).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.0.lock().unwrap().body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.0.lock().unwrap().body.size_hint()
+    }
+}
+
+async fn handshake_http1<T>(
+    io: TokioIo<T>,
+    connect_timeout: Duration,
+) -> Result<Connection, ErrorCode>
+where
+    T: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + Unpin + 'static,
+{
+    let (sender, conn) = timeout(connect_timeout, http1::handshake(io))
+        .await
+        .map_err(|_| ErrorCode::ConnectionTimeout)?
+        .map_err(hyper_request_error)?;
+    spawn_connection(conn);
+    Ok(Connection::Http1(sender))
+}
+
+/// Drives a connection in the background for as long as any handle to it,
+/// pooled or in use, remains alive.
+fn spawn_connection<F, E>(conn: F)
+where
+    F: Future<Output = Result<(), E>> + Send + 'static,
+    E: std::fmt::Display,
+{
+    preview2::with_ambient_tokio_runtime(|| {
+        tokio::task::spawn(async move {
+            match conn.await {
+                Ok(()) => {}
+                // TODO: shouldn't throw away this error and ideally should
+                // surface somewhere.
+                Err(e) => tracing::warn!("dropping error {e}"),
+            }
+        })
+    });
+}
+
+#[cfg(not(any(target_arch = "riscv64", target_arch = "s390x")))]
+fn tls_config(config: &HttpClientConfig) -> anyhow::Result<rustls::ClientConfig> {
+    use anyhow::Context;
+    use tokio_rustls::rustls::OwnedTrustAnchor;
+
+    // derived from https://github.com/tokio-rs/tls/blob/master/tokio-rustls/examples/client/src/main.rs
+    let mut root_cert_store = rustls::RootCertStore::empty();
+    if config.webpki_roots {
+        root_cert_store.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+            OwnedTrustAnchor::from_subject_spki_name_constraints(
+                ta.subject,
+                ta.spki,
+                ta.name_constraints,
+            )
+        }));
+    }
+    for cert in config.root_certificates.iter() {
+        root_cert_store
+            .add(&rustls::Certificate(cert.clone()))
+            .context("invalid root certificate")?;
+    }
+    let builder = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(root_cert_store);
+    let mut tls = match &config.client_certificate {
+        Some(cert) => builder
+            .with_client_auth_cert(
+                cert.chain
+                    .iter()
+                    .cloned()
+                    .map(rustls::Certificate)
+                    .collect(),
+                rustls::PrivateKey(cert.private_key.clone()),
+            )
+            .context("invalid client certificate")?,
+        None => builder.with_no_client_auth(),
+    };
+    if config.http2 {
+        tls.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    }
+    Ok(tls)
+}
+
+/// An executor for the background tasks spawned by HTTP/2 connections.
+#[cfg(not(any(target_arch = "riscv64", target_arch = "s390x")))]
+#[derive(Clone, Copy)]
+struct TokioExecutor;
+
+#[cfg(not(any(target_arch = "riscv64", target_arch = "s390x")))]
+impl<F> hyper::rt::Executor<F> for TokioExecutor
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    fn execute(&self, fut: F) {
+        preview2::with_ambient_tokio_runtime(|| tokio::task::spawn(fut));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use http_body_util::{Empty, Full};
+    use hyper::body::Bytes;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    async fn server() -> (String, Arc<AtomicUsize>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let connections = Arc::new(AtomicUsize::new(0));
+        let accepted = connections.clone();
+        tokio::task::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                accepted.fetch_add(1, Ordering::SeqCst);
+                tokio::task::spawn(async move {
+                    let service = hyper::service::service_fn(|_req| async {
+                        Ok::<_, std::convert::Infallible>(hyper::Response::new(Full::new(
+                            Bytes::from_static(b"hello"),
+                        )))
+                    });
+                    let _ = hyper::server::conn::http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+                });
+            }
+        });
+        (addr, connections)
+    }
+
+    fn request(authority: &str) -> OutgoingRequest {
+        let request = hyper::Request::builder()
+            .uri(format!("http://{authority}/"))
+            .header(hyper::header::HOST, authority)
+            .body(
+                Empty::<Bytes>::new()
+                    .map_err(|_| unreachable!("Infallible error"))
+                    .boxed(),
+            )
+            .unwrap();
+        OutgoingRequest {
+            use_tls: false,
+            authority: authority.to_string(),
+            request,
+            connect_timeout: Duration::from_secs(10),
+            first_byte_timeout: Duration::from_secs(10),
+            between_bytes_timeout: Duration::from_secs(10),
+        }
+    }
+
+    async fn get(client: &HttpClient, authority: &str) {
+        let resp = client.send_request(request(authority)).await.unwrap();
+        let body = resp.resp.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], b"hello");
+
+        // Give the worker a chance to return the connection to the pool.
+        let _ = timeout(
+            Duration::from_secs(1),
+            Arc::into_inner(resp.worker).unwrap(),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn reuses_connections() {
+        let (authority, connections) = server().await;
+        let client = HttpClient::default();
+        for _ in 0..3 {
+            get(&client, &authority).await;
+        }
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn idle_limit() {
+        let (authority, connections) = server().await;
+        let client = HttpClient::new(HttpClientConfig {
+            max_idle_per_authority: 0,
+            ..HttpClientConfig::default()
+        })
+        .unwrap();
+        for _ in 0..3 {
+            get(&client, &authority).await;
+        }
+        assert_eq!(connections.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn retries_stale_connections() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        async fn read_request(stream: &mut TcpStream) {
+            let mut request = Vec::new();
+            while !request.ends_with(b"\r\n\r\n") {
+                let mut byte = [0];
+                if stream.read(&mut byte).await.unwrap() == 0 {
+                    return;
+                }
+                request.push(byte[0]);
+            }
+        }
+        const RESPONSE: &[u8] = b"HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\nhello";
+
+        // The first connection is closed by the server upon receiving a
+        // second request, as if it had timed out while idle in the pool.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let authority = listener.local_addr().unwrap().to_string();
+        let connections = Arc::new(AtomicUsize::new(0));
+        let accepted = connections.clone();
+        tokio::task::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            accepted.fetch_add(1, Ordering::SeqCst);
+            read_request(&mut stream).await;
+            stream.write_all(RESPONSE).await.unwrap();
+            read_request(&mut stream).await;
+            drop(stream);
+
+            let (mut stream, _) = listener.accept().await.unwrap();
+            accepted.fetch_add(1, Ordering::SeqCst);
+            read_request(&mut stream).await;
+            stream.write_all(RESPONSE).await.unwrap();
+            read_request(&mut stream).await;
+        });
+
+        let client = HttpClient::default();
+        get(&client, &authority).await;
+        get(&client, &authority).await;
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+    }
+}
@@ -1,8 +1,12 @@
+pub use crate::client::{ClientCertificate, HttpClient, HttpClientConfig};
+pub use crate::policy::OutgoingPolicy;
 pub use crate::types::{WasiHttpCtx, WasiHttpView};
 
 pub mod body;
+pub mod client;
 pub mod http_impl;
 pub mod io;
+pub mod policy;
 pub mod proxy;
 pub mod types;
 pub mod types_impl;
@@ -0,0 +1,402 @@
+//! Restrictions and transformations applied to outgoing requests before
+//! they're sent.
+
+use crate::{
+    bindings::http::types::ErrorCode,
+    body::{HyperIncomingBody, HyperOutgoingBody},
+    types::{IncomingResponseInternal, OutgoingRequest},
+};
+use http_body_util::BodyExt;
+use hyper::body::{Body, Bytes, Frame, SizeHint};
+use hyper::header::{HeaderName, HeaderValue};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A policy describing which outgoing requests a guest may make and how
+/// they're modified on their way out.
+///
+/// Hosts in the allow and deny lists, as well as the hosts that headers are
+/// injected for, are matched against the authority requested by the guest.
+/// A pattern is either an exact host name such as `example.com`, a wildcard
+/// such as `*.example.com` matching any subdomain, or either of those with a
+/// port such as `example.com:8080` to match only that port. Host names are
+/// compared case-insensitively.
+///
+/// The default policy allows every request and leaves it unmodified.
+#[derive(Clone, Debug, Default)]
+pub struct OutgoingPolicy {
+    allowed_hosts: Option<Vec<String>>,
+    denied_hosts: Vec<String>,
+    rewrites: Vec<(String, String)>,
+    headers: Vec<(String, HeaderName, HeaderValue)>,
+    max_request_body_size: Option<u64>,
+    max_response_body_size: Option<u64>,
+}
+
+impl OutgoingPolicy {
+    /// Creates a policy which allows every request.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only allow requests to hosts matching `pattern`, and any other hosts
+    /// passed to this method.
+    ///
+    /// Once a host has been allowed, requests to any host which hasn't been
+    /// are denied with `HTTP-request-denied`.
+    pub fn allow_host(&mut self, pattern: impl Into<String>) -> &mut Self {
+        self.allowed_hosts
+            .get_or_insert_with(Vec::new)
+            .push(pattern.into());
+        self
+    }
+
+    /// Deny requests to hosts matching `pattern`, even if they're also
+    /// allowed.
+    pub fn deny_host(&mut self, pattern: impl Into<String>) -> &mut Self {
+        self.denied_hosts.push(pattern.into());
+        self
+    }
+
+    /// Send requests for the authority `from` to `to` instead.
+    ///
+    /// Both are full authorities including the port, for example
+    /// `api.example.com:443`. The request's `Host` header is updated to match,
+    /// while the allow and deny lists still apply to `from`.
+    pub fn rewrite_authority(
+        &mut self,
+        from: impl Into<String>,
+        to: impl Into<String>,
+    ) -> &mut Self {
+        self.rewrites.push((from.into(), to.into()));
+        self
+    }
+
+    /// Set the header `name` to `value` on all requests to hosts matching
+    /// `pattern`, replacing any value provided by the guest.
+    ///
+    /// This can be used to attach credentials which the guest itself never
+    /// has access to.
+    pub fn header(
+        &mut self,
+        pattern: impl Into<String>,
+        name: HeaderName,
+        value: HeaderValue,
+    ) -> &mut Self {
+        self.headers.push((pattern.into(), name, value));
+        self
+    }
+
+    /// Limit the size of outgoing request bodies to `size` bytes.
+    ///
+    /// Larger bodies fail with `HTTP-request-body-size`.
+    pub fn max_request_body_size(&mut self, size: u64) -> &mut Self {
+        self.max_request_body_size = Some(size);
+        self
+    }
+
+    /// Limit the size of incoming response bodies to `size` bytes.
+    ///
+    /// Larger bodies fail with `HTTP-response-body-size`.
+    pub fn max_response_body_size(&mut self, size: u64) -> &mut Self {
+        self.max_response_body_size = Some(size);
+        self
+    }
+
+    /// Checks `request` against this policy and applies any rewrites and
+    /// injected headers to it.
+    pub fn apply_to_request(&self, request: &mut OutgoingRequest) -> Result<(), ErrorCode> {
+        let authority = request.authority.clone();
+
+        if self
+            .denied_hosts
+            .iter()
+            .any(|p| host_matches(p, &authority))
+        {
+            tracing::debug!("denied outgoing request to {authority}");
+            return Err(ErrorCode::HttpRequestDenied);
+        }
+        if let Some(allowed) = &self.allowed_hosts {
+            if !allowed.iter().any(|p| host_matches(p, &authority)) {
+                tracing::debug!("outgoing request to {authority} is not allowed");
+                return Err(ErrorCode::HttpRequestDenied);
+            }
+        }
+
+        if let Some(limit) = self.max_request_body_size {
+            if let Some(len) = content_length(request.request.headers()) {
+                if len > limit {
+                    return Err(ErrorCode::HttpRequestBodySize(Some(len)));
+                }
+            }
+            let body = std::mem::replace(request.request.body_mut(), empty_body());
+            *request.request.body_mut() =
+                LimitedBody::new(body, limit, ErrorCode::HttpRequestBodySize).boxed();
+        }
+
+        let headers = request.request.headers_mut();
+        for (pattern, name, value) in self.headers.iter() {
+            if host_matches(pattern, &authority) {
+                headers.insert(name.clone(), value.clone());
+            }
+        }
+
+        if let Some((_, to)) = self.rewrites.iter().find(|(from, _)| *from == authority) {
+            let mut parts = request.request.uri().clone().into_parts();
+            parts.authority =
+                Some(to.parse().map_err(|_| {
+                    ErrorCode::InternalError(Some(format!("invalid authority {to}")))
+                })?);
+            *request.request.uri_mut() = hyper::Uri::from_parts(parts)
+                .map_err(|e| ErrorCode::InternalError(Some(e.to_string())))?;
+            let host = HeaderValue::from_str(to)
+                .map_err(|e| ErrorCode::InternalError(Some(e.to_string())))?;
+            request
+                .request
+                .headers_mut()
+                .insert(hyper::header::HOST, host);
+            request.authority = to.clone();
+        }
+
+        Ok(())
+    }
+
+    /// Applies this policy's response body size limit to `response`.
+    pub fn apply_to_response(
+        &self,
+        mut response: IncomingResponseInternal,
+    ) -> Result<IncomingResponseInternal, ErrorCode> {
+        if let Some(limit) = self.max_response_body_size {
+            if let Some(len) = content_length(response.resp.headers()) {
+                if len > limit {
+                    return Err(ErrorCode::HttpResponseBodySize(Some(len)));
+                }
+            }
+            let body = std::mem::replace(response.resp.body_mut(), empty_body());
+            *response.resp.body_mut() =
+                LimitedBody::new(body, limit, ErrorCode::HttpResponseBodySize).boxed();
+        }
+        Ok(response)
+    }
+}
+
+/// Returns whether `authority`, a `host:port` pair, matches `pattern`.
+fn host_matches(pattern: &str, authority: &str) -> bool {
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (host, Some(port)),
+        None => (authority, None),
+    };
+    let pattern_host = match pattern.rsplit_once(':') {
+        Some((pattern_host, pattern_port)) => {
+            if Some(pattern_port) != port {
+                return false;
+            }
+            pattern_host
+        }
+        None => pattern,
+    };
+
+    // A trailing dot makes a name fully qualified, but it's the same host.
+    let host = host.strip_suffix('.').unwrap_or(host);
+    let pattern_host = pattern_host.strip_suffix('.').unwrap_or(pattern_host);
+
+    match pattern_host.strip_prefix("*.") {
+        Some(suffix) => {
+            host.len() > suffix.len() + 1
+                && host.as_bytes()[host.len() - suffix.len() - 1] == b'.'
+                && host[host.len() - suffix.len()..].eq_ignore_ascii_case(suffix)
+        }
+        None => host.eq_ignore_ascii_case(pattern_host),
+    }
+}
+
+fn content_length(headers: &hyper::HeaderMap) -> Option<u64> {
+    headers
+        .get(hyper::header::CONTENT_LENGTH)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+}
+
+fn empty_body() -> HyperOutgoingBody {
+    http_body_util::Empty::<Bytes>::new()
+        .map_err(|_| unreachable!("Infallible error"))
+        .boxed()
+}
+
+/// A body which fails once more than `limit` bytes have been read from it.
+struct LimitedBody {
+    body: HyperIncomingBody,
+    limit: u64,
+    read: u64,
+    error: fn(Option<u64>) -> ErrorCode,
+}
+
+impl LimitedBody {
+    fn new(body: HyperIncomingBody, limit: u64, error: fn(Option<u64>) -> ErrorCode) -> Self {
+        Self {
+            body,
+            limit,
+            read: 0,
+            error,
+        }
+    }
+}
+
+impl Body for LimitedBody {
+    type Data = Bytes;
+    type Error = ErrorCode;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, ErrorCode>>> {
+        let frame = match Pin::new(&mut self.body).poll_frame(cx) {
+            Poll::Ready(Some(Ok(frame))) => frame,
+            other => return other,
+        };
+        if let Some(data) = frame.data_ref() {
+            self.read += data.len() as u64;
+            if self.read > self.limit {
+                return Poll::Ready(Some(Err((self.error)(Some(self.read)))));
+            }
+        }
+        Poll::Ready(Some(Ok(frame)))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.body.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use http_body_util::Full;
+    use std::time::Duration;
+
+    fn request(authority: &str, body: &'static [u8]) -> OutgoingRequest {
+        let request = hyper::Request::builder()
+            .uri(format!("http://{authority}/path"))
+            .header(hyper::header::HOST, authority)
+            .body(
+                Full::new(Bytes::from_static(body))
+                    .map_err(|_| unreachable!("Infallible error"))
+                    .boxed(),
+            )
+            .unwrap();
+        OutgoingRequest {
+            use_tls: false,
+            authority: authority.to_string(),
+            request,
+            connect_timeout: Duration::from_secs(1),
+            first_byte_timeout: Duration::from_secs(1),
+            between_bytes_timeout: Duration::from_secs(1),
+        }
+    }
+
+    #[test]
+    fn host_patterns() {
+        assert!(host_matches("example.com", "example.com:443"));
+        assert!(host_matches("EXAMPLE.com", "example.COM:80"));
+        assert!(host_matches("example.com:443", "example.com:443"));
+        assert!(!host_matches("example.com:443", "example.com:80"));
+        assert!(!host_matches("example.com", "api.example.com:443"));
+        assert!(host_matches("*.example.com", "api.example.com:443"));
+        assert!(host_matches("*.example.com", "a.b.example.com:443"));
+        assert!(!host_matches("*.example.com", "example.com:443"));
+        assert!(!host_matches("*.example.com", "badexample.com:443"));
+        assert!(host_matches("example.com", "example.com.:443"));
+        assert!(host_matches("example.com.", "example.com:443"));
+        assert!(host_matches("*.example.com", "api.example.com.:443"));
+        assert!(host_matches("api.example.com:443", "API.example.com.:443"));
+    }
+
+    #[test]
+    fn allow_and_deny() {
+        let mut policy = OutgoingPolicy::new();
+        policy
+            .allow_host("*.example.com")
+            .deny_host("secret.example.com");
+
+        let mut req = request("api.example.com:80", b"");
+        assert!(policy.apply_to_request(&mut req).is_ok());
+
+        let mut req = request("secret.example.com:80", b"");
+        assert!(matches!(
+            policy.apply_to_request(&mut req),
+            Err(ErrorCode::HttpRequestDenied)
+        ));
+
+        let mut req = request("secret.example.com.:80", b"");
+        assert!(matches!(
+            policy.apply_to_request(&mut req),
+            Err(ErrorCode::HttpRequestDenied)
+        ));
+
+        let mut req = request("localhost:80", b"");
+        assert!(matches!(
+            policy.apply_to_request(&mut req),
+            Err(ErrorCode::HttpRequestDenied)
+        ));
+    }
+
+    #[test]
+    fn rewrite_and_headers() {
+        let mut policy = OutgoingPolicy::new();
+        policy
+            .rewrite_authority("api.example.com:80", "10.0.0.1:8080")
+            .header(
+                "api.example.com",
+                HeaderName::from_static("authorization"),
+                HeaderValue::from_static("Bearer secret"),
+            );
+
+        let mut req = request("api.example.com:80", b"");
+        req.request.headers_mut().insert(
+            hyper::header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer guest"),
+        );
+        policy.apply_to_request(&mut req).unwrap();
+        assert_eq!(req.authority, "10.0.0.1:8080");
+        assert_eq!(req.request.uri(), "http://10.0.0.1:8080/path");
+        assert_eq!(req.request.headers()[hyper::header::HOST], "10.0.0.1:8080");
+        assert_eq!(
+            req.request.headers()[hyper::header::AUTHORIZATION],
+            "Bearer secret"
+        );
+    }
+
+    #[tokio::test]
+    async fn request_body_size() {
+        let mut policy = OutgoingPolicy::new();
+        policy.max_request_body_size(4);
+
+        let mut req = request("example.com:80", b"1234");
+        policy.apply_to_request(&mut req).unwrap();
+        let body = req.request.into_body().collect().await.unwrap();
+        assert_eq!(&body.to_bytes()[..], b"1234");
+
+        let mut req = request("example.com:80", b"12345");
+        policy.apply_to_request(&mut req).unwrap();
+        assert!(matches!(
+            req.request.into_body().collect().await,
+            Err(ErrorCode::HttpRequestBodySize(Some(5)))
+        ));
+
+        let mut req = request("example.com:80", b"");
+        req.request.headers_mut().insert(
+            hyper::header::CONTENT_LENGTH,
+            HeaderValue::from_static("100"),
+        );
+        assert!(matches!(
+            policy.apply_to_request(&mut req),
+            Err(ErrorCode::HttpRequestBodySize(Some(100)))
+        ));
+    }
+}
@@ -1,24 +1,75 @@
 //! Implements the base structure (i.e. [WasiHttpCtx]) that will provide the
 //! implementation of the wasi-http API.
 
-use crate::io::TokioIo;
 use crate::{
     bindings::http::types::{self, Method, Scheme},
     body::{HostIncomingBody, HyperIncomingBody, HyperOutgoingBody},
-    dns_error, hyper_request_error,
+    client::HttpClient,
+    policy::OutgoingPolicy,
 };
 use http_body_util::BodyExt;
 use hyper::header::HeaderName;
 use std::any::Any;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use std::time::Duration;
-use tokio::net::TcpStream;
-use tokio::time::timeout;
 use wasmtime::component::{Resource, ResourceTable};
 use wasmtime_wasi::preview2::{self, AbortOnDropJoinHandle, Subscribe};
 
 /// Capture the state necessary for use in the wasi-http API implementation.
-pub struct WasiHttpCtx;
+///
+/// It holds the client outgoing requests are sent through, and the policy
+/// [`default_send_request`] checks them against. Create one with [`WasiHttpCtx::new`] or
+/// [`Default`] to use a client shared by all such contexts, or with
+/// [`WasiHttpCtx::with_client`] to use a client of your own.
+#[derive(Clone)]
+pub struct WasiHttpCtx {
+    client: HttpClient,
+    policy: Arc<OutgoingPolicy>,
+}
+
+impl Default for WasiHttpCtx {
+    fn default() -> Self {
+        // Building a client loads its TLS configuration, so contexts which
+        // don't specify a client share one instead of building their own.
+        static CLIENT: OnceLock<HttpClient> = OnceLock::new();
+        Self::with_client(CLIENT.get_or_init(HttpClient::default).clone())
+    }
+}
+
+impl WasiHttpCtx {
+    /// Creates a new context which sends outgoing requests through a client
+    /// shared with all the other contexts created with this function.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new context which sends outgoing requests through `client`.
+    ///
+    /// Contexts sharing a client also share its connection pool.
+    pub fn with_client(client: HttpClient) -> Self {
+        Self {
+            client,
+            policy: Arc::default(),
+        }
+    }
+
+    /// Returns the client used to send outgoing requests.
+    pub fn client(&self) -> &HttpClient {
+        &self.client
+    }
+
+    /// Sets the policy applied to outgoing requests made through
+    /// [`default_send_request`].
+    pub fn set_policy(&mut self, policy: OutgoingPolicy) -> &mut Self {
+        self.policy = Arc::new(policy);
+        self
+    }
+
+    /// Returns the policy applied to outgoing requests.
+    pub fn policy(&self) -> &OutgoingPolicy {
+        &self.policy
+    }
+}
 
 pub struct OutgoingRequest {
     pub use_tls: bool,
@@ -112,26 +163,16 @@ pub(crate) fn remove_forbidden_headers(
 
 pub fn default_send_request(
     view: &mut dyn WasiHttpView,
-    OutgoingRequest {
-        use_tls,
-        authority,
-        request,
-        connect_timeout,
-        first_byte_timeout,
-        between_bytes_timeout,
-    }: OutgoingRequest,
+    mut request: OutgoingRequest,
 ) -> wasmtime::Result<Resource<HostFutureIncomingResponse>> {
+    let client = view.ctx().client.clone();
+    let policy = view.ctx().policy.clone();
     let handle = preview2::spawn(async move {
-        let resp = handler(
-            authority,
-            use_tls,
-            connect_timeout,
-            first_byte_timeout,
-            request,
-            between_bytes_timeout,
-        )
-        .await;
-        Ok(resp)
+        if let Err(e) = policy.apply_to_request(&mut request) {
+            return Ok(Err(e));
+        }
+        let resp = client.send_request(request).await;
+        Ok(resp.and_then(|resp| policy.apply_to_response(resp)))
     });
 
     let fut = view.table().push(HostFutureIncomingResponse::new(handle))?;
@@ -139,124 +180,6 @@ pub fn default_send_request(
     Ok(fut)
 }
 
-async fn handler(
-    authority: String,
-    use_tls: bool,
-    connect_timeout: Duration,
-    first_byte_timeout: Duration,
-    request: http::Request<HyperOutgoingBody>,
-    between_bytes_timeout: Duration,
-) -> Result<IncomingResponseInternal, types::ErrorCode> {
-    let tcp_stream = TcpStream::connect(authority.clone())
-        .await
-        .map_err(|e| match e.kind() {
-            std::io::ErrorKind::AddrNotAvailable => {
-                dns_error("address not available".to_string(), 0)
-            }
-
-            _ => {
-                if e.to_string()
-                    .starts_with("failed to lookup address information")
-                {
-                    dns_error("address not available".to_string(), 0)
-                } else {
-                    types::ErrorCode::ConnectionRefused
-                }
-            }
-        })?;
-
-    let (mut sender, worker) = if use_tls {
-        #[cfg(any(target_arch = "riscv64", target_arch = "s390x"))]
-        {
-            return Err(crate::bindings::http::types::ErrorCode::InternalError(
-                Some("unsupported architecture for SSL".to_string()),
-            ));
-        }
-
-        #[cfg(not(any(target_arch = "riscv64", target_arch = "s390x")))]
-        {
-            use tokio_rustls::rustls::OwnedTrustAnchor;
-
-            // derived from https://github.com/tokio-rs/tls/blob/master/tokio-rustls/examples/client/src/main.rs
-            let mut root_cert_store = rustls::RootCertStore::empty();
-            root_cert_store.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
-                OwnedTrustAnchor::from_subject_spki_name_constraints(
-                    ta.subject,
-                    ta.spki,
-                    ta.name_constraints,
-                )
-            }));
-            let config = rustls::ClientConfig::builder()
-                .with_safe_defaults()
-                .with_root_certificates(root_cert_store)
-                .with_no_client_auth();
-            let connector = tokio_rustls::TlsConnector::from(std::sync::Arc::new(config));
-            let mut parts = authority.split(":");
-            let host = parts.next().unwrap_or(&authority);
-            let domain = rustls::ServerName::try_from(host).map_err(|e| {
-                tracing::warn!("dns lookup error: {e:?}");
-                dns_error("invalid dns name".to_string(), 0)
-            })?;
-            let stream = connector.connect(domain, tcp_stream).await.map_err(|e| {
-                tracing::warn!("tls protocol error: {e:?}");
-                types::ErrorCode::TlsProtocolError
-            })?;
-            let stream = TokioIo::new(stream);
-
-            let (sender, conn) = timeout(
-                connect_timeout,
-                hyper::client::conn::http1::handshake(stream),
-            )
-            .await
-            .map_err(|_| types::ErrorCode::ConnectionTimeout)?
-            .map_err(hyper_request_error)?;
-
-            let worker = preview2::spawn(async move {
-                match conn.await {
-                    Ok(()) => {}
-                    // TODO: shouldn't throw away this error and ideally should
-                    // surface somewhere.
-                    Err(e) => tracing::warn!("dropping error {e}"),
-                }
-            });
-
-            (sender, worker)
-        }
-    } else {
-        let tcp_stream = TokioIo::new(tcp_stream);
-        let (sender, conn) = timeout(
-            connect_timeout,
-            // TODO: we should plumb the builder through the http context, and use it here
-            hyper::client::conn::http1::handshake(tcp_stream),
-        )
-        .await
-        .map_err(|_| types::ErrorCode::ConnectionTimeout)?
-        .map_err(hyper_request_error)?;
-
-        let worker = preview2::spawn(async move {
-            match conn.await {
-                Ok(()) => {}
-                // TODO: same as above, shouldn't throw this error away.
-                Err(e) => tracing::warn!("dropping error {e}"),
-            }
-        });
-
-        (sender, worker)
-    };
-
-    let resp = timeout(first_byte_timeout, sender.send_request(request))
-        .await
-        .map_err(|_| types::ErrorCode::ConnectionReadTimeout)?
-        .map_err(hyper_request_error)?
-        .map(|body| body.map_err(hyper_request_error).boxed());
-
-    Ok(IncomingResponseInternal {
-        resp,
-        worker: Arc::new(worker),
-        between_bytes_timeout,
-    })
-}
-
 impl From<http::Method> for types::Method {
     fn from(method: http::Method) -> Self {
         if method == http::Method::GET {
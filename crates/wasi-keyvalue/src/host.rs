@@ -0,0 +1,256 @@
+//! Implementations of the generated host traits in terms of
+//! [`KeyValueStore`](crate::KeyValueStore).
+
+use crate::bindings::keyvalue::{atomics, batch, store};
+use crate::{Bucket, Error, KeyResponse, WasiKeyValueView};
+use anyhow::Result;
+use wasmtime::component::Resource;
+
+/// Looks up the name of `bucket` so that it can be passed to the backend
+/// alongside a borrow of the view.
+fn bucket_name<T: WasiKeyValueView>(view: &mut T, bucket: &Resource<Bucket>) -> Result<String> {
+    Ok(view.table().get(bucket)?.name.clone())
+}
+
+impl<T: WasiKeyValueView> store::Host for T {
+    fn open(&mut self, identifier: String) -> Result<Result<Resource<Bucket>, Error>> {
+        if let Err(e) = self.ctx().store.open(&identifier) {
+            return Ok(Err(e));
+        }
+        let bucket = self.table().push(Bucket { name: identifier })?;
+        Ok(Ok(bucket))
+    }
+}
+
+impl<T: WasiKeyValueView> store::HostBucket for T {
+    fn get(
+        &mut self,
+        bucket: Resource<Bucket>,
+        key: String,
+    ) -> Result<Result<Option<Vec<u8>>, Error>> {
+        let name = bucket_name(self, &bucket)?;
+        Ok(self.ctx().store.get(&name, &key))
+    }
+
+    fn set(
+        &mut self,
+        bucket: Resource<Bucket>,
+        key: String,
+        value: Vec<u8>,
+    ) -> Result<Result<(), Error>> {
+        let name = bucket_name(self, &bucket)?;
+        Ok(self.ctx().store.set(&name, &key, value))
+    }
+
+    fn delete(&mut self, bucket: Resource<Bucket>, key: String) -> Result<Result<(), Error>> {
+        let name = bucket_name(self, &bucket)?;
+        Ok(self.ctx().store.delete(&name, &key))
+    }
+
+    fn exists(&mut self, bucket: Resource<Bucket>, key: String) -> Result<Result<bool, Error>> {
+        let name = bucket_name(self, &bucket)?;
+        Ok(self.ctx().store.exists(&name, &key))
+    }
+
+    fn list_keys(
+        &mut self,
+        bucket: Resource<Bucket>,
+        cursor: Option<u64>,
+    ) -> Result<Result<KeyResponse, Error>> {
+        let name = bucket_name(self, &bucket)?;
+        Ok(self.ctx().store.list_keys(&name, cursor))
+    }
+
+    fn drop(&mut self, bucket: Resource<Bucket>) -> Result<()> {
+        self.table().delete(bucket)?;
+        Ok(())
+    }
+}
+
+impl<T: WasiKeyValueView> atomics::Host for T {
+    fn increment(
+        &mut self,
+        bucket: Resource<Bucket>,
+        key: String,
+        delta: u64,
+    ) -> Result<Result<u64, Error>> {
+        let name = bucket_name(self, &bucket)?;
+        Ok(self.ctx().store.increment(&name, &key, delta))
+    }
+}
+
+impl<T: WasiKeyValueView> batch::Host for T {
+    fn get_many(
+        &mut self,
+        bucket: Resource<Bucket>,
+        keys: Vec<String>,
+    ) -> Result<Result<Vec<Option<(String, Vec<u8>)>>, Error>> {
+        let name = bucket_name(self, &bucket)?;
+        let store = &self.ctx().store;
+        let mut result = Vec::with_capacity(keys.len());
+        for key in keys {
+            match store.get(&name, &key) {
+                Ok(value) => result.push(value.map(|value| (key, value))),
+                Err(e) => return Ok(Err(e)),
+            }
+        }
+        Ok(Ok(result))
+    }
+
+    fn set_many(
+        &mut self,
+        bucket: Resource<Bucket>,
+        key_values: Vec<(String, Vec<u8>)>,
+    ) -> Result<Result<(), Error>> {
+        let name = bucket_name(self, &bucket)?;
+        let store = &self.ctx().store;
+        for (key, value) in key_values {
+            if let Err(e) = store.set(&name, &key, value) {
+                return Ok(Err(e));
+            }
+        }
+        Ok(Ok(()))
+    }
+
+    fn delete_many(
+        &mut self,
+        bucket: Resource<Bucket>,
+        keys: Vec<String>,
+    ) -> Result<Result<(), Error>> {
+        let name = bucket_name(self, &bucket)?;
+        let store = &self.ctx().store;
+        for key in keys {
+            if let Err(e) = store.delete(&name, &key) {
+                return Ok(Err(e));
+            }
+        }
+        Ok(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bindings::keyvalue::atomics::Host as _;
+    use crate::bindings::keyvalue::batch::Host as _;
+    use crate::bindings::keyvalue::store::HostBucket as _;
+    use crate::{InMemoryStore, KeyValueStore, WasiKeyValueCtx};
+    use std::sync::Arc;
+    use wasmtime::component::ResourceTable;
+
+    struct Host {
+        ctx: WasiKeyValueCtx,
+        table: ResourceTable,
+    }
+
+    impl WasiKeyValueView for Host {
+        fn ctx(&mut self) -> &mut WasiKeyValueCtx {
+            &mut self.ctx
+        }
+        fn table(&mut self) -> &mut ResourceTable {
+            &mut self.table
+        }
+    }
+
+    fn host(store: impl KeyValueStore + 'static) -> Host {
+        Host {
+            ctx: WasiKeyValueCtx::new(Arc::new(store)),
+            table: ResourceTable::new(),
+        }
+    }
+
+    fn borrow(bucket: &Resource<Bucket>) -> Resource<Bucket> {
+        Resource::new_borrow(bucket.rep())
+    }
+
+    #[test]
+    fn bucket_operations() -> Result<()> {
+        let mut host = host(InMemoryStore::new());
+        let bucket = store::Host::open(&mut host, "a".to_string())?.unwrap();
+        assert_eq!(host.table.get(&bucket)?.name(), "a");
+
+        host.set(borrow(&bucket), "k".to_string(), b"v".to_vec())?
+            .unwrap();
+        let value = store::HostBucket::get(&mut host, borrow(&bucket), "k".to_string())?.unwrap();
+        assert_eq!(value, Some(b"v".to_vec()));
+        assert!(host.exists(borrow(&bucket), "k".to_string())?.unwrap());
+        let keys = host.list_keys(borrow(&bucket), None)?.unwrap();
+        assert_eq!(keys.keys, ["k"]);
+        assert_eq!(keys.cursor, None);
+
+        assert_eq!(
+            host.increment(borrow(&bucket), "n".to_string(), 3)?
+                .unwrap(),
+            3
+        );
+        host.set_many(
+            borrow(&bucket),
+            vec![
+                ("x".to_string(), b"1".to_vec()),
+                ("y".to_string(), b"2".to_vec()),
+            ],
+        )?
+        .unwrap();
+        let values = host
+            .get_many(
+                borrow(&bucket),
+                vec!["x".to_string(), "missing".to_string()],
+            )?
+            .unwrap();
+        assert_eq!(values, [Some(("x".to_string(), b"1".to_vec())), None]);
+        host.delete_many(borrow(&bucket), vec!["x".to_string(), "y".to_string()])?
+            .unwrap();
+        assert!(!host.exists(borrow(&bucket), "x".to_string())?.unwrap());
+
+        // Buckets opened with the same identifier share their data.
+        let other = store::Host::open(&mut host, "a".to_string())?.unwrap();
+        let value = store::HostBucket::get(&mut host, borrow(&other), "k".to_string())?.unwrap();
+        assert_eq!(value, Some(b"v".to_vec()));
+
+        let rep = bucket.rep();
+        store::HostBucket::drop(&mut host, bucket)?;
+        assert!(host
+            .table
+            .get(&Resource::<Bucket>::new_borrow(rep))
+            .is_err());
+        store::HostBucket::drop(&mut host, other)?;
+        Ok(())
+    }
+
+    #[test]
+    fn errors_are_returned_to_the_guest() -> Result<()> {
+        struct Denied;
+        impl KeyValueStore for Denied {
+            fn open(&self, _: &str) -> Result<(), Error> {
+                Err(Error::AccessDenied)
+            }
+            fn get(&self, _: &str, _: &str) -> Result<Option<Vec<u8>>, Error> {
+                Err(Error::Other("unreachable".to_string()))
+            }
+            fn set(&self, _: &str, _: &str, _: Vec<u8>) -> Result<(), Error> {
+                Err(Error::Other("read-only".to_string()))
+            }
+            fn delete(&self, _: &str, _: &str) -> Result<(), Error> {
+                Err(Error::Other("read-only".to_string()))
+            }
+            fn list_keys(&self, _: &str, _: Option<u64>) -> Result<KeyResponse, Error> {
+                Err(Error::Other("unreachable".to_string()))
+            }
+            fn increment(&self, _: &str, _: &str, _: u64) -> Result<u64, Error> {
+                Err(Error::Other("read-only".to_string()))
+            }
+        }
+
+        let mut host = host(Denied);
+        let result = store::Host::open(&mut host, "a".to_string())?;
+        assert!(matches!(result, Err(Error::AccessDenied)));
+
+        // Errors of the backend's operations are passed through too.
+        let bucket = host.table.push(Bucket {
+            name: "a".to_string(),
+        })?;
+        let result = host.set_many(borrow(&bucket), vec![("k".to_string(), vec![])])?;
+        assert!(matches!(result, Err(Error::Other(msg)) if msg == "read-only"));
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+//! A [`KeyValueStore`] which keeps all data in host memory.
+
+use crate::{Error, KeyResponse, KeyValueStore};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+/// The maximum number of keys returned from a single `list-keys` call.
+const LIST_KEYS_PAGE_SIZE: usize = 100;
+
+/// A [`KeyValueStore`] which keeps all buckets in memory.
+///
+/// Buckets are created on first use and all data is lost when the store is
+/// dropped. This is primarily intended for tests and local development.
+#[derive(Default)]
+pub struct InMemoryStore {
+    buckets: Mutex<HashMap<String, BTreeMap<String, Vec<u8>>>>,
+}
+
+impl InMemoryStore {
+    /// Creates a new, empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn with_bucket<R>(
+        &self,
+        bucket: &str,
+        f: impl FnOnce(&mut BTreeMap<String, Vec<u8>>) -> R,
+    ) -> R {
+        let mut buckets = self.buckets.lock().unwrap();
+        f(buckets.entry(bucket.to_string()).or_default())
+    }
+}
+
+impl KeyValueStore for InMemoryStore {
+    fn get(&self, bucket: &str, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.with_bucket(bucket, |b| b.get(key).cloned()))
+    }
+
+    fn set(&self, bucket: &str, key: &str, value: Vec<u8>) -> Result<(), Error> {
+        self.with_bucket(bucket, |b| b.insert(key.to_string(), value));
+        Ok(())
+    }
+
+    fn delete(&self, bucket: &str, key: &str) -> Result<(), Error> {
+        self.with_bucket(bucket, |b| b.remove(key));
+        Ok(())
+    }
+
+    fn exists(&self, bucket: &str, key: &str) -> Result<bool, Error> {
+        Ok(self.with_bucket(bucket, |b| b.contains_key(key)))
+    }
+
+    fn list_keys(&self, bucket: &str, cursor: Option<u64>) -> Result<KeyResponse, Error> {
+        let start = usize::try_from(cursor.unwrap_or(0))
+            .map_err(|_| Error::Other("invalid cursor".to_string()))?;
+        Ok(self.with_bucket(bucket, |b| {
+            let keys = b
+                .keys()
+                .skip(start)
+                .take(LIST_KEYS_PAGE_SIZE)
+                .cloned()
+                .collect::<Vec<_>>();
+            let end = start + keys.len();
+            KeyResponse {
+                keys,
+                cursor: if end < b.len() {
+                    Some(end as u64)
+                } else {
+                    None
+                },
+            }
+        }))
+    }
+
+    fn increment(&self, bucket: &str, key: &str, delta: u64) -> Result<u64, Error> {
+        self.with_bucket(bucket, |b| {
+            let current = match b.get(key) {
+                Some(value) => {
+                    let bytes = <[u8; 8]>::try_from(value.as_slice()).map_err(|_| {
+                        Error::Other(format!("value of `{key}` is not a 64-bit integer"))
+                    })?;
+                    u64::from_le_bytes(bytes)
+                }
+                None => 0,
+            };
+            let new = current.wrapping_add(delta);
+            b.insert(key.to_string(), new.to_le_bytes().to_vec());
+            Ok(new)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn basic_operations() {
+        let store = InMemoryStore::new();
+        assert_eq!(store.get("a", "k").unwrap(), None);
+        store.set("a", "k", b"v".to_vec()).unwrap();
+        assert_eq!(store.get("a", "k").unwrap(), Some(b"v".to_vec()));
+        assert!(store.exists("a", "k").unwrap());
+
+        // Buckets are independent of each other.
+        assert!(!store.exists("b", "k").unwrap());
+
+        store.delete("a", "k").unwrap();
+        assert!(!store.exists("a", "k").unwrap());
+        store.delete("a", "k").unwrap();
+    }
+
+    #[test]
+    fn increment() {
+        let store = InMemoryStore::new();
+        assert_eq!(store.increment("a", "n", 2).unwrap(), 2);
+        assert_eq!(store.increment("a", "n", 3).unwrap(), 5);
+        assert_eq!(
+            store.get("a", "n").unwrap(),
+            Some(5u64.to_le_bytes().to_vec())
+        );
+
+        store.set("a", "s", b"abc".to_vec()).unwrap();
+        assert!(store.increment("a", "s", 1).is_err());
+    }
+
+    #[test]
+    fn list_keys_paginates() {
+        let store = InMemoryStore::new();
+        let n = LIST_KEYS_PAGE_SIZE + 10;
+        for i in 0..n {
+            store.set("a", &format!("{i:04}"), vec![]).unwrap();
+        }
+
+        let first = store.list_keys("a", None).unwrap();
+        assert_eq!(first.keys.len(), LIST_KEYS_PAGE_SIZE);
+        assert_eq!(first.keys[0], "0000");
+        let second = store.list_keys("a", first.cursor).unwrap();
+        assert_eq!(second.keys.len(), 10);
+        assert_eq!(second.cursor, None);
+        assert_eq!(second.keys[9], format!("{:04}", n - 1));
+    }
+}
@@ -0,0 +1,41 @@
+//! Wasmtime's implementation of the proposed `wasi:keyvalue` interfaces.
+//!
+//! Embedders implement [`WasiKeyValueView`] for their store's data and call
+//! [`add_to_linker`] to make the `store`, `atomics` and `batch` interfaces
+//! available to components. The actual data lives behind the
+//! [`KeyValueStore`] trait which can be backed by anything from the
+//! [`InMemoryStore`] provided here to a remote database.
+
+pub use crate::in_memory::InMemoryStore;
+pub use crate::types::{Bucket, KeyValueStore, WasiKeyValueCtx, WasiKeyValueView};
+
+mod host;
+pub mod in_memory;
+pub mod types;
+
+pub mod bindings {
+    wasmtime::component::bindgen!({
+        path: "wit",
+        world: "wasi:keyvalue/imports",
+        tracing: true,
+        async: false,
+        with: {
+            "wasi:keyvalue/store/bucket": super::types::Bucket,
+        }
+    });
+
+    pub use wasi::keyvalue;
+}
+
+pub use bindings::keyvalue::store::{Error, KeyResponse};
+
+/// Adds all `wasi:keyvalue` interfaces to the `linker` provided.
+pub fn add_to_linker<T>(l: &mut wasmtime::component::Linker<T>) -> anyhow::Result<()>
+where
+    T: WasiKeyValueView,
+{
+    bindings::keyvalue::store::add_to_linker(l, |t| t)?;
+    bindings::keyvalue::atomics::add_to_linker(l, |t| t)?;
+    bindings::keyvalue::batch::add_to_linker(l, |t| t)?;
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use crate::{Error, InMemoryStore, KeyResponse};
+use std::sync::Arc;
+use wasmtime::component::ResourceTable;
+
+/// A backend for the `wasi:keyvalue` interfaces.
+///
+/// Every operation is scoped to a bucket, identified by the string the guest
+/// passed to `open`. Implementations are shared between all stores which use
+/// them, so they're responsible for their own synchronization.
+///
+/// Operations are invoked synchronously from the guest's host calls, so
+/// implementations talking to a remote service will block the calling thread
+/// for the duration of each request.
+pub trait KeyValueStore: Send + Sync {
+    /// Validates that the guest may access the bucket named `identifier`.
+    ///
+    /// By default all buckets are accessible.
+    fn open(&self, identifier: &str) -> Result<(), Error> {
+        let _ = identifier;
+        Ok(())
+    }
+
+    /// Returns the value of `key`, or `None` if it isn't present.
+    fn get(&self, bucket: &str, key: &str) -> Result<Option<Vec<u8>>, Error>;
+
+    /// Sets `key` to `value`, overwriting any previous value.
+    fn set(&self, bucket: &str, key: &str, value: Vec<u8>) -> Result<(), Error>;
+
+    /// Removes `key`, doing nothing if it isn't present.
+    fn delete(&self, bucket: &str, key: &str) -> Result<(), Error>;
+
+    /// Returns whether `key` is present.
+    fn exists(&self, bucket: &str, key: &str) -> Result<bool, Error> {
+        Ok(self.get(bucket, key)?.is_some())
+    }
+
+    /// Returns a page of keys in the bucket starting at `cursor`, along with
+    /// the cursor for the next page if there is one.
+    fn list_keys(&self, bucket: &str, cursor: Option<u64>) -> Result<KeyResponse, Error>;
+
+    /// Atomically adds `delta` to the value of `key`, returning the new value.
+    ///
+    /// Missing keys are treated as zero. Values are stored as little-endian
+    /// `u64`s.
+    fn increment(&self, bucket: &str, key: &str, delta: u64) -> Result<u64, Error>;
+}
+
+/// Per-store state for the `wasi:keyvalue` interfaces.
+pub struct WasiKeyValueCtx {
+    pub(crate) store: Arc<dyn KeyValueStore>,
+}
+
+impl WasiKeyValueCtx {
+    /// Creates a context which serves all buckets from `store`.
+    ///
+    /// The same `store` may be shared by any number of contexts, for example
+    /// to persist data across instantiations.
+    pub fn new(store: Arc<dyn KeyValueStore>) -> Self {
+        Self { store }
+    }
+
+    /// Returns the backend used by this context.
+    pub fn store(&self) -> &Arc<dyn KeyValueStore> {
+        &self.store
+    }
+}
+
+impl Default for WasiKeyValueCtx {
+    /// Creates a context backed by a fresh, empty [`InMemoryStore`].
+    fn default() -> Self {
+        Self::new(Arc::new(InMemoryStore::new()))
+    }
+}
+
+/// Provides access to the state needed by the `wasi:keyvalue` host
+/// implementations.
+pub trait WasiKeyValueView: Send {
+    fn ctx(&mut self) -> &mut WasiKeyValueCtx;
+    fn table(&mut self) -> &mut ResourceTable;
+}
+
+/// The host representation of a `wasi:keyvalue/store/bucket` resource.
+pub struct Bucket {
+    pub(crate) name: String,
+}
+
+impl Bucket {
+    /// The identifier this bucket was opened with.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
@@ -2,7 +2,11 @@
 //! this crate. The `Box<dyn ...>` types returned by these interfaces allow
 //! implementations to maintain backend-specific state between calls.
 
+#[cfg(feature = "onnx")]
+pub mod onnxruntime;
 pub mod openvino;
+#[cfg(feature = "tract")]
+pub mod tract;
 
 use self::openvino::OpenvinoBackend;
 use crate::wit::types::{ExecutionTarget, GraphEncoding, Tensor};
@@ -13,7 +17,15 @@ use wiggle::GuestError;
 
 /// Return a list of all available backend frameworks.
 pub fn list() -> Vec<crate::Backend> {
-    vec![Backend::from(OpenvinoBackend::default())]
+    #[allow(unused_mut)]
+    let mut backends = vec![Backend::from(OpenvinoBackend::default())];
+    // Only one backend can be registered per encoding; ONNX Runtime is
+    // preferred over tract when both are enabled.
+    #[cfg(feature = "onnx")]
+    backends.push(Backend::from(onnxruntime::OnnxBackend::default()));
+    #[cfg(all(feature = "tract", not(feature = "onnx")))]
+    backends.push(Backend::from(tract::TractBackend::default()));
+    backends
 }
 
 /// A [Backend] contains the necessary state to load [Graph]s.
@@ -45,7 +57,7 @@ pub trait BackendGraph: Send + Sync {
 pub trait BackendExecutionContext: Send + Sync {
     fn set_input(&mut self, index: u32, tensor: &Tensor) -> Result<(), BackendError>;
     fn compute(&mut self) -> Result<(), BackendError>;
-    fn get_output(&mut self, index: u32, destination: &mut [u8]) -> Result<u32, BackendError>;
+    fn get_output(&mut self, index: u32) -> Result<Tensor, BackendError>;
 }
 
 /// Errors returned by a backend; [BackendError::BackendAccess] is a catch-all
@@ -60,4 +72,6 @@ pub enum BackendError {
     InvalidNumberOfBuilders(usize, usize),
     #[error("Not enough memory to copy tensor data of size: {0}")]
     NotEnoughMemory(usize),
+    #[error("The backend does not support the {0:?} execution target")]
+    UnsupportedTarget(ExecutionTarget),
 }
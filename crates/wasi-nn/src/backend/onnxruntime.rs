@@ -0,0 +1,226 @@
+//! Implements a `wasi-nn` [`BackendInner`] for ONNX models using [ONNX
+//! Runtime].
+//!
+//! [ONNX Runtime]: https://onnxruntime.ai
+
+use super::{BackendError, BackendExecutionContext, BackendFromDir, BackendGraph, BackendInner};
+use crate::wit::types::{ExecutionTarget, GraphEncoding, Tensor, TensorType};
+use crate::{ExecutionContext, Graph};
+use anyhow::anyhow;
+use ndarray::{ArrayD, CowArray, IxDyn};
+use ort::{
+    tensor::TensorElementDataType, Environment, ExecutionProvider, GraphOptimizationLevel,
+    OrtError, Session, SessionBuilder, Value,
+};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+#[derive(Default)]
+pub struct OnnxBackend(Option<Arc<Environment>>);
+
+impl BackendInner for OnnxBackend {
+    fn encoding(&self) -> GraphEncoding {
+        GraphEncoding::Onnx
+    }
+
+    fn load(&mut self, builders: &[&[u8]], target: ExecutionTarget) -> Result<Graph, BackendError> {
+        if builders.len() != 1 {
+            return Err(BackendError::InvalidNumberOfBuilders(1, builders.len()));
+        }
+        let execution_providers = execution_providers(target)?;
+
+        // As with OpenVINO, the environment is created lazily since this is
+        // the point at which the ONNX Runtime library is loaded.
+        if self.0.is_none() {
+            let environment = Environment::builder()
+                .with_name("wasmtime-wasi-nn")
+                .build()?
+                .into_arc();
+            self.0.replace(environment);
+        }
+        let environment = self
+            .0
+            .as_ref()
+            .expect("the ONNX Runtime environment was previously constructed");
+
+        let session = SessionBuilder::new(environment)?
+            .with_execution_providers(execution_providers)?
+            .with_optimization_level(GraphOptimizationLevel::Level3)?
+            .with_model_from_memory(builders[0])?;
+        let box_: Box<dyn BackendGraph> = Box::new(OnnxGraph(Arc::new(Mutex::new(session))));
+        Ok(box_.into())
+    }
+
+    fn as_dir_loadable(&mut self) -> Option<&mut dyn BackendFromDir> {
+        Some(self)
+    }
+}
+
+impl BackendFromDir for OnnxBackend {
+    fn load_from_dir(
+        &mut self,
+        path: &Path,
+        target: ExecutionTarget,
+    ) -> Result<Graph, BackendError> {
+        let model = std::fs::read(path.join("model.onnx")).map_err(anyhow::Error::from)?;
+        self.load(&[&model], target)
+    }
+}
+
+struct OnnxGraph(Arc<Mutex<Session>>);
+
+impl BackendGraph for OnnxGraph {
+    fn init_execution_context(&self) -> Result<ExecutionContext, BackendError> {
+        let inputs = self.0.lock().unwrap().inputs.len();
+        let box_: Box<dyn BackendExecutionContext> = Box::new(OnnxExecutionContext {
+            session: self.0.clone(),
+            inputs: (0..inputs).map(|_| None).collect(),
+            outputs: Vec::new(),
+        });
+        Ok(box_.into())
+    }
+}
+
+struct OnnxExecutionContext {
+    session: Arc<Mutex<Session>>,
+    inputs: Vec<Option<InputArray>>,
+    outputs: Vec<Tensor>,
+}
+
+/// An owned copy of a guest tensor; ONNX Runtime values borrow their data so
+/// these are only converted to [`Value`]s during `compute`.
+enum InputArray {
+    F32(CowArray<'static, f32, IxDyn>),
+    F64(CowArray<'static, f64, IxDyn>),
+    U8(CowArray<'static, u8, IxDyn>),
+    I32(CowArray<'static, i32, IxDyn>),
+    I64(CowArray<'static, i64, IxDyn>),
+}
+
+impl BackendExecutionContext for OnnxExecutionContext {
+    fn set_input(&mut self, index: u32, tensor: &Tensor) -> Result<(), BackendError> {
+        let slot = self
+            .inputs
+            .get_mut(index as usize)
+            .ok_or_else(|| anyhow!("the model has no input at index {index}"))?;
+        *slot = Some(to_input_array(tensor)?);
+        Ok(())
+    }
+
+    fn compute(&mut self) -> Result<(), BackendError> {
+        let session = self.session.lock().unwrap();
+        let mut values = Vec::with_capacity(self.inputs.len());
+        for (i, input) in self.inputs.iter().enumerate() {
+            let input = input
+                .as_ref()
+                .ok_or_else(|| anyhow!("input {i} has not been set"))?;
+            let allocator = session.allocator();
+            values.push(match input {
+                InputArray::F32(a) => Value::from_array(allocator, a)?,
+                InputArray::F64(a) => Value::from_array(allocator, a)?,
+                InputArray::U8(a) => Value::from_array(allocator, a)?,
+                InputArray::I32(a) => Value::from_array(allocator, a)?,
+                InputArray::I64(a) => Value::from_array(allocator, a)?,
+            });
+        }
+
+        let outputs = session.run(values)?;
+        self.outputs = outputs
+            .iter()
+            .zip(&session.outputs)
+            .map(|(value, output)| to_tensor(value, output.output_type))
+            .collect::<Result<_, _>>()?;
+        Ok(())
+    }
+
+    fn get_output(&mut self, index: u32) -> Result<Tensor, BackendError> {
+        let tensor = self
+            .outputs
+            .get(index as usize)
+            .ok_or_else(|| anyhow!("no output at index {index}; has compute been called?"))?;
+        Ok(tensor.clone())
+    }
+}
+
+impl From<OrtError> for BackendError {
+    fn from(e: OrtError) -> Self {
+        BackendError::BackendAccess(anyhow::Error::new(e))
+    }
+}
+
+/// Return the ONNX Runtime execution providers to try, in order, for the
+/// `ExecutionTarget` provided by wasi-nn. ONNX Runtime falls back to the CPU if
+/// none of them are available; it has no TPU execution providers.
+fn execution_providers(target: ExecutionTarget) -> Result<Vec<ExecutionProvider>, BackendError> {
+    match target {
+        ExecutionTarget::Cpu => Ok(vec![ExecutionProvider::CPU(Default::default())]),
+        ExecutionTarget::Gpu => Ok(vec![
+            ExecutionProvider::CUDA(Default::default()),
+            ExecutionProvider::DirectML(Default::default()),
+            ExecutionProvider::CoreML(Default::default()),
+        ]),
+        ExecutionTarget::Tpu => Err(BackendError::UnsupportedTarget(target)),
+    }
+}
+
+/// Convert a guest tensor, whose data is little-endian, into an owned array.
+fn to_input_array(tensor: &Tensor) -> Result<InputArray, BackendError> {
+    let shape = tensor
+        .dimensions
+        .iter()
+        .map(|&d| d as usize)
+        .collect::<Vec<_>>();
+    let data = &tensor.data;
+    Ok(match tensor.tensor_type {
+        TensorType::Fp32 => InputArray::F32(from_le_bytes(&shape, data, f32::from_le_bytes)?),
+        TensorType::Fp64 => InputArray::F64(from_le_bytes(&shape, data, f64::from_le_bytes)?),
+        TensorType::U8 => InputArray::U8(from_le_bytes(&shape, data, u8::from_le_bytes)?),
+        TensorType::I32 => InputArray::I32(from_le_bytes(&shape, data, i32::from_le_bytes)?),
+        TensorType::I64 => InputArray::I64(from_le_bytes(&shape, data, i64::from_le_bytes)?),
+        ty @ (TensorType::Fp16 | TensorType::Bf16) => {
+            return Err(anyhow!("tensor type {ty:?} is not supported by the ONNX backend").into())
+        }
+    })
+}
+
+fn from_le_bytes<T, const N: usize>(
+    shape: &[usize],
+    data: &[u8],
+    convert: fn([u8; N]) -> T,
+) -> Result<CowArray<'static, T, IxDyn>, BackendError> {
+    if data.len() % N != 0 {
+        return Err(anyhow!("tensor data is not a multiple of the element size").into());
+    }
+    let values = data
+        .chunks_exact(N)
+        .map(|chunk| convert(chunk.try_into().unwrap()))
+        .collect::<Vec<_>>();
+    let array = ArrayD::from_shape_vec(IxDyn(shape), values).map_err(anyhow::Error::from)?;
+    Ok(CowArray::from(array))
+}
+
+/// Convert an output value into a tensor with the little-endian data expected
+/// by the guest.
+fn to_tensor(value: &Value, ty: TensorElementDataType) -> Result<Tensor, BackendError> {
+    macro_rules! extract {
+        ($t:ty, $tensor_type:ident) => {{
+            let array = value.try_extract::<$t>()?;
+            let view = array.view();
+            Tensor {
+                dimensions: view.shape().iter().map(|&d| d as u32).collect(),
+                tensor_type: TensorType::$tensor_type,
+                data: view.iter().flat_map(|v| v.to_le_bytes()).collect(),
+            }
+        }};
+    }
+    Ok(match ty {
+        TensorElementDataType::Float32 => extract!(f32, Fp32),
+        TensorElementDataType::Float64 => extract!(f64, Fp64),
+        TensorElementDataType::Uint8 => extract!(u8, U8),
+        TensorElementDataType::Int32 => extract!(i32, I32),
+        TensorElementDataType::Int64 => extract!(i64, I64),
+        ty => {
+            return Err(anyhow!("output type {ty:?} is not supported by the ONNX backend").into())
+        }
+    })
+}
@@ -121,17 +121,15 @@ impl BackendExecutionContext for OpenvinoExecutionContext {
         Ok(())
     }
 
-    fn get_output(&mut self, index: u32, destination: &mut [u8]) -> Result<u32, BackendError> {
+    fn get_output(&mut self, index: u32) -> Result<Tensor, BackendError> {
         let output_name = self.0.get_output_name(index as usize)?;
         let blob = self.1.get_blob(&output_name)?;
-        let blob_size = blob.byte_len()?;
-        if blob_size > destination.len() {
-            return Err(BackendError::NotEnoughMemory(blob_size));
-        }
-
-        // Copy the tensor data into the destination buffer.
-        destination[..blob_size].copy_from_slice(blob.buffer()?);
-        Ok(blob_size as u32)
+        let desc = blob.tensor_desc()?;
+        Ok(Tensor {
+            dimensions: desc.dims().iter().map(|&d| d as u32).collect(),
+            tensor_type: map_precision_to_tensor_type(desc.precision())?,
+            data: blob.buffer()?.to_vec(),
+        })
     }
 }
 
@@ -171,6 +169,19 @@ fn map_tensor_type_to_precision(tensor_type: TensorType) -> openvino::Precision
     }
 }
 
+/// Return the wasi-nn `TensorType` for an OpenVINO precision.
+fn map_precision_to_tensor_type(precision: Precision) -> Result<TensorType, BackendError> {
+    Ok(match precision {
+        Precision::FP16 => TensorType::Fp16,
+        Precision::FP32 => TensorType::Fp32,
+        Precision::FP64 => TensorType::Fp64,
+        Precision::U8 => TensorType::U8,
+        Precision::I32 => TensorType::I32,
+        Precision::I64 => TensorType::I64,
+        p => return Err(anyhow::anyhow!("unsupported output precision: {p:?}").into()),
+    })
+}
+
 /// Read a file into a byte vector.
 fn read(path: &Path) -> anyhow::Result<Vec<u8>> {
     let mut file = File::open(path)?;
@@ -0,0 +1,288 @@
+//! Implements a `wasi-nn` [`BackendInner`] for ONNX models using [tract], a
+//! pure-Rust inference engine. Unlike the other backends this requires no
+//! native libraries to be installed, which makes it a convenient default for
+//! testing.
+//!
+//! [tract]: https://github.com/sonos/tract
+
+use super::{BackendError, BackendExecutionContext, BackendFromDir, BackendGraph, BackendInner};
+use crate::wit::types::{ExecutionTarget, GraphEncoding, Tensor, TensorType};
+use crate::{ExecutionContext, Graph};
+use anyhow::anyhow;
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::Arc;
+use tract_onnx::prelude::{
+    Datum, DatumType, Framework, InferenceFact, InferenceModel, InferenceModelExt, TValue, TVec,
+    Tensor as TractTensor, TypedFact, TypedModel, TypedRunnableModel,
+};
+
+#[derive(Default)]
+pub struct TractBackend;
+
+impl BackendInner for TractBackend {
+    fn encoding(&self) -> GraphEncoding {
+        GraphEncoding::Onnx
+    }
+
+    fn load(&mut self, builders: &[&[u8]], target: ExecutionTarget) -> Result<Graph, BackendError> {
+        if builders.len() != 1 {
+            return Err(BackendError::InvalidNumberOfBuilders(1, builders.len()));
+        }
+        if !matches!(target, ExecutionTarget::Cpu) {
+            return Err(BackendError::UnsupportedTarget(target));
+        }
+
+        let model = tract_onnx::onnx().model_for_read(&mut Cursor::new(builders[0]))?;
+        let inputs = model.input_outlets()?.len();
+        let box_: Box<dyn BackendGraph> = Box::new(TractGraph {
+            model: Arc::new(model),
+            inputs,
+        });
+        Ok(box_.into())
+    }
+
+    fn as_dir_loadable(&mut self) -> Option<&mut dyn BackendFromDir> {
+        Some(self)
+    }
+}
+
+impl BackendFromDir for TractBackend {
+    fn load_from_dir(
+        &mut self,
+        path: &Path,
+        target: ExecutionTarget,
+    ) -> Result<Graph, BackendError> {
+        let model = std::fs::read(path.join("model.onnx")).map_err(anyhow::Error::from)?;
+        self.load(&[&model], target)
+    }
+}
+
+struct TractGraph {
+    /// The model as parsed from the ONNX file; input shapes are only known
+    /// once the guest sets them, so optimization is deferred until
+    /// `compute`.
+    model: Arc<InferenceModel>,
+    inputs: usize,
+}
+
+impl BackendGraph for TractGraph {
+    fn init_execution_context(&self) -> Result<ExecutionContext, BackendError> {
+        let box_: Box<dyn BackendExecutionContext> = Box::new(TractExecutionContext {
+            model: self.model.clone(),
+            inputs: vec![None; self.inputs],
+            outputs: TVec::new(),
+            plan: None,
+        });
+        Ok(box_.into())
+    }
+}
+
+/// The shape and type of each input, used to decide whether a previously
+/// optimized plan can be reused.
+type InputFacts = Vec<(DatumType, Vec<usize>)>;
+
+struct TractExecutionContext {
+    model: Arc<InferenceModel>,
+    inputs: Vec<Option<TractTensor>>,
+    outputs: TVec<TValue>,
+    plan: Option<(InputFacts, Arc<TypedRunnableModel<TypedModel>>)>,
+}
+
+impl TractExecutionContext {
+    /// Return a runnable plan for the given inputs, optimizing the model again
+    /// only if the inputs differ from the ones of the previous computation.
+    fn plan(
+        &mut self,
+        facts: InputFacts,
+    ) -> Result<Arc<TypedRunnableModel<TypedModel>>, BackendError> {
+        if let Some((previous, plan)) = &self.plan {
+            if *previous == facts {
+                return Ok(plan.clone());
+            }
+        }
+
+        let mut model = (*self.model).clone();
+        for (i, (datum_type, shape)) in facts.iter().enumerate() {
+            let fact: InferenceFact = TypedFact::dt_shape(*datum_type, shape.clone()).into();
+            model = model.with_input_fact(i, fact)?;
+        }
+        let plan = Arc::new(model.into_optimized()?.into_runnable()?);
+        self.plan = Some((facts, plan.clone()));
+        Ok(plan)
+    }
+}
+
+impl BackendExecutionContext for TractExecutionContext {
+    fn set_input(&mut self, index: u32, tensor: &Tensor) -> Result<(), BackendError> {
+        let slot = self
+            .inputs
+            .get_mut(index as usize)
+            .ok_or_else(|| anyhow!("the model has no input at index {index}"))?;
+        *slot = Some(to_tract_tensor(tensor)?);
+        Ok(())
+    }
+
+    fn compute(&mut self) -> Result<(), BackendError> {
+        let inputs = self
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(i, t)| {
+                t.clone()
+                    .ok_or_else(|| anyhow!("input {i} has not been set"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let facts = inputs
+            .iter()
+            .map(|t| (t.datum_type(), t.shape().to_vec()))
+            .collect();
+        let plan = self.plan(facts)?;
+        self.outputs = plan.run(inputs.into_iter().map(TValue::from).collect())?;
+        Ok(())
+    }
+
+    fn get_output(&mut self, index: u32) -> Result<Tensor, BackendError> {
+        let tensor = self
+            .outputs
+            .get(index as usize)
+            .ok_or_else(|| anyhow!("no output at index {index}; has compute been called?"))?;
+        from_tract_tensor(tensor)
+    }
+}
+
+/// Convert a guest tensor, whose data is little-endian, into a tract tensor.
+fn to_tract_tensor(tensor: &Tensor) -> Result<TractTensor, BackendError> {
+    let shape = tensor
+        .dimensions
+        .iter()
+        .map(|&d| d as usize)
+        .collect::<Vec<_>>();
+    let data = &tensor.data;
+    match tensor.tensor_type {
+        TensorType::Fp32 => from_le_bytes(&shape, data, f32::from_le_bytes),
+        TensorType::Fp64 => from_le_bytes(&shape, data, f64::from_le_bytes),
+        TensorType::U8 => from_le_bytes(&shape, data, u8::from_le_bytes),
+        TensorType::I32 => from_le_bytes(&shape, data, i32::from_le_bytes),
+        TensorType::I64 => from_le_bytes(&shape, data, i64::from_le_bytes),
+        ty @ (TensorType::Fp16 | TensorType::Bf16) => {
+            Err(anyhow!("tensor type {ty:?} is not supported by the tract backend").into())
+        }
+    }
+}
+
+fn from_le_bytes<T: Datum + Copy, const N: usize>(
+    shape: &[usize],
+    data: &[u8],
+    convert: fn([u8; N]) -> T,
+) -> Result<TractTensor, BackendError> {
+    if data.len() % N != 0 {
+        return Err(anyhow!("tensor data is not a multiple of the element size").into());
+    }
+    let values = data
+        .chunks_exact(N)
+        .map(|chunk| convert(chunk.try_into().unwrap()))
+        .collect::<Vec<_>>();
+    Ok(TractTensor::from_shape(shape, &values)?)
+}
+
+/// Convert a tract tensor into a tensor with the little-endian data expected by
+/// the guest.
+fn from_tract_tensor(tensor: &TractTensor) -> Result<Tensor, BackendError> {
+    fn collect<T: Datum, const N: usize>(
+        tensor: &TractTensor,
+        convert: fn(&T) -> [u8; N],
+    ) -> Result<Vec<u8>, BackendError> {
+        Ok(tensor.as_slice::<T>()?.iter().flat_map(convert).collect())
+    }
+    let (tensor_type, data) = match tensor.datum_type() {
+        DatumType::F32 => (
+            TensorType::Fp32,
+            collect(tensor, |v: &f32| v.to_le_bytes())?,
+        ),
+        DatumType::F64 => (
+            TensorType::Fp64,
+            collect(tensor, |v: &f64| v.to_le_bytes())?,
+        ),
+        DatumType::U8 => (TensorType::U8, collect(tensor, |v: &u8| v.to_le_bytes())?),
+        DatumType::I32 => (TensorType::I32, collect(tensor, |v: &i32| v.to_le_bytes())?),
+        DatumType::I64 => (TensorType::I64, collect(tensor, |v: &i64| v.to_le_bytes())?),
+        ty => {
+            return Err(anyhow!("output type {ty:?} is not supported by the tract backend").into())
+        }
+    };
+    Ok(Tensor {
+        dimensions: tensor.shape().iter().map(|&d| d as u32).collect(),
+        tensor_type,
+        data,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Encode a length-delimited protobuf field.
+    fn field(number: u8, contents: &[u8]) -> Vec<u8> {
+        assert!(contents.len() < 128);
+        let mut bytes = vec![number << 3 | 2, contents.len() as u8];
+        bytes.extend_from_slice(contents);
+        bytes
+    }
+
+    /// Encode a `ValueInfoProto` for a one-dimensional float tensor.
+    fn value_info(name: &str, len: u8) -> Vec<u8> {
+        let dim = field(1, &[1 << 3, len]);
+        let tensor = [vec![1 << 3, 1], field(2, &dim)].concat();
+        [field(1, name.as_bytes()), field(2, &field(1, &tensor))].concat()
+    }
+
+    /// Hand-encode an ONNX model which applies `Relu` to a `float[4]`.
+    fn relu_model() -> Vec<u8> {
+        let node = [field(1, b"x"), field(2, b"y"), field(4, b"Relu")].concat();
+        let graph = [
+            field(1, &node),
+            field(2, b"relu"),
+            field(11, &value_info("x", 4)),
+            field(12, &value_info("y", 4)),
+        ]
+        .concat();
+        let opset = [2 << 3, 13];
+        [vec![1 << 3, 7], field(7, &graph), field(8, &opset)].concat()
+    }
+
+    #[test]
+    fn relu() {
+        let graph = TractBackend
+            .load(&[&relu_model()], ExecutionTarget::Cpu)
+            .unwrap();
+        let mut context = graph.init_execution_context().unwrap();
+        let input = [-1.0f32, 2.0, -3.0, 4.0];
+        let tensor = Tensor {
+            dimensions: vec![4],
+            tensor_type: TensorType::Fp32,
+            data: input.iter().flat_map(|v| v.to_le_bytes()).collect(),
+        };
+        context.set_input(0, &tensor).unwrap();
+        context.compute().unwrap();
+
+        let output = context.get_output(0).unwrap();
+        assert_eq!(output.dimensions, [4]);
+        assert!(matches!(output.tensor_type, TensorType::Fp32));
+        let output = output
+            .data
+            .chunks_exact(4)
+            .map(|c| f32::from_le_bytes(c.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(output, [0.0, 2.0, 0.0, 4.0]);
+    }
+
+    #[test]
+    fn unsupported_target() {
+        let result = TractBackend.load(&[&relu_model()], ExecutionTarget::Tpu);
+        assert!(matches!(
+            result,
+            Err(BackendError::UnsupportedTarget(ExecutionTarget::Tpu))
+        ));
+    }
+}
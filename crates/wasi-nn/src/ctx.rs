@@ -4,6 +4,7 @@ use crate::backend::{self, BackendError};
 use crate::wit::types::GraphEncoding;
 use crate::{Backend, ExecutionContext, Graph, InMemoryRegistry, Registry};
 use anyhow::anyhow;
+use std::sync::{Arc, Mutex};
 use std::{collections::HashMap, hash::Hash, path::Path};
 use thiserror::Error;
 use wiggle::GuestError;
@@ -36,8 +37,11 @@ pub fn preload(
 }
 
 /// Capture the state necessary for calling into the backend ML libraries.
+///
+/// Cloning a context, e.g. for another store, shares its backends and
+/// registry; the graphs and execution contexts of the guest are not shared.
 pub struct WasiNnCtx {
-    pub(crate) backends: HashMap<GraphEncoding, Backend>,
+    pub(crate) backends: Arc<HashMap<GraphEncoding, Mutex<Backend>>>,
     pub(crate) registry: Registry,
     pub(crate) graphs: Table<GraphId, Graph>,
     pub(crate) executions: Table<GraphExecutionContextId, ExecutionContext>,
@@ -46,9 +50,12 @@ pub struct WasiNnCtx {
 impl WasiNnCtx {
     /// Make a new context from the default state.
     pub fn new(backends: impl IntoIterator<Item = Backend>, registry: Registry) -> Self {
-        let backends = backends.into_iter().map(|b| (b.encoding(), b)).collect();
+        let backends = backends
+            .into_iter()
+            .map(|b| (b.encoding(), Mutex::new(b)))
+            .collect();
         Self {
-            backends,
+            backends: Arc::new(backends),
             registry,
             graphs: Table::default(),
             executions: Table::default(),
@@ -56,6 +63,17 @@ impl WasiNnCtx {
     }
 }
 
+impl Clone for WasiNnCtx {
+    fn clone(&self) -> Self {
+        Self {
+            backends: self.backends.clone(),
+            registry: self.registry.clone(),
+            graphs: Table::default(),
+            executions: Table::default(),
+        }
+    }
+}
+
 /// Possible errors while interacting with [WasiNnCtx].
 #[derive(Debug, Error)]
 pub enum WasiNnError {
@@ -71,7 +89,7 @@ pub enum WasiNnError {
 pub enum UsageError {
     #[error("Invalid context; has the load function been called?")]
     InvalidContext,
-    #[error("Only OpenVINO's IR is currently supported, passed encoding: {0:?}")]
+    #[error("No backend is available for the passed encoding: {0:?}")]
     InvalidEncoding(GraphEncoding),
     #[error("OpenVINO expects only two buffers (i.e. [ir, weights]), passed: {0}")]
     InvalidNumberOfBuilders(u32),
@@ -112,10 +130,6 @@ where
         key
     }
 
-    pub fn get(&self, key: K) -> Option<&V> {
-        self.entries.get(&key)
-    }
-
     pub fn get_mut(&mut self, key: K) -> Option<&mut V> {
         self.entries.get_mut(&key)
     }
@@ -134,6 +148,7 @@ mod test {
 
     #[test]
     fn example() {
+        #[derive(Clone)]
         struct FakeRegistry;
         impl GraphRegistry for FakeRegistry {
             fn get_mut(&mut self, _: &str) -> Option<&mut Graph> {
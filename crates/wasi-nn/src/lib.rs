@@ -3,7 +3,7 @@ mod registry;
 
 pub mod backend;
 pub use ctx::{preload, WasiNnCtx};
-pub use registry::{GraphRegistry, InMemoryRegistry};
+pub use registry::{GraphRegistry, InMemoryRegistry, ManifestRegistry};
 pub mod wit;
 pub mod witx;
 
@@ -62,22 +62,27 @@ impl std::ops::DerefMut for ExecutionContext {
     }
 }
 
-/// A container for graphs.
-pub struct Registry(Box<dyn GraphRegistry>);
+/// A container for graphs; cloning it shares the graphs.
+pub struct Registry(Box<dyn registry::CloneRegistry>);
+impl Clone for Registry {
+    fn clone(&self) -> Self {
+        Self(self.0.clone_box())
+    }
+}
 impl std::ops::Deref for Registry {
     type Target = dyn GraphRegistry;
     fn deref(&self) -> &Self::Target {
-        self.0.as_ref()
+        self.0.as_registry()
     }
 }
 impl std::ops::DerefMut for Registry {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        self.0.as_mut()
+        self.0.as_registry_mut()
     }
 }
 impl<T> From<T> for Registry
 where
-    T: GraphRegistry + 'static,
+    T: GraphRegistry + Clone + 'static,
 {
     fn from(value: T) -> Self {
         Self(Box::new(value))
@@ -6,6 +6,7 @@ use crate::wit::types::ExecutionTarget;
 use anyhow::{anyhow, bail};
 use std::{collections::HashMap, path::Path};
 
+#[derive(Clone)]
 pub struct InMemoryRegistry(HashMap<String, Graph>);
 impl InMemoryRegistry {
     pub fn new() -> Self {
@@ -0,0 +1,321 @@
+//! Implement a [`GraphRegistry`] described by a manifest file.
+//!
+//! The manifest is a TOML file listing models by name:
+//!
+//! ```toml
+//! # The maximum number of graphs kept loaded at once (optional).
+//! max-loaded = 16
+//!
+//! [[model]]
+//! name = "mobilenet"
+//! encoding = "openvino"
+//! target = "cpu" # optional, the default
+//! files = ["mobilenet/model.xml", "mobilenet/model.bin"]
+//! ```
+//!
+//! File paths are relative to the directory containing the manifest. Graphs
+//! are only loaded the first time they are requested and the least recently
+//! used graphs are unloaded once more than `max-loaded` of them are loaded.
+//! The registry can be cloned cheaply so that many stores share the loaded
+//! graphs.
+
+use super::{Graph, GraphRegistry};
+use crate::backend::BackendError;
+use crate::wit::types::{ExecutionTarget, GraphEncoding};
+use crate::Backend;
+use anyhow::{anyhow, bail, Context};
+use serde_derive::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// The default for `max-loaded`.
+const DEFAULT_MAX_LOADED: usize = 16;
+
+#[derive(Clone)]
+pub struct ManifestRegistry {
+    inner: Arc<Inner>,
+    /// The graph last returned by [`GraphRegistry::get_mut`], which only
+    /// lends graphs.
+    last: Option<Graph>,
+}
+
+struct Inner {
+    models: HashMap<String, Model>,
+    /// Each backend is only locked while it turns the files of a model, read
+    /// beforehand, into a graph; holding this lock also prevents two stores
+    /// from loading the same graph at once.
+    backends: HashMap<GraphEncoding, Mutex<Backend>>,
+    loaded: Mutex<Loaded>,
+}
+
+struct Model {
+    encoding: GraphEncoding,
+    target: ExecutionTarget,
+    files: Vec<PathBuf>,
+}
+
+/// The currently loaded graphs, each tagged with the last time it was used.
+struct Loaded {
+    max: usize,
+    clock: u64,
+    graphs: HashMap<String, (Graph, u64)>,
+}
+
+impl Loaded {
+    fn get(&mut self, name: &str) -> Option<Graph> {
+        self.clock += 1;
+        let (graph, last_used) = self.graphs.get_mut(name)?;
+        *last_used = self.clock;
+        Some(graph.clone())
+    }
+
+    fn insert(&mut self, name: &str, graph: Graph) {
+        self.clock += 1;
+        self.graphs.insert(name.to_string(), (graph, self.clock));
+        while self.graphs.len() > self.max {
+            let oldest = self
+                .graphs
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(name, _)| name.clone())
+                .unwrap();
+            tracing::debug!("unloading wasi-nn graph {oldest}");
+            self.graphs.remove(&oldest);
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct Manifest {
+    max_loaded: Option<usize>,
+    #[serde(default)]
+    model: Vec<ManifestModel>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ManifestModel {
+    name: String,
+    encoding: String,
+    target: Option<String>,
+    files: Vec<PathBuf>,
+}
+
+impl ManifestRegistry {
+    /// Read the manifest at `path`; the models it lists are loaded with the
+    /// given `backends`.
+    ///
+    /// This checks that each model has a backend and that its files exist but
+    /// does not load any graph.
+    pub fn from_file(
+        path: &Path,
+        backends: impl IntoIterator<Item = Backend>,
+    ) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read manifest: {}", path.display()))?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        Self::from_str(&contents, base, backends)
+            .with_context(|| format!("invalid manifest: {}", path.display()))
+    }
+
+    fn from_str(
+        contents: &str,
+        base: &Path,
+        backends: impl IntoIterator<Item = Backend>,
+    ) -> anyhow::Result<Self> {
+        let manifest: Manifest = toml::from_str(contents)?;
+        let backends: HashMap<_, _> = backends.into_iter().map(|b| (b.encoding(), b)).collect();
+
+        let mut models = HashMap::new();
+        for model in manifest.model {
+            let encoding: GraphEncoding = model.encoding.parse()?;
+            if !backends.contains_key(&encoding) {
+                bail!(
+                    "no backend available for model `{}` with encoding `{}`",
+                    model.name,
+                    model.encoding
+                );
+            }
+            let target = match model.target.as_deref().map(str::to_lowercase).as_deref() {
+                None | Some("cpu") => ExecutionTarget::Cpu,
+                Some("gpu") => ExecutionTarget::Gpu,
+                Some("tpu") => ExecutionTarget::Tpu,
+                Some(other) => bail!("unknown execution target for `{}`: {other}", model.name),
+            };
+            let files = model.files.iter().map(|f| base.join(f)).collect::<Vec<_>>();
+            if let Some(missing) = files.iter().find(|f| !f.is_file()) {
+                bail!(
+                    "file for model `{}` does not exist: {}",
+                    model.name,
+                    missing.display()
+                );
+            }
+            let model_ = Model {
+                encoding,
+                target,
+                files,
+            };
+            if models.insert(model.name.clone(), model_).is_some() {
+                bail!("duplicate model name: {}", model.name);
+            }
+        }
+
+        let max = manifest.max_loaded.unwrap_or(DEFAULT_MAX_LOADED);
+        if max == 0 {
+            bail!("`max-loaded` must be at least 1");
+        }
+        let backends = backends
+            .into_iter()
+            .map(|(encoding, backend)| (encoding, Mutex::new(backend)))
+            .collect();
+        Ok(Self {
+            inner: Arc::new(Inner {
+                models,
+                backends,
+                loaded: Mutex::new(Loaded {
+                    max,
+                    clock: 0,
+                    graphs: HashMap::new(),
+                }),
+            }),
+            last: None,
+        })
+    }
+
+    /// Return the names of all models listed in the manifest.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.inner.models.keys().map(|s| s.as_str())
+    }
+
+    /// Return the number of graphs currently loaded.
+    pub fn loaded(&self) -> usize {
+        self.inner.loaded.lock().unwrap().graphs.len()
+    }
+}
+
+impl GraphRegistry for ManifestRegistry {
+    fn get_mut(&mut self, name: &str) -> Option<&mut Graph> {
+        self.last = match self.get_or_load(name) {
+            Ok(graph) => graph,
+            Err(e) => {
+                tracing::warn!("failed to load wasi-nn graph {name}: {e:?}");
+                None
+            }
+        };
+        self.last.as_mut()
+    }
+
+    fn get_or_load(&mut self, name: &str) -> Result<Option<Graph>, BackendError> {
+        let inner = &*self.inner;
+        let model = match inner.models.get(name) {
+            Some(model) => model,
+            None => return Ok(None),
+        };
+        if let Some(graph) = inner.loaded.lock().unwrap().get(name) {
+            return Ok(Some(graph));
+        }
+
+        tracing::debug!("loading wasi-nn graph {name}");
+        let contents = model
+            .files
+            .iter()
+            .map(|f| std::fs::read(f).with_context(|| format!("failed to read {}", f.display())))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let slices = contents.iter().map(|c| c.as_slice()).collect::<Vec<_>>();
+        let mut backend = inner
+            .backends
+            .get(&model.encoding)
+            .ok_or_else(|| anyhow!("no backend for model `{name}`"))?
+            .lock()
+            .unwrap();
+        // Another store may have loaded the graph while waiting for the lock.
+        if let Some(graph) = inner.loaded.lock().unwrap().get(name) {
+            return Ok(Some(graph));
+        }
+        let graph = backend.load(&slices, model.target)?;
+        drop(backend);
+        inner.loaded.lock().unwrap().insert(name, graph.clone());
+        Ok(Some(graph))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::backend::{BackendFromDir, BackendGraph, BackendInner};
+    use crate::ExecutionContext;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tempfile::TempDir;
+
+    /// A backend which counts how many graphs it has loaded.
+    struct CountingBackend(Arc<AtomicUsize>);
+
+    impl BackendInner for CountingBackend {
+        fn encoding(&self) -> GraphEncoding {
+            GraphEncoding::Onnx
+        }
+        fn load(&mut self, builders: &[&[u8]], _: ExecutionTarget) -> Result<Graph, BackendError> {
+            assert_eq!(builders, [b"model"]);
+            self.0.fetch_add(1, Ordering::SeqCst);
+            let graph: Box<dyn BackendGraph> = Box::new(FakeGraph);
+            Ok(graph.into())
+        }
+        fn as_dir_loadable(&mut self) -> Option<&mut dyn BackendFromDir> {
+            None
+        }
+    }
+
+    struct FakeGraph;
+
+    impl BackendGraph for FakeGraph {
+        fn init_execution_context(&self) -> Result<ExecutionContext, BackendError> {
+            Err(anyhow!("fake graphs cannot be executed").into())
+        }
+    }
+
+    fn registry(max_loaded: usize) -> (ManifestRegistry, Arc<AtomicUsize>, TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("model.onnx"), b"model").unwrap();
+        let manifest = format!(
+            "max-loaded = {max_loaded}\n\
+             [[model]]\nname = \"a\"\nencoding = \"onnx\"\nfiles = [\"model.onnx\"]\n\
+             [[model]]\nname = \"b\"\nencoding = \"onnx\"\nfiles = [\"model.onnx\"]\n"
+        );
+        let loads = Arc::new(AtomicUsize::new(0));
+        let backend = Backend::from(CountingBackend(loads.clone()));
+        let registry = ManifestRegistry::from_str(&manifest, dir.path(), [backend]).unwrap();
+        (registry, loads, dir)
+    }
+
+    #[test]
+    fn loads_lazily_and_shares_graphs() {
+        let (mut registry, loads, _dir) = registry(2);
+        let mut other = registry.clone();
+        assert_eq!(loads.load(Ordering::SeqCst), 0);
+        assert!(registry.get_or_load("a").unwrap().is_some());
+        assert!(other.get_mut("a").is_some());
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+        assert!(registry.get_or_load("missing").unwrap().is_none());
+        assert!(registry.get_mut("missing").is_none());
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let (mut registry, loads, _dir) = registry(1);
+        registry.get_or_load("a").unwrap();
+        registry.get_or_load("b").unwrap();
+        assert_eq!(registry.loaded(), 1);
+        registry.get_or_load("b").unwrap();
+        assert_eq!(loads.load(Ordering::SeqCst), 2);
+        registry.get_or_load("a").unwrap();
+        assert_eq!(loads.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn rejects_unknown_encodings() {
+        let manifest = "[[model]]\nname = \"a\"\nencoding = \"pytorch\"\nfiles = []\n";
+        assert!(ManifestRegistry::from_str(manifest, Path::new("."), []).is_err());
+    }
+}
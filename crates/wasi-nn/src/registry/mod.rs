@@ -4,13 +4,43 @@
 //! by name. This API does not mandate how a graph is loaded or how it must be
 //! stored--it could be stored remotely and rematerialized when needed, e.g. A
 //! naive in-memory implementation, [`InMemoryRegistry`] is provided for use
-//! with the Wasmtime CLI.
+//! with the Wasmtime CLI, as is [`ManifestRegistry`], which lazily loads the
+//! models described by a manifest file.
 
 mod in_memory;
+mod manifest;
 
+use crate::backend::BackendError;
 use crate::Graph;
 pub use in_memory::InMemoryRegistry;
+pub use manifest::ManifestRegistry;
 
 pub trait GraphRegistry: Send + Sync {
     fn get_mut(&mut self, name: &str) -> Option<&mut Graph>;
+
+    /// Return the graph named `name`, if any. Registries which load graphs
+    /// upon request override this to report why loading failed.
+    fn get_or_load(&mut self, name: &str) -> Result<Option<Graph>, BackendError> {
+        Ok(self.get_mut(name).cloned())
+    }
+}
+
+/// The registries [`crate::Registry`] can hold, which must be cloneable so
+/// that contexts can share them.
+pub(crate) trait CloneRegistry: GraphRegistry {
+    fn clone_box(&self) -> Box<dyn CloneRegistry>;
+    fn as_registry(&self) -> &(dyn GraphRegistry + 'static);
+    fn as_registry_mut(&mut self) -> &mut (dyn GraphRegistry + 'static);
+}
+
+impl<T: GraphRegistry + Clone + 'static> CloneRegistry for T {
+    fn clone_box(&self) -> Box<dyn CloneRegistry> {
+        Box::new(self.clone())
+    }
+    fn as_registry(&self) -> &(dyn GraphRegistry + 'static) {
+        self
+    }
+    fn as_registry_mut(&mut self) -> &mut (dyn GraphRegistry + 'static) {
+        self
+    }
 }
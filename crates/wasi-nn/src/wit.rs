@@ -4,138 +4,283 @@
 //! (though it could be) so by "preview2" here we mean that this can be called
 //! with the component model's canonical ABI.
 //!
-//! This module exports its [`types`] for use throughout the crate and the
-//! [`ML`] object, which exposes [`ML::add_to_linker`]. To implement all of
-//! this, this module proceeds in steps:
+//! This module exports its [`types`] for use throughout the crate, the
+//! [`WasiNnView`] trait which embedders implement for their store's data and
+//! [`add_to_linker`]. To implement all of this, this module proceeds in steps:
 //! 1. generate all of the WIT glue code into a `gen::*` namespace
 //! 2. wire up the `gen::*` glue to the context state, delegating actual
 //!    computation to a [`Backend`]
 //! 3. convert some types
 //!
+//! Graphs, execution contexts and tensors are all resources stored in the
+//! view's [`ResourceTable`]. The `compute` function is `async` and runs
+//! inference on Tokio's blocking thread pool so that it does not block the
+//! executor; [`sync::add_to_linker`] provides the same interfaces for
+//! synchronous embeddings.
+//!
 //! [`Backend`]: crate::Backend
 //! [`types`]: crate::wit::types
 
-use crate::{ctx::UsageError, WasiNnCtx};
+use crate::backend::BackendError;
+use crate::{ctx::UsageError, Graph, WasiNnCtx};
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
 use std::{error::Error, fmt, hash::Hash, str::FromStr};
+use wasmtime::component::{Linker, Resource, ResourceTable};
 
 /// Generate the traits and types from the `wasi-nn` WIT specification.
 mod gen_ {
-    wasmtime::component::bindgen!("ml" in "spec/wit/wasi-nn.wit");
+    wasmtime::component::bindgen!({
+        world: "ml",
+        path: "wit/wasi-nn.wit",
+        tracing: true,
+        async: {
+            only_imports: ["[method]graph-execution-context.compute"],
+        },
+        with: {
+            "wasi:nn/tensor/tensor": super::types::Tensor,
+            "wasi:nn/graph/graph": super::types::Graph,
+            "wasi:nn/inference/graph-execution-context": super::types::GraphExecutionContext,
+        },
+    });
 }
 use gen_::wasi::nn as gen; // Shortcut to the module containing the types we need.
 
 // Export the `types` used in this crate as well as `ML::add_to_linker`.
 pub mod types {
     use super::gen;
-    pub use gen::graph::{ExecutionTarget, Graph, GraphEncoding};
-    pub use gen::inference::GraphExecutionContext;
-    pub use gen::tensor::{Tensor, TensorType};
+    use crate::ExecutionContext;
+    use std::sync::{Arc, Mutex};
+
+    pub use crate::Graph;
+    pub use gen::graph::{ExecutionTarget, GraphEncoding};
+    pub use gen::tensor::TensorType;
+
+    /// A tensor passed to or returned from a backend; this is also the host
+    /// representation of the `tensor` resource.
+    #[derive(Clone, Debug)]
+    pub struct Tensor {
+        pub dimensions: Vec<u32>,
+        pub tensor_type: TensorType,
+        pub data: Vec<u8>,
+    }
+
+    /// The host representation of the `graph-execution-context` resource.
+    ///
+    /// The context is shared with the blocking task running `compute`.
+    pub struct GraphExecutionContext(pub(crate) Arc<Mutex<ExecutionContext>>);
 }
 pub use gen_::Ml as ML;
+use types::{GraphExecutionContext, Tensor};
+
+/// Provides access to the state used by the `wasi:nn` interfaces.
+pub trait WasiNnView: Send {
+    fn ctx(&mut self) -> &mut WasiNnCtx;
+    fn table(&mut self) -> &mut ResourceTable;
+}
 
-impl gen::graph::Host for WasiNnCtx {
+/// Add the `wasi:nn` interfaces to `linker`.
+///
+/// This requires a [`Config`](wasmtime::Config) with async support and must be
+/// used from within a Tokio runtime.
+pub fn add_to_linker<T: WasiNnView>(linker: &mut Linker<T>) -> Result<()> {
+    gen::tensor::add_to_linker(linker, |t| t)?;
+    gen::graph::add_to_linker(linker, |t| t)?;
+    gen::inference::add_to_linker(linker, |t| t)?;
+    gen::errors::add_to_linker(linker, |t| t)?;
+    Ok(())
+}
+
+impl<T: WasiNnView> gen::graph::Host for T {
     /// Load an opaque sequence of bytes to use for inference.
     fn load(
         &mut self,
         builders: Vec<gen::graph::GraphBuilder>,
         encoding: gen::graph::GraphEncoding,
         target: gen::graph::ExecutionTarget,
-    ) -> wasmtime::Result<Result<gen::graph::Graph, gen::errors::Error>> {
-        let graph = if let Some(backend) = self.backends.get_mut(&encoding) {
+    ) -> Result<Result<Resource<Graph>, gen::errors::Error>> {
+        let graph = if let Some(backend) = self.ctx().backends.get(&encoding) {
             let slices = builders.iter().map(|s| s.as_slice()).collect::<Vec<_>>();
-            backend.load(&slices, target.into())?
+            match backend.lock().unwrap().load(&slices, target) {
+                Ok(graph) => graph,
+                Err(e) => return Ok(Err(e.into())),
+            }
         } else {
-            return Err(UsageError::InvalidEncoding(encoding.into()).into());
+            return Ok(Err(UsageError::InvalidEncoding(encoding).into()));
         };
-        let graph_id = self.graphs.insert(graph);
-        Ok(Ok(graph_id))
+        Ok(Ok(self.table().push(graph)?))
     }
 
     fn load_by_name(
         &mut self,
         name: String,
-    ) -> wasmtime::Result<Result<gen::graph::Graph, gen::errors::Error>> {
-        if let Some(graph) = self.registry.get_mut(&name) {
-            let graph_id = self.graphs.insert(graph.clone().into());
-            Ok(Ok(graph_id))
-        } else {
-            return Err(UsageError::NotFound(name.to_string()).into());
+    ) -> Result<Result<Resource<Graph>, gen::errors::Error>> {
+        match self.ctx().registry.get_or_load(&name) {
+            Ok(Some(graph)) => Ok(Ok(self.table().push(graph)?)),
+            Ok(None) => Ok(Err(UsageError::NotFound(name).into())),
+            Err(e) => Ok(Err(e.into())),
         }
     }
 }
 
-impl gen::inference::Host for WasiNnCtx {
+impl<T: WasiNnView> gen::graph::HostGraph for T {
     /// Create an execution instance of a loaded graph.
-    ///
-    /// TODO: remove completely?
     fn init_execution_context(
         &mut self,
-        graph_id: gen::graph::Graph,
-    ) -> wasmtime::Result<Result<gen::inference::GraphExecutionContext, gen::errors::Error>> {
-        let exec_context = if let Some(graph) = self.graphs.get(graph_id) {
-            graph.init_execution_context()?
-        } else {
-            return Err(UsageError::InvalidGraphHandle.into());
+        graph: Resource<Graph>,
+    ) -> Result<Result<Resource<GraphExecutionContext>, gen::errors::Error>> {
+        let exec_context = match self.table().get(&graph)?.init_execution_context() {
+            Ok(exec_context) => exec_context,
+            Err(e) => return Ok(Err(e.into())),
         };
+        let exec_context = GraphExecutionContext(Arc::new(Mutex::new(exec_context)));
+        Ok(Ok(self.table().push(exec_context)?))
+    }
 
-        let exec_context_id = self.executions.insert(exec_context);
-        Ok(Ok(exec_context_id))
+    fn drop(&mut self, graph: Resource<Graph>) -> Result<()> {
+        self.table().delete(graph)?;
+        Ok(())
     }
+}
 
+#[async_trait::async_trait]
+impl<T: WasiNnView> gen::inference::HostGraphExecutionContext for T {
     /// Define the inputs to use for inference.
     fn set_input(
         &mut self,
-        exec_context_id: gen::inference::GraphExecutionContext,
+        exec_context: Resource<GraphExecutionContext>,
         index: u32,
-        tensor: gen::tensor::Tensor,
-    ) -> wasmtime::Result<Result<(), gen::errors::Error>> {
-        if let Some(exec_context) = self.executions.get_mut(exec_context_id) {
-            exec_context.set_input(index, &tensor)?;
-            Ok(Ok(()))
-        } else {
-            Err(UsageError::InvalidGraphHandle.into())
-        }
+        tensor: Resource<Tensor>,
+    ) -> Result<Result<(), gen::errors::Error>> {
+        set_input(self.table(), exec_context, index, tensor)
     }
 
     /// Compute the inference on the given inputs.
     ///
-    /// TODO: refactor to compute(list<tensor>) -> result<list<tensor>, error>
-    fn compute(
+    /// Inference can take a long time so it runs on a blocking thread.
+    async fn compute(
         &mut self,
-        exec_context_id: gen::inference::GraphExecutionContext,
-    ) -> wasmtime::Result<Result<(), gen::errors::Error>> {
-        if let Some(exec_context) = self.executions.get_mut(exec_context_id) {
-            exec_context.compute()?;
-            Ok(Ok(()))
-        } else {
-            Err(UsageError::InvalidExecutionContextHandle.into())
-        }
+        exec_context: Resource<GraphExecutionContext>,
+    ) -> Result<Result<(), gen::errors::Error>> {
+        let exec_context = self.table().get(&exec_context)?.0.clone();
+        let result =
+            tokio::task::spawn_blocking(move || exec_context.lock().unwrap().compute()).await?;
+        Ok(result.map_err(Into::into))
     }
 
     /// Extract the outputs after inference.
     fn get_output(
         &mut self,
-        exec_context_id: gen::inference::GraphExecutionContext,
+        exec_context: Resource<GraphExecutionContext>,
         index: u32,
-    ) -> wasmtime::Result<Result<gen::tensor::TensorData, gen::errors::Error>> {
-        if let Some(exec_context) = self.executions.get_mut(exec_context_id) {
-            // Read the output bytes. TODO: this involves a hard-coded upper
-            // limit on the tensor size that is necessary because there is no
-            // way to introspect the graph outputs
-            // (https://github.com/WebAssembly/wasi-nn/issues/37).
-            let mut destination = vec![0; 1024 * 1024];
-            let bytes_read = exec_context.get_output(index, &mut destination)?;
-            destination.truncate(bytes_read as usize);
-            Ok(Ok(destination))
-        } else {
-            Err(UsageError::InvalidGraphHandle.into())
-        }
+    ) -> Result<Result<Resource<Tensor>, gen::errors::Error>> {
+        get_output(self.table(), exec_context, index)
+    }
+
+    fn drop(&mut self, exec_context: Resource<GraphExecutionContext>) -> Result<()> {
+        self.table().delete(exec_context)?;
+        Ok(())
+    }
+}
+
+fn set_input(
+    table: &mut ResourceTable,
+    exec_context: Resource<GraphExecutionContext>,
+    index: u32,
+    tensor: Resource<Tensor>,
+) -> Result<Result<(), gen::errors::Error>> {
+    let tensor = table.get(&tensor)?;
+    let exec_context = table.get(&exec_context)?;
+    let result = exec_context.0.lock().unwrap().set_input(index, tensor);
+    Ok(result.map_err(Into::into))
+}
+
+fn get_output(
+    table: &mut ResourceTable,
+    exec_context: Resource<GraphExecutionContext>,
+    index: u32,
+) -> Result<Result<Resource<Tensor>, gen::errors::Error>> {
+    let result = table
+        .get(&exec_context)?
+        .0
+        .lock()
+        .unwrap()
+        .get_output(index);
+    match result {
+        Ok(tensor) => Ok(Ok(table.push(tensor)?)),
+        Err(e) => Ok(Err(e.into())),
+    }
+}
+
+impl<T: WasiNnView> gen::tensor::HostTensor for T {
+    fn new(
+        &mut self,
+        dimensions: gen::tensor::TensorDimensions,
+        tensor_type: gen::tensor::TensorType,
+        data: gen::tensor::TensorData,
+    ) -> Result<Resource<Tensor>> {
+        let tensor = Tensor {
+            dimensions,
+            tensor_type,
+            data,
+        };
+        Ok(self.table().push(tensor)?)
+    }
+
+    fn dimensions(&mut self, tensor: Resource<Tensor>) -> Result<gen::tensor::TensorDimensions> {
+        Ok(self.table().get(&tensor)?.dimensions.clone())
+    }
+
+    fn ty(&mut self, tensor: Resource<Tensor>) -> Result<gen::tensor::TensorType> {
+        Ok(self.table().get(&tensor)?.tensor_type)
+    }
+
+    fn data(&mut self, tensor: Resource<Tensor>) -> Result<gen::tensor::TensorData> {
+        Ok(self.table().get(&tensor)?.data.clone())
+    }
+
+    fn drop(&mut self, tensor: Resource<Tensor>) -> Result<()> {
+        self.table().delete(tensor)?;
+        Ok(())
     }
 }
 
-impl gen::errors::Host for WasiNnCtx {}
+impl<T: WasiNnView> gen::tensor::Host for T {}
+
+impl<T: WasiNnView> gen::inference::Host for T {}
 
-impl gen::tensor::Host for WasiNnCtx {}
+impl<T: WasiNnView> gen::errors::Host for T {}
+
+/// Backend failures are reported to the guest rather than trapping; the details
+/// of the failure are only logged.
+impl From<BackendError> for gen::errors::Error {
+    fn from(e: BackendError) -> Self {
+        tracing::debug!("wasi-nn backend error: {e:?}");
+        match e {
+            BackendError::BackendAccess(_) => gen::errors::Error::RuntimeError,
+            BackendError::GuestAccess(_) | BackendError::InvalidNumberOfBuilders(..) => {
+                gen::errors::Error::InvalidArgument
+            }
+            BackendError::NotEnoughMemory(_) => gen::errors::Error::TooLarge,
+            BackendError::UnsupportedTarget(_) => gen::errors::Error::UnsupportedOperation,
+        }
+    }
+}
+
+impl From<UsageError> for gen::errors::Error {
+    fn from(e: UsageError) -> Self {
+        tracing::debug!("wasi-nn usage error: {e}");
+        match e {
+            UsageError::InvalidEncoding(_) => gen::errors::Error::InvalidEncoding,
+            UsageError::NotFound(_) => gen::errors::Error::NotFound,
+            UsageError::NotEnoughMemory(_) => gen::errors::Error::TooLarge,
+            UsageError::InvalidContext
+            | UsageError::InvalidNumberOfBuilders(_)
+            | UsageError::InvalidGraphHandle
+            | UsageError::InvalidExecutionContextHandle => gen::errors::Error::InvalidArgument,
+        }
+    }
+}
 
 impl Hash for gen::graph::GraphEncoding {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
@@ -165,3 +310,73 @@ impl fmt::Display for GraphEncodingParseError {
     }
 }
 impl Error for GraphEncodingParseError {}
+
+/// Synchronous versions of the `wasi:nn` interfaces, which run `compute` on the
+/// calling thread.
+pub mod sync {
+    use super::types::{GraphExecutionContext, Tensor};
+    use super::WasiNnView;
+    use anyhow::Result;
+    use wasmtime::component::{Linker, Resource};
+
+    mod gen_ {
+        wasmtime::component::bindgen!({
+            world: "ml",
+            path: "wit/wasi-nn.wit",
+            tracing: true,
+            async: false,
+            with: {
+                "wasi:nn/tensor": super::super::gen::tensor,
+                "wasi:nn/graph": super::super::gen::graph,
+                "wasi:nn/errors": super::super::gen::errors,
+                "wasi:nn/inference/graph-execution-context": super::super::types::GraphExecutionContext,
+            },
+        });
+    }
+    use self::gen_::wasi::nn::inference;
+    use super::gen::errors::Error;
+
+    /// Add the `wasi:nn` interfaces to `linker`.
+    pub fn add_to_linker<T: WasiNnView>(linker: &mut Linker<T>) -> Result<()> {
+        super::gen::tensor::add_to_linker(linker, |t| t)?;
+        super::gen::graph::add_to_linker(linker, |t| t)?;
+        inference::add_to_linker(linker, |t| t)?;
+        super::gen::errors::add_to_linker(linker, |t| t)?;
+        Ok(())
+    }
+
+    impl<T: WasiNnView> inference::HostGraphExecutionContext for T {
+        fn set_input(
+            &mut self,
+            exec_context: Resource<GraphExecutionContext>,
+            index: u32,
+            tensor: Resource<Tensor>,
+        ) -> Result<Result<(), Error>> {
+            super::set_input(self.table(), exec_context, index, tensor)
+        }
+
+        fn compute(
+            &mut self,
+            exec_context: Resource<GraphExecutionContext>,
+        ) -> Result<Result<(), Error>> {
+            let exec_context = self.table().get(&exec_context)?;
+            let result = exec_context.0.lock().unwrap().compute();
+            Ok(result.map_err(Into::into))
+        }
+
+        fn get_output(
+            &mut self,
+            exec_context: Resource<GraphExecutionContext>,
+            index: u32,
+        ) -> Result<Result<Resource<Tensor>, Error>> {
+            super::get_output(self.table(), exec_context, index)
+        }
+
+        fn drop(&mut self, exec_context: Resource<GraphExecutionContext>) -> Result<()> {
+            self.table().delete(exec_context)?;
+            Ok(())
+        }
+    }
+
+    impl<T: WasiNnView> inference::Host for T {}
+}
@@ -13,6 +13,7 @@
 //!
 //! [`types`]: crate::wit::types
 
+use crate::backend::BackendError;
 use crate::ctx::{UsageError, WasiNnCtx, WasiNnError, WasiNnResult as Result};
 use wiggle::GuestPtr;
 
@@ -58,7 +59,7 @@ impl<'a> gen::wasi_ephemeral_nn::WasiEphemeralNn for WasiNnCtx {
         encoding: gen::types::GraphEncoding,
         target: gen::types::ExecutionTarget,
     ) -> Result<gen::types::Graph> {
-        let graph = if let Some(backend) = self.backends.get_mut(&encoding.into()) {
+        let graph = if let Some(backend) = self.backends.get(&encoding.into()) {
             // Retrieve all of the "builder lists" from the Wasm memory (see
             // $graph_builder_array) as slices for a backend to operate on.
             let mut slices = vec![];
@@ -70,7 +71,7 @@ impl<'a> gen::wasi_ephemeral_nn::WasiEphemeralNn for WasiNnCtx {
                 slices.push(slice);
             }
             let slice_refs = slices.iter().map(|s| s.as_ref()).collect::<Vec<_>>();
-            backend.load(&slice_refs, target.into())?
+            backend.lock().unwrap().load(&slice_refs, target.into())?
         } else {
             return Err(UsageError::InvalidEncoding(encoding.into()).into());
         };
@@ -80,8 +81,8 @@ impl<'a> gen::wasi_ephemeral_nn::WasiEphemeralNn for WasiNnCtx {
 
     fn load_by_name<'b>(&mut self, name: &wiggle::GuestPtr<'b, str>) -> Result<gen::types::Graph> {
         let name = name.as_str()?.unwrap();
-        if let Some(graph) = self.registry.get_mut(&name) {
-            let graph_id = self.graphs.insert(graph.clone().into());
+        if let Some(graph) = self.registry.get_or_load(&name)? {
+            let graph_id = self.graphs.insert(graph);
             Ok(graph_id.into())
         } else {
             return Err(UsageError::NotFound(name.to_string()).into());
@@ -136,11 +137,14 @@ impl<'a> gen::wasi_ephemeral_nn::WasiEphemeralNn for WasiNnCtx {
         out_buffer_max_size: u32,
     ) -> Result<u32> {
         if let Some(exec_context) = self.executions.get_mut(exec_context_id.into()) {
-            let mut destination = out_buffer
-                .as_array(out_buffer_max_size)
-                .as_slice_mut()?
-                .expect("cannot use with shared memories; see https://github.com/bytecodealliance/wasmtime/issues/5235 (TODO)");
-            Ok(exec_context.get_output(index, &mut destination)?)
+            let tensor = exec_context.get_output(index)?;
+            if tensor.data.len() > out_buffer_max_size as usize {
+                return Err(BackendError::NotEnoughMemory(tensor.data.len()).into());
+            }
+            out_buffer
+                .as_array(tensor.data.len() as u32)
+                .copy_from_slice(&tensor.data)?;
+            Ok(tensor.data.len() as u32)
         } else {
             Err(UsageError::InvalidGraphHandle.into())
         }
@@ -2,26 +2,92 @@
 //!
 //! [`wasi-threads`]: https://github.com/WebAssembly/wasi-threads
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
+use pool::WorkerPool;
 use rand::Rng;
 use std::panic::{catch_unwind, AssertUnwindSafe};
-use std::sync::Arc;
-use std::thread;
-use wasmtime::{Caller, ExternType, InstancePre, Linker, Module, SharedMemory, Store, ValType};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use wasmtime::{
+    Caller, Extern, ExternType, InstancePre, Linker, Module, SharedMemory, Store, UpdateDeadline,
+    ValType,
+};
 use wasmtime_wasi::maybe_exit_on_error;
 
+mod pool;
+
 // This name is a function export designated by the wasi-threads specification:
 // https://github.com/WebAssembly/wasi-threads/#detailed-design-discussion
 const WASI_ENTRY_POINT: &str = "wasi_thread_start";
 
 pub struct WasiThreadsCtx<T> {
     instance_pre: Arc<InstancePre<T>>,
+    linker: Arc<Linker<T>>,
+    pool: Arc<WorkerPool>,
+    exit_on_error: bool,
+    error: Arc<Mutex<Option<anyhow::Error>>>,
+    failed: Arc<AtomicBool>,
 }
 
 impl<T: Clone + Send + 'static> WasiThreadsCtx<T> {
     pub fn new(module: Module, linker: Arc<Linker<T>>) -> Result<Self> {
         let instance_pre = Arc::new(linker.instantiate_pre(&module)?);
-        Ok(Self { instance_pre })
+        Ok(Self {
+            instance_pre,
+            linker,
+            pool: WorkerPool::new(None),
+            exit_on_error: true,
+            error: Arc::new(Mutex::new(None)),
+            failed: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Limit the number of threads which may be running at once; once the
+    /// limit is reached `thread-spawn` fails until one of them exits.
+    ///
+    /// By default there is no limit.
+    pub fn max_threads(mut self, max: usize) -> Self {
+        self.pool = WorkerPool::new(Some(max));
+        self
+    }
+
+    /// Configure what happens when a spawned thread traps, calls `proc_exit`
+    /// or panics in host code.
+    ///
+    /// By specification this ends execution for all threads. By default this
+    /// is done by exiting the process with the same status as the CLI would
+    /// use had the main thread failed.
+    ///
+    /// When this is disabled, the other threads are stopped instead: the
+    /// engine's epoch is incremented, spawned threads trap at their next epoch
+    /// check and threads blocked in `memory.atomic.wait` are woken up. This
+    /// requires [`wasmtime::Config::epoch_interruption`]. The main thread's
+    /// store should stop once [`WasiThreadsCtx::failed`] returns `true` in its
+    /// epoch deadline callback, and then report [`WasiThreadsCtx::take_error`]
+    /// as its own error. Threads blocked in host calls stop once they return.
+    pub fn exit_on_error(mut self, exit: bool) -> Self {
+        self.exit_on_error = exit;
+        self
+    }
+
+    /// Return whether a spawned thread failed, and so all threads should stop.
+    pub fn failed(&self) -> bool {
+        self.failed.load(Ordering::SeqCst)
+    }
+
+    /// Return the error of the first spawned thread which failed, if
+    /// [`WasiThreadsCtx::exit_on_error`] is disabled.
+    ///
+    /// The main thread should treat this error as its own: e.g., an exit
+    /// status requested by `proc_exit` is available by downcasting it to
+    /// [`wasmtime_wasi::I32Exit`].
+    pub fn take_error(&self) -> Option<anyhow::Error> {
+        self.error.lock().unwrap().take()
+    }
+
+    /// Return the number of spawned threads which have not yet exited.
+    pub fn running_threads(&self) -> usize {
+        self.pool.running()
     }
 
     pub fn spawn(&self, host: T, thread_start_arg: i32) -> Result<i32> {
@@ -45,48 +111,97 @@ impl<T: Clone + Send + 'static> WasiThreadsCtx<T> {
             return Ok(-1);
         }
 
-        // Start a Rust thread running a new instance of the current module.
+        // Run a new instance of the current module on one of the pool's
+        // workers.
         let wasi_thread_id = random_thread_id();
-        let builder = thread::Builder::new().name(format!("wasi-thread-{}", wasi_thread_id));
-        builder.spawn(move || {
+        let exit_on_error = self.exit_on_error;
+        let error = self.error.clone();
+        let failed = self.failed.clone();
+        let linker = self.linker.clone();
+        let job = Box::new(move || {
+            let engine = instance_pre.module().engine().clone();
+            let mut memory = None;
+
             // Catch any panic failures in host code; e.g., if a WASI module
             // were to crash, we want all threads to exit, not just this one.
             let result = catch_unwind(AssertUnwindSafe(|| {
-                // Each new instance is created in its own store.
-                let mut store = Store::new(&instance_pre.module().engine(), host);
-                let instance = instance_pre.instantiate(&mut store).unwrap();
-                let thread_entry_point = instance
-                    .get_typed_func::<(i32, i32), ()>(&mut store, WASI_ENTRY_POINT)
-                    .unwrap();
+                // Each new instance is created in its own store, which stops
+                // at its next epoch check once another thread failed.
+                let mut store = Store::new(&engine, host);
+                let stop = failed.clone();
+                store.epoch_deadline_callback(move |_| {
+                    if stop.load(Ordering::SeqCst) {
+                        bail!("wasi-thread-{wasi_thread_id} stopped because another thread failed");
+                    }
+                    Ok(UpdateDeadline::Continue(1))
+                });
+                store.set_epoch_deadline(1);
+
+                memory = shared_memory(&linker, &mut store, instance_pre.module());
+                let instance = instance_pre.instantiate(&mut store)?;
+                let thread_entry_point =
+                    instance.get_typed_func::<(i32, i32), ()>(&mut store, WASI_ENTRY_POINT)?;
 
                 // Start the thread's entry point. Any traps or calls to
                 // `proc_exit`, by specification, should end execution for all
-                // threads. This code uses `process::exit` to do so, which is
-                // what the user expects from the CLI but probably not in a
-                // Wasmtime embedding.
+                // threads.
                 log::trace!(
                     "spawned thread id = {}; calling start function `{}` with: {}",
                     wasi_thread_id,
                     WASI_ENTRY_POINT,
                     thread_start_arg
                 );
-                match thread_entry_point.call(&mut store, (wasi_thread_id, thread_start_arg)) {
-                    Ok(_) => log::trace!("exiting thread id = {} normally", wasi_thread_id),
-                    Err(e) => {
-                        log::trace!("exiting thread id = {} due to error", wasi_thread_id);
-                        let e = maybe_exit_on_error(e);
-                        eprintln!("Error: {:?}", e);
-                        std::process::exit(1);
-                    }
-                }
+                thread_entry_point.call(&mut store, (wasi_thread_id, thread_start_arg))
             }));
 
-            if let Err(e) = result {
-                eprintln!("wasi-thread-{} panicked: {:?}", wasi_thread_id, e);
+            let e = match result {
+                Ok(Ok(())) => {
+                    log::trace!("exiting thread id = {} normally", wasi_thread_id);
+                    return;
+                }
+                Ok(Err(e)) => {
+                    log::trace!("exiting thread id = {} due to error", wasi_thread_id);
+                    e
+                }
+                Err(payload) => {
+                    log::trace!("exiting thread id = {} due to panic", wasi_thread_id);
+                    let message = payload
+                        .downcast_ref::<&str>()
+                        .copied()
+                        .or_else(|| payload.downcast_ref::<String>().map(|s| s.as_str()))
+                        .unwrap_or("Box<dyn Any>");
+                    anyhow!("wasi-thread-{} panicked: {}", wasi_thread_id, message)
+                }
+            };
+            if exit_on_error {
+                // This is what the user expects from the CLI but probably not
+                // in a Wasmtime embedding.
+                let e = maybe_exit_on_error(e);
+                eprintln!("Error: {:?}", e);
                 std::process::exit(1);
             }
-        })?;
 
+            // Only the first failure is reported: the others are most likely
+            // threads which were stopped because of it.
+            let mut error = error.lock().unwrap();
+            if failed.swap(true, Ordering::SeqCst) {
+                return;
+            }
+            *error = Some(e);
+            drop(error);
+            engine.increment_epoch();
+            if let Some(memory) = memory {
+                memory.atomic_notify_all();
+            }
+        });
+
+        if !self.pool.execute(job)? {
+            log::error!(
+                "failed to spawn thread: the maximum of {} running threads was reached",
+                self.pool.running()
+            );
+            return Ok(-1);
+        }
         Ok(wasi_thread_id)
     }
 }
@@ -120,10 +235,9 @@ pub fn add_to_linker<T: Clone + Send + 'static>(
             let host = caller.data().clone();
             let ctx = get_cx(caller.data_mut());
             match ctx.spawn(host, start_arg) {
-                Ok(thread_id) => {
-                    assert!(thread_id >= 0, "thread_id = {}", thread_id);
-                    thread_id
-                }
+                // A negative ID indicates that the spawn failed, which has
+                // already been logged.
+                Ok(thread_id) => thread_id,
                 Err(e) => {
                     log::error!("failed to spawn thread: {}", e);
                     -1
@@ -150,6 +264,20 @@ pub fn add_to_linker<T: Clone + Send + 'static>(
     Ok(())
 }
 
+/// Find the shared memory defined in `linker` for the import of `module`.
+fn shared_memory<T>(
+    linker: &Linker<T>,
+    store: &mut Store<T>,
+    module: &Module,
+) -> Option<SharedMemory> {
+    module.imports().find_map(|import| {
+        import.ty().memory()?;
+        linker
+            .get(&mut *store, import.module(), import.name())
+            .and_then(Extern::into_shared_memory)
+    })
+}
+
 /// Check if wasi-threads' `wasi_thread_start` export is present.
 fn has_entry_point(module: &Module) -> bool {
     module.get_export(WASI_ENTRY_POINT).is_some()
@@ -0,0 +1,149 @@
+//! A pool of OS threads on which wasi-threads are run.
+//!
+//! Spawning an OS thread for each `thread-spawn` call is expensive for guests
+//! which repeatedly create short-lived threads, so finished workers wait for a
+//! while for more work before exiting. The pool also bounds the number of
+//! wasi-threads running at once.
+
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// How long an idle worker waits for a new job before exiting.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+type Job = Box<dyn FnOnce() + Send>;
+
+pub(crate) struct WorkerPool {
+    max_threads: Option<usize>,
+    state: Mutex<State>,
+    work: Condvar,
+}
+
+struct State {
+    /// Jobs waiting for a worker to pick them up.
+    queue: VecDeque<Job>,
+    /// Workers waiting for a job.
+    idle: usize,
+    /// Jobs which are queued or running.
+    running: usize,
+}
+
+impl WorkerPool {
+    pub(crate) fn new(max_threads: Option<usize>) -> Arc<Self> {
+        Arc::new(Self {
+            max_threads,
+            state: Mutex::new(State {
+                queue: VecDeque::new(),
+                idle: 0,
+                running: 0,
+            }),
+            work: Condvar::new(),
+        })
+    }
+
+    /// Run `job` on an idle worker, starting a new one if there is none.
+    ///
+    /// Returns `Ok(false)` without running the job if the maximum number of
+    /// threads are already running.
+    pub(crate) fn execute(self: &Arc<Self>, job: Job) -> Result<bool> {
+        let mut state = self.state.lock().unwrap();
+        if self.max_threads.map_or(false, |max| state.running >= max) {
+            return Ok(false);
+        }
+        state.running += 1;
+        state.queue.push_back(job);
+
+        // Idle workers which have been notified but not yet woken up are still
+        // counted as idle, so only start a new worker if every idle one already
+        // has a job waiting for it.
+        if state.queue.len() <= state.idle {
+            self.work.notify_one();
+            return Ok(true);
+        }
+        let pool = self.clone();
+        let spawned = thread::Builder::new()
+            .name("wasi-threads-worker".to_string())
+            .spawn(move || pool.work());
+        if let Err(e) = spawned {
+            state.running -= 1;
+            state.queue.pop_back();
+            return Err(e.into());
+        }
+        Ok(true)
+    }
+
+    /// Return the number of jobs which are queued or running.
+    pub(crate) fn running(&self) -> usize {
+        self.state.lock().unwrap().running
+    }
+
+    fn work(self: Arc<Self>) {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(job) = state.queue.pop_front() {
+                drop(state);
+                job();
+                state = self.state.lock().unwrap();
+                state.running -= 1;
+                continue;
+            }
+
+            state.idle += 1;
+            let (guard, timeout) = self.work.wait_timeout(state, IDLE_TIMEOUT).unwrap();
+            state = guard;
+            state.idle -= 1;
+            if timeout.timed_out() && state.queue.is_empty() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn limits_running_jobs() {
+        let pool = WorkerPool::new(Some(2));
+        let (release, wait) = mpsc::channel::<()>();
+        let wait = Arc::new(Mutex::new(wait));
+        for _ in 0..2 {
+            let wait = wait.clone();
+            let job = Box::new(move || wait.lock().unwrap().recv().unwrap());
+            assert!(pool.execute(job).unwrap());
+        }
+        assert!(!pool.execute(Box::new(|| {})).unwrap());
+        assert_eq!(pool.running(), 2);
+
+        release.send(()).unwrap();
+        release.send(()).unwrap();
+        while pool.running() > 0 {
+            thread::yield_now();
+        }
+        assert!(pool.execute(Box::new(|| {})).unwrap());
+    }
+
+    #[test]
+    fn reuses_idle_workers() {
+        let pool = WorkerPool::new(None);
+        let (send, recv) = mpsc::channel();
+        let mut workers = Vec::new();
+        for _ in 0..4 {
+            let send = send.clone();
+            let job = Box::new(move || send.send(thread::current().id()).unwrap());
+            assert!(pool.execute(job).unwrap());
+            workers.push(recv.recv().unwrap());
+            while pool.running() > 0 {
+                thread::yield_now();
+            }
+        }
+        // Each job was only queued once the previous one finished, so a single
+        // worker ran all of them.
+        assert!(workers.iter().all(|w| *w == workers[0]));
+    }
+}
@@ -1,15 +1,16 @@
 use super::clocks::host::{monotonic_clock, wall_clock};
 use crate::preview2::{
     clocks::{self, HostMonotonicClock, HostWallClock},
-    filesystem::Dir,
+    filesystem::{Dir, FsAccounting},
     pipe, random, stdio,
     stdio::{StdinStream, StdoutStream},
-    DirPerms, FilePerms,
+    DirPerms, FilePerms, FsQuota, FsUsage,
 };
 use cap_rand::{Rng, RngCore, SeedableRng};
 use cap_std::ipnet::{self, IpNet};
 use cap_std::net::Pool;
 use cap_std::{ambient_authority, AmbientAuthority};
+use std::collections::BTreeMap;
 use std::mem;
 use std::net::{Ipv4Addr, Ipv6Addr};
 use std::sync::Arc;
@@ -21,7 +22,11 @@ pub struct WasiCtxBuilder {
     stderr: Box<dyn StdoutStream>,
     env: Vec<(String, String)>,
     args: Vec<String>,
+    config_vars: BTreeMap<String, String>,
+    config_secrets: BTreeMap<String, String>,
+    logging_span: tracing::Span,
     preopens: Vec<(Dir, String)>,
+    filesystem_quota: FsQuota,
 
     pool: Pool,
     random: Box<dyn RngCore + Send + Sync>,
@@ -42,7 +47,10 @@ impl WasiCtxBuilder {
     /// * stdout and stderr eat all input but it doesn't go anywhere
     /// * no env vars
     /// * no arguments
+    /// * no runtime configuration
+    /// * `wasi:logging` messages are emitted outside of any span
     /// * no preopens
+    /// * no limits on filesystem activity
     /// * clocks use the host implementation of wall/monotonic clocks
     /// * RNGs are all initialized with random state and suitable generator
     ///   quality to satisfy the requirements of WASI APIs.
@@ -72,7 +80,11 @@ impl WasiCtxBuilder {
             stderr: Box::new(pipe::SinkOutputStream),
             env: Vec::new(),
             args: Vec::new(),
+            config_vars: BTreeMap::new(),
+            config_secrets: BTreeMap::new(),
+            logging_span: tracing::Span::none(),
             preopens: Vec::new(),
+            filesystem_quota: FsQuota::default(),
             pool: Pool::new(),
             random: random::thread_rng(),
             insecure_random,
@@ -139,6 +151,51 @@ impl WasiCtxBuilder {
         self
     }
 
+    /// Sets a runtime configuration value made available to the guest through
+    /// the `wasi:config/runtime` interface.
+    ///
+    /// Unlike environment variables these values aren't part of the guest's
+    /// process environment. Setting a key which was previously set, including
+    /// as a secret, replaces the previous value.
+    pub fn config_var(&mut self, k: impl AsRef<str>, v: impl AsRef<str>) -> &mut Self {
+        let k = k.as_ref();
+        self.config_secrets.remove(k);
+        self.config_vars.insert(k.to_owned(), v.as_ref().to_owned());
+        self
+    }
+
+    /// Sets multiple runtime configuration values, see
+    /// [`WasiCtxBuilder::config_var`].
+    pub fn config_vars(&mut self, vars: &[(impl AsRef<str>, impl AsRef<str>)]) -> &mut Self {
+        for (k, v) in vars {
+            self.config_var(k, v);
+        }
+        self
+    }
+
+    /// Sets a secret runtime configuration value.
+    ///
+    /// Secrets can be looked up by name with `wasi:config/runtime#get` like
+    /// any other value but aren't included in the results of `get-all`, so a
+    /// guest can only read a secret it already knows the name of.
+    pub fn config_secret(&mut self, k: impl AsRef<str>, v: impl AsRef<str>) -> &mut Self {
+        let k = k.as_ref();
+        self.config_vars.remove(k);
+        self.config_secrets
+            .insert(k.to_owned(), v.as_ref().to_owned());
+        self
+    }
+
+    /// Sets the `tracing` span within which messages logged by the guest
+    /// through `wasi:logging` are emitted.
+    ///
+    /// Fields recorded on the span, such as a tenant or request id, are
+    /// attached to every message the guest logs.
+    pub fn logging_span(&mut self, span: tracing::Span) -> &mut Self {
+        self.logging_span = span;
+        self
+    }
+
     pub fn preopened_dir(
         &mut self,
         dir: cap_std::fs::Dir,
@@ -151,6 +208,16 @@ impl WasiCtxBuilder {
         self
     }
 
+    /// Limits the filesystem activity the guest may perform through the
+    /// preopened directories.
+    ///
+    /// Usage is tracked regardless of whether any limits are set and can be
+    /// inspected with [`WasiCtx::filesystem_usage`].
+    pub fn filesystem_quota(&mut self, quota: FsQuota) -> &mut Self {
+        self.filesystem_quota = quota;
+        self
+    }
+
     /// Set the generator for the secure random number generator to the custom
     /// generator specified.
     ///
@@ -285,7 +352,11 @@ impl WasiCtxBuilder {
             stderr,
             env,
             args,
+            config_vars,
+            config_secrets,
+            logging_span,
             preopens,
+            filesystem_quota,
             pool,
             random,
             insecure_random,
@@ -303,7 +374,11 @@ impl WasiCtxBuilder {
             stderr,
             env,
             args,
+            config_vars,
+            config_secrets,
+            logging_span,
             preopens,
+            fs_accounting: Arc::new(FsAccounting::new(filesystem_quota)),
             pool: Arc::new(pool),
             random,
             insecure_random,
@@ -330,7 +405,11 @@ pub struct WasiCtx {
     pub(crate) monotonic_clock: Box<dyn HostMonotonicClock + Send + Sync>,
     pub(crate) env: Vec<(String, String)>,
     pub(crate) args: Vec<String>,
+    pub(crate) config_vars: BTreeMap<String, String>,
+    pub(crate) config_secrets: BTreeMap<String, String>,
+    pub(crate) logging_span: tracing::Span,
     pub(crate) preopens: Vec<(Dir, String)>,
+    pub(crate) fs_accounting: Arc<FsAccounting>,
     pub(crate) stdin: Box<dyn StdinStream>,
     pub(crate) stdout: Box<dyn StdoutStream>,
     pub(crate) stderr: Box<dyn StdoutStream>,
@@ -338,6 +417,14 @@ pub struct WasiCtx {
     pub(crate) allowed_network_uses: AllowedNetworkUses,
 }
 
+impl WasiCtx {
+    /// Returns the filesystem activity performed through this context so
+    /// far, as limited by [`WasiCtxBuilder::filesystem_quota`].
+    pub fn filesystem_usage(&self) -> FsUsage {
+        self.fs_accounting.usage()
+    }
+}
+
 pub struct AllowedNetworkUses {
     pub ip_name_lookup: bool,
     pub udp: bool,
@@ -6,6 +6,7 @@ use anyhow::anyhow;
 use bytes::{Bytes, BytesMut};
 use std::io;
 use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 pub type FsResult<T> = Result<T, FsError>;
@@ -24,6 +25,171 @@ impl From<io::Error> for FsError {
     }
 }
 
+/// Limits on the filesystem activity a guest may perform through
+/// `wasi:filesystem`.
+///
+/// Each limit is cumulative over the lifetime of a [`WasiCtx`] and is
+/// configured with [`WasiCtxBuilder::filesystem_quota`]. Once a limit is
+/// reached further operations of that kind fail with `error-code::quota`. A
+/// limit of `None`, the default, means that activity is only counted.
+///
+/// [`WasiCtx`]: crate::preview2::WasiCtx
+/// [`WasiCtxBuilder::filesystem_quota`]: crate::preview2::WasiCtxBuilder::filesystem_quota
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FsQuota {
+    /// Maximum number of bytes read from files, both through
+    /// `descriptor.read` and through streams.
+    pub max_bytes_read: Option<u64>,
+    /// Maximum number of bytes written to files, both through
+    /// `descriptor.write` and through streams.
+    pub max_bytes_written: Option<u64>,
+    /// Maximum number of files, directories and links created.
+    pub max_files_created: Option<u64>,
+    /// Maximum number of entries returned from directory listings.
+    pub max_dir_entries_listed: Option<u64>,
+}
+
+/// A snapshot of the filesystem activity performed through a [`WasiCtx`],
+/// as returned by [`WasiCtx::filesystem_usage`].
+///
+/// [`WasiCtx`]: crate::preview2::WasiCtx
+/// [`WasiCtx::filesystem_usage`]: crate::preview2::WasiCtx::filesystem_usage
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FsUsage {
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    /// Files opened with `CREATE` but not `EXCLUSIVE` are only counted when
+    /// [`FsQuota::max_files_created`] is set, as finding out whether they
+    /// already existed takes an extra `stat`.
+    pub files_created: u64,
+    pub dir_entries_listed: u64,
+}
+
+/// Running totals of filesystem activity checked against an [`FsQuota`].
+///
+/// This is shared between a `WasiCtx` and the file streams created from it,
+/// which is why the counters are atomics.
+#[derive(Default)]
+pub(crate) struct FsAccounting {
+    quota: FsQuota,
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+    files_created: AtomicU64,
+    dir_entries_listed: AtomicU64,
+}
+
+impl FsAccounting {
+    pub fn new(quota: FsQuota) -> Self {
+        FsAccounting {
+            quota,
+            ..FsAccounting::default()
+        }
+    }
+
+    pub fn usage(&self) -> FsUsage {
+        FsUsage {
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+            files_created: self.files_created.load(Ordering::Relaxed),
+            dir_entries_listed: self.dir_entries_listed.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Reserves up to `len` bytes of the read quota, returning how many bytes
+    /// may be read.
+    ///
+    /// Bytes which end up not being read must be returned with
+    /// [`FsAccounting::unreserve_read`].
+    pub fn reserve_read(&self, len: u64) -> Result<u64, types::ErrorCode> {
+        reserve(&self.bytes_read, self.quota.max_bytes_read, len, false)
+    }
+
+    pub fn unreserve_read(&self, len: u64) {
+        self.bytes_read.fetch_sub(len, Ordering::Relaxed);
+    }
+
+    /// Reserves exactly `len` bytes of the write quota.
+    pub fn reserve_write(&self, len: u64) -> Result<(), types::ErrorCode> {
+        reserve(&self.bytes_written, self.quota.max_bytes_written, len, true)?;
+        Ok(())
+    }
+
+    pub fn unreserve_write(&self, len: u64) {
+        self.bytes_written.fetch_sub(len, Ordering::Relaxed);
+    }
+
+    /// Returns how many more bytes may be written before the write quota is
+    /// exhausted.
+    pub fn write_capacity(&self) -> u64 {
+        match self.quota.max_bytes_written {
+            Some(max) => max.saturating_sub(self.bytes_written.load(Ordering::Relaxed)),
+            None => u64::MAX,
+        }
+    }
+
+    /// Whether the number of created files is limited.
+    pub fn limits_creates(&self) -> bool {
+        self.quota.max_files_created.is_some()
+    }
+
+    /// Reserves the quota for creating one file, directory or link.
+    ///
+    /// If the creation then fails the quota must be returned with
+    /// [`FsAccounting::unreserve_create`].
+    pub fn reserve_create(&self) -> Result<(), types::ErrorCode> {
+        reserve(&self.files_created, self.quota.max_files_created, 1, true)?;
+        Ok(())
+    }
+
+    pub fn unreserve_create(&self) {
+        self.files_created.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Reserves the quota for listing one directory entry.
+    pub fn reserve_dir_entry(&self) -> Result<(), types::ErrorCode> {
+        reserve(
+            &self.dir_entries_listed,
+            self.quota.max_dir_entries_listed,
+            1,
+            true,
+        )?;
+        Ok(())
+    }
+
+    pub fn unreserve_dir_entry(&self) {
+        self.dir_entries_listed.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Adds up to `amt` to `counter` without exceeding `max`, returning how much
+/// was added.
+///
+/// If `exact` is set then either all of `amt` is added or none of it is. A
+/// non-zero request that can't be satisfied at all fails with
+/// `error-code::quota`.
+fn reserve(
+    counter: &AtomicU64,
+    max: Option<u64>,
+    amt: u64,
+    exact: bool,
+) -> Result<u64, types::ErrorCode> {
+    let mut granted = 0;
+    counter
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+            let available = match max {
+                Some(max) => max.saturating_sub(used),
+                None => u64::MAX - used,
+            };
+            granted = amt.min(available);
+            if (exact && granted < amt) || (granted == 0 && amt > 0) {
+                return None;
+            }
+            Some(used + granted)
+        })
+        .map_err(|_| types::ErrorCode::Quota)?;
+    Ok(granted)
+}
+
 pub enum Descriptor {
     File(File),
     Dir(Dir),
@@ -135,14 +301,33 @@ impl Dir {
 pub struct FileInputStream {
     file: Arc<cap_std::fs::File>,
     position: u64,
+    accounting: Arc<FsAccounting>,
 }
 impl FileInputStream {
     pub fn new(file: Arc<cap_std::fs::File>, position: u64) -> Self {
-        Self { file, position }
+        Self::with_accounting(file, position, Arc::new(FsAccounting::default()))
+    }
+
+    /// Creates a stream whose reads count against the quota of a `WasiCtx`.
+    pub(crate) fn with_accounting(
+        file: Arc<cap_std::fs::File>,
+        position: u64,
+        accounting: Arc<FsAccounting>,
+    ) -> Self {
+        Self {
+            file,
+            position,
+            accounting,
+        }
     }
 
     pub async fn read(&mut self, size: usize) -> Result<Bytes, StreamError> {
         use system_interface::fs::FileIoExt;
+        let size = self
+            .accounting
+            .reserve_read(size as u64)
+            .map_err(|e| StreamError::LastOperationFailed(e.into()))?;
+        let size = size as usize;
         let f = Arc::clone(&self.file);
         let p = self.position;
         let (r, mut buf) = spawn_blocking(move || {
@@ -151,7 +336,14 @@ impl FileInputStream {
             (r, buf)
         })
         .await;
-        let n = read_result(r)?;
+        let n = match read_result(r) {
+            Ok(n) => n,
+            Err(e) => {
+                self.accounting.unreserve_read(size as u64);
+                return Err(e);
+            }
+        };
+        self.accounting.unreserve_read((size - n) as u64);
         buf.truncate(n);
         self.position += n as u64;
         Ok(buf.freeze())
@@ -182,6 +374,7 @@ pub(crate) struct FileOutputStream {
     file: Arc<cap_std::fs::File>,
     mode: FileOutputMode,
     state: OutputState,
+    accounting: Arc<FsAccounting>,
 }
 
 enum OutputState {
@@ -195,18 +388,24 @@ enum OutputState {
 }
 
 impl FileOutputStream {
-    pub fn write_at(file: Arc<cap_std::fs::File>, position: u64) -> Self {
+    pub fn write_at(
+        file: Arc<cap_std::fs::File>,
+        position: u64,
+        accounting: Arc<FsAccounting>,
+    ) -> Self {
         Self {
             file,
             mode: FileOutputMode::Position(position),
             state: OutputState::Ready,
+            accounting,
         }
     }
-    pub fn append(file: Arc<cap_std::fs::File>) -> Self {
+    pub fn append(file: Arc<cap_std::fs::File>, accounting: Arc<FsAccounting>) -> Self {
         Self {
             file,
             mode: FileOutputMode::Append,
             state: OutputState::Ready,
+            accounting,
         }
     }
 }
@@ -228,31 +427,36 @@ impl HostOutputStream for FileOutputStream {
             }
         }
 
+        self.accounting
+            .reserve_write(buf.len() as u64)
+            .map_err(|e| StreamError::LastOperationFailed(e.into()))?;
+
         let f = Arc::clone(&self.file);
         let m = self.mode;
-        let task = spawn_blocking(move || match m {
-            FileOutputMode::Position(mut p) => {
-                let mut total = 0;
-                let mut buf = buf;
-                while !buf.is_empty() {
-                    let nwritten = f.write_at(buf.as_ref(), p)?;
-                    // afterwards buf contains [nwritten, len):
-                    let _ = buf.split_to(nwritten);
-                    p += nwritten as u64;
-                    total += nwritten;
-                }
-                Ok(total)
-            }
-            FileOutputMode::Append => {
-                let mut total = 0;
-                let mut buf = buf;
-                while !buf.is_empty() {
-                    let nwritten = f.append(buf.as_ref())?;
-                    let _ = buf.split_to(nwritten);
-                    total += nwritten;
-                }
-                Ok(total)
+        let accounting = Arc::clone(&self.accounting);
+        let task = spawn_blocking(move || {
+            let len = buf.len();
+            let mut total = 0;
+            let mut buf = buf;
+            while !buf.is_empty() {
+                let result = match m {
+                    FileOutputMode::Position(p) => f.write_at(buf.as_ref(), p + total as u64),
+                    FileOutputMode::Append => f.append(buf.as_ref()),
+                };
+                let nwritten = match result {
+                    Ok(nwritten) => nwritten,
+                    Err(e) => {
+                        // Only the bytes which were written count against
+                        // the quota.
+                        accounting.unreserve_write((len - total) as u64);
+                        return Err(e);
+                    }
+                };
+                // afterwards buf contains [nwritten, len):
+                let _ = buf.split_to(nwritten);
+                total += nwritten;
             }
+            Ok(total)
         });
         self.state = OutputState::Waiting(task);
         Ok(())
@@ -272,7 +476,12 @@ impl HostOutputStream for FileOutputStream {
     }
     fn check_write(&mut self) -> Result<usize, StreamError> {
         match self.state {
-            OutputState::Ready => Ok(FILE_WRITE_CAPACITY),
+            OutputState::Ready => match self.accounting.write_capacity() {
+                0 => Err(StreamError::LastOperationFailed(
+                    types::ErrorCode::Quota.into(),
+                )),
+                n => Ok(FILE_WRITE_CAPACITY.min(n.try_into().unwrap_or(usize::MAX))),
+            },
             OutputState::Closed => Err(StreamError::Closed),
             OutputState::Error(_) => match mem::replace(&mut self.state, OutputState::Closed) {
                 OutputState::Error(e) => Err(StreamError::LastOperationFailed(e.into())),
@@ -323,3 +532,59 @@ impl IntoIterator for ReaddirIterator {
         self.0.into_inner().unwrap()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn accounting_enforces_quota() {
+        let accounting = FsAccounting::new(FsQuota {
+            max_bytes_read: Some(10),
+            max_bytes_written: Some(10),
+            max_files_created: Some(1),
+            max_dir_entries_listed: None,
+        });
+
+        // Reads are clamped to what's left of the quota.
+        assert_eq!(accounting.reserve_read(8).unwrap(), 8);
+        assert_eq!(accounting.reserve_read(8).unwrap(), 2);
+        assert_eq!(accounting.reserve_read(0).unwrap(), 0);
+        assert!(matches!(
+            accounting.reserve_read(1),
+            Err(types::ErrorCode::Quota)
+        ));
+        accounting.unreserve_read(3);
+        assert_eq!(accounting.usage().bytes_read, 7);
+
+        // Writes are all-or-nothing.
+        accounting.reserve_write(6).unwrap();
+        assert_eq!(accounting.write_capacity(), 4);
+        assert!(matches!(
+            accounting.reserve_write(5),
+            Err(types::ErrorCode::Quota)
+        ));
+        accounting.reserve_write(4).unwrap();
+        assert_eq!(accounting.write_capacity(), 0);
+
+        accounting.reserve_create().unwrap();
+        assert!(matches!(
+            accounting.reserve_create(),
+            Err(types::ErrorCode::Quota)
+        ));
+
+        for _ in 0..100 {
+            accounting.reserve_dir_entry().unwrap();
+        }
+
+        assert_eq!(
+            accounting.usage(),
+            FsUsage {
+                bytes_read: 7,
+                bytes_written: 10,
+                files_created: 1,
+                dir_entries_listed: 100,
+            }
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use crate::preview2::bindings::config::runtime;
+use crate::preview2::WasiView;
+
+impl<T: WasiView> runtime::Host for T {
+    fn get(&mut self, key: String) -> anyhow::Result<Result<Option<String>, runtime::ConfigError>> {
+        let ctx = self.ctx();
+        let value = ctx
+            .config_vars
+            .get(&key)
+            .or_else(|| ctx.config_secrets.get(&key));
+        Ok(Ok(value.cloned()))
+    }
+
+    fn get_all(&mut self) -> anyhow::Result<Result<Vec<(String, String)>, runtime::ConfigError>> {
+        // Secrets are deliberately omitted here so that they can only be
+        // observed by a guest which already knows their name.
+        Ok(Ok(self
+            .ctx()
+            .config_vars
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::preview2::{WasiCtx, WasiCtxBuilder};
+    use wasmtime::component::ResourceTable;
+
+    struct Host {
+        table: ResourceTable,
+        ctx: WasiCtx,
+    }
+
+    impl WasiView for Host {
+        fn table(&self) -> &ResourceTable {
+            &self.table
+        }
+        fn table_mut(&mut self) -> &mut ResourceTable {
+            &mut self.table
+        }
+        fn ctx(&self) -> &WasiCtx {
+            &self.ctx
+        }
+        fn ctx_mut(&mut self) -> &mut WasiCtx {
+            &mut self.ctx
+        }
+    }
+
+    #[test]
+    fn secrets_are_not_listed() {
+        let mut builder = WasiCtxBuilder::new();
+        builder
+            .config_vars(&[("a", "1"), ("b", "2")])
+            .config_secret("token", "hunter2")
+            .config_secret("b", "3");
+        let mut host = Host {
+            table: ResourceTable::new(),
+            ctx: builder.build(),
+        };
+
+        assert_eq!(
+            runtime::Host::get(&mut host, "a".to_string())
+                .unwrap()
+                .unwrap(),
+            Some("1".to_string())
+        );
+        assert_eq!(
+            runtime::Host::get(&mut host, "token".to_string())
+                .unwrap()
+                .unwrap(),
+            Some("hunter2".to_string())
+        );
+        assert_eq!(
+            runtime::Host::get(&mut host, "c".to_string())
+                .unwrap()
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            runtime::Host::get_all(&mut host).unwrap().unwrap(),
+            [("a".to_string(), "1".to_string())]
+        );
+    }
+}
@@ -41,10 +41,14 @@ impl<T: WasiView> types::Host for T {
         let err = self.table_mut().get(&err)?;
 
         // Currently `err` always comes from the stream implementation which
-        // uses standard reads/writes so only check for `std::io::Error` here.
+        // uses standard reads/writes, or is a quota error from the stream's
+        // accounting.
         if let Some(err) = err.downcast_ref::<std::io::Error>() {
             return Ok(Some(ErrorCode::from(err)));
         }
+        if let Some(code) = err.downcast_ref::<ErrorCode>() {
+            return Ok(Some(*code));
+        }
 
         Ok(None)
     }
@@ -220,6 +224,7 @@ impl<T: WasiView> HostDescriptor for T {
         use std::io::IoSliceMut;
         use system_interface::fs::FileIoExt;
 
+        let accounting = self.ctx().fs_accounting.clone();
         let table = self.table();
 
         let f = table.get(&fd)?.file()?;
@@ -227,6 +232,7 @@ impl<T: WasiView> HostDescriptor for T {
             return Err(ErrorCode::NotPermitted.into());
         }
 
+        let len = accounting.reserve_read(len)?;
         let (mut buffer, r) = f
             .spawn_blocking(move |f| {
                 let mut buffer = vec![0; len.try_into().unwrap_or(usize::MAX)];
@@ -235,7 +241,17 @@ impl<T: WasiView> HostDescriptor for T {
             })
             .await;
 
-        let (bytes_read, state) = match r? {
+        let r = match r {
+            Ok(n) => {
+                accounting.unreserve_read(len - n as u64);
+                n
+            }
+            Err(e) => {
+                accounting.unreserve_read(len);
+                return Err(e.into());
+            }
+        };
+        let (bytes_read, state) = match r {
             0 => (0, true),
             n => (n, false),
         };
@@ -258,15 +274,28 @@ impl<T: WasiView> HostDescriptor for T {
         use std::io::IoSlice;
         use system_interface::fs::FileIoExt;
 
+        let accounting = self.ctx().fs_accounting.clone();
         let table = self.table();
         let f = table.get(&fd)?.file()?;
         if !f.perms.contains(FilePerms::WRITE) {
             return Err(ErrorCode::NotPermitted.into());
         }
 
-        let bytes_written = f
+        let len = buf.len() as u64;
+        accounting.reserve_write(len)?;
+        let bytes_written = match f
             .spawn_blocking(move |f| f.write_vectored_at(&[IoSlice::new(&buf)], offset))
-            .await?;
+            .await
+        {
+            Ok(n) => {
+                accounting.unreserve_write(len - n as u64);
+                n
+            }
+            Err(e) => {
+                accounting.unreserve_write(len);
+                return Err(e.into());
+            }
+        };
 
         Ok(types::Filesize::try_from(bytes_written).expect("usize fits in Filesize"))
     }
@@ -368,13 +397,18 @@ impl<T: WasiView> HostDescriptor for T {
         fd: Resource<types::Descriptor>,
         path: String,
     ) -> FsResult<()> {
+        let accounting = self.ctx().fs_accounting.clone();
         let table = self.table();
         let d = table.get(&fd)?.dir()?;
         if !d.perms.contains(DirPerms::MUTATE) {
             return Err(ErrorCode::NotPermitted.into());
         }
-        d.spawn_blocking(move |d| d.create_dir(&path)).await?;
-        Ok(())
+        accounting.reserve_create()?;
+        let result = d.spawn_blocking(move |d| d.create_dir(&path)).await;
+        if result.is_err() {
+            accounting.unreserve_create();
+        }
+        Ok(result?)
     }
 
     async fn stat(&mut self, fd: Resource<types::Descriptor>) -> FsResult<types::DescriptorStat> {
@@ -461,6 +495,7 @@ impl<T: WasiView> HostDescriptor for T {
         new_descriptor: Resource<types::Descriptor>,
         new_path: String,
     ) -> FsResult<()> {
+        let accounting = self.ctx().fs_accounting.clone();
         let table = self.table();
         let old_dir = table.get(&fd)?.dir()?;
         if !old_dir.perms.contains(DirPerms::MUTATE) {
@@ -473,11 +508,15 @@ impl<T: WasiView> HostDescriptor for T {
         if symlink_follow(old_path_flags) {
             return Err(ErrorCode::Invalid.into());
         }
+        accounting.reserve_create()?;
         let new_dir_handle = std::sync::Arc::clone(&new_dir.dir);
-        old_dir
+        let result = old_dir
             .spawn_blocking(move |d| d.hard_link(&old_path, &new_dir_handle, &new_path))
-            .await?;
-        Ok(())
+            .await;
+        if result.is_err() {
+            accounting.unreserve_create();
+        }
+        Ok(result?)
     }
 
     async fn open_at(
@@ -492,6 +531,7 @@ impl<T: WasiView> HostDescriptor for T {
         use system_interface::fs::{FdFlags, GetSetFdFlags};
         use types::{DescriptorFlags, OpenFlags};
 
+        let accounting = self.ctx().fs_accounting.clone();
         let table = self.table_mut();
         let d = table.get(&fd)?.dir()?;
         if !d.perms.contains(DirPerms::READ) {
@@ -553,6 +593,24 @@ impl<T: WasiView> HostDescriptor for T {
             }
         }
 
+        // Opening with `CREATE` only counts against the quota when the file
+        // doesn't exist yet. Finding that out takes an extra `stat` so without
+        // a limit only exclusive creations are counted.
+        let creates = if !oflags.contains(OpenFlags::CREATE) {
+            false
+        } else if oflags.contains(OpenFlags::EXCLUSIVE) {
+            true
+        } else if accounting.limits_creates() {
+            let path = path.clone();
+            d.spawn_blocking(move |d| d.symlink_metadata(&path).is_err())
+                .await
+        } else {
+            false
+        };
+        if creates {
+            accounting.reserve_create()?;
+        }
+
         // Represents each possible outcome from the spawn_blocking operation.
         // This makes sure we don't have to give spawn_blocking any way to
         // manipulate the table.
@@ -579,7 +637,11 @@ impl<T: WasiView> HostDescriptor for T {
                     Ok(OpenResult::File(opened))
                 }
             })
-            .await?;
+            .await;
+        if opened.is_err() && creates {
+            accounting.unreserve_create();
+        }
+        let opened = opened?;
 
         match opened {
             OpenResult::Dir(dir) => {
@@ -670,13 +732,20 @@ impl<T: WasiView> HostDescriptor for T {
         #[cfg(windows)]
         use cap_fs_ext::DirExt;
 
+        let accounting = self.ctx().fs_accounting.clone();
         let table = self.table();
         let d = table.get(&fd)?.dir()?;
         if !d.perms.contains(DirPerms::MUTATE) {
             return Err(ErrorCode::NotPermitted.into());
         }
-        Ok(d.spawn_blocking(move |d| d.symlink(&src_path, &dest_path))
-            .await?)
+        accounting.reserve_create()?;
+        let result = d
+            .spawn_blocking(move |d| d.symlink(&src_path, &dest_path))
+            .await;
+        if result.is_err() {
+            accounting.unreserve_create();
+        }
+        Ok(result?)
     }
 
     async fn unlink_file_at(
@@ -710,7 +779,8 @@ impl<T: WasiView> HostDescriptor for T {
         let clone = std::sync::Arc::clone(&f.file);
 
         // Create a stream view for it.
-        let reader = FileInputStream::new(clone, offset);
+        let accounting = self.ctx().fs_accounting.clone();
+        let reader = FileInputStream::with_accounting(clone, offset, accounting);
 
         // Insert the stream view into the table. Trap if the table is full.
         let index = self.table_mut().push(InputStream::File(reader))?;
@@ -734,7 +804,8 @@ impl<T: WasiView> HostDescriptor for T {
         let clone = std::sync::Arc::clone(&f.file);
 
         // Create a stream view for it.
-        let writer = FileOutputStream::write_at(clone, offset);
+        let accounting = self.ctx().fs_accounting.clone();
+        let writer = FileOutputStream::write_at(clone, offset, accounting);
         let writer: OutputStream = Box::new(writer);
 
         // Insert the stream view into the table. Trap if the table is full.
@@ -757,7 +828,8 @@ impl<T: WasiView> HostDescriptor for T {
         let clone = std::sync::Arc::clone(&f.file);
 
         // Create a stream view for it.
-        let appender = FileOutputStream::append(clone);
+        let accounting = self.ctx().fs_accounting.clone();
+        let appender = FileOutputStream::append(clone, accounting);
         let appender: OutputStream = Box::new(appender);
 
         // Insert the stream view into the table. Trap if the table is full.
@@ -828,9 +900,15 @@ impl<T: WasiView> HostDirectoryEntryStream for T {
         &mut self,
         stream: Resource<types::DirectoryEntryStream>,
     ) -> FsResult<Option<types::DirectoryEntry>> {
+        let accounting = self.ctx().fs_accounting.clone();
         let table = self.table();
         let readdir = table.get(&stream)?;
-        readdir.next()
+        accounting.reserve_dir_entry()?;
+        let entry = readdir.next();
+        if !matches!(entry, Ok(Some(_))) {
+            accounting.unreserve_dir_entry();
+        }
+        entry
     }
 
     fn drop(&mut self, stream: Resource<types::DirectoryEntryStream>) -> anyhow::Result<()> {
@@ -0,0 +1,135 @@
+use crate::preview2::bindings::logging::logging::{self, Level};
+use crate::preview2::WasiView;
+
+/// The `tracing` target that guest log messages are emitted under.
+///
+/// Guest messages can be enabled independently of the host's own logging by
+/// filtering on this target, for example with `WASMTIME_LOG=wasi_logging=info`.
+pub(crate) const TARGET: &str = "wasi_logging";
+
+impl<T: WasiView> logging::Host for T {
+    fn log(&mut self, level: Level, context: String, message: String) -> anyhow::Result<()> {
+        let _enter = self.ctx().logging_span.enter();
+        let context = context.as_str();
+        match level {
+            Level::Trace => tracing::trace!(target: TARGET, context, "{message}"),
+            Level::Debug => tracing::debug!(target: TARGET, context, "{message}"),
+            Level::Info => tracing::info!(target: TARGET, context, "{message}"),
+            Level::Warn => tracing::warn!(target: TARGET, context, "{message}"),
+            Level::Error => tracing::error!(target: TARGET, context, "{message}"),
+            // `tracing` has no level above `ERROR`, so critical messages are
+            // distinguished with an extra field instead.
+            Level::Critical => {
+                tracing::error!(target: TARGET, context, critical = true, "{message}")
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::preview2::{WasiCtx, WasiCtxBuilder};
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use wasmtime::component::ResourceTable;
+
+    struct Ctx {
+        table: ResourceTable,
+        wasi: WasiCtx,
+    }
+
+    impl WasiView for Ctx {
+        fn table(&self) -> &ResourceTable {
+            &self.table
+        }
+        fn table_mut(&mut self) -> &mut ResourceTable {
+            &mut self.table
+        }
+        fn ctx(&self) -> &WasiCtx {
+            &self.wasi
+        }
+        fn ctx_mut(&mut self) -> &mut WasiCtx {
+            &mut self.wasi
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Logs `messages` with a context whose logging span is created by
+    /// `span`, returning the lines written by a subscriber at the `INFO` level.
+    fn log(span: impl FnOnce() -> tracing::Span, messages: &[(Level, &str)]) -> Vec<String> {
+        let output = Output::default();
+        let writer = output.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::INFO)
+            .with_ansi(false)
+            .without_time()
+            .with_writer(move || writer.clone())
+            .finish();
+        tracing::subscriber::with_default(subscriber, || {
+            let mut ctx = Ctx {
+                table: ResourceTable::new(),
+                wasi: WasiCtxBuilder::new().logging_span(span()).build(),
+            };
+            for (level, message) in messages {
+                logging::Host::log(&mut ctx, *level, "ctx".to_string(), message.to_string())
+                    .unwrap();
+            }
+        });
+        let output = output.0.lock().unwrap();
+        String::from_utf8(output.clone())
+            .unwrap()
+            .lines()
+            .map(|l| l.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn levels() {
+        let lines = log(
+            tracing::Span::none,
+            &[
+                (Level::Trace, "trace"),
+                (Level::Debug, "debug"),
+                (Level::Info, "info"),
+                (Level::Warn, "warn"),
+                (Level::Error, "error"),
+                (Level::Critical, "critical"),
+            ],
+        );
+        assert_eq!(lines.len(), 4, "{lines:?}");
+        assert!(lines[0].starts_with(" INFO wasi_logging:"), "{lines:?}");
+        assert!(lines[0].ends_with("info context=\"ctx\""), "{lines:?}");
+        assert!(lines[1].starts_with(" WARN wasi_logging:"), "{lines:?}");
+        assert!(lines[2].starts_with("ERROR wasi_logging:"), "{lines:?}");
+        assert!(!lines[2].contains("critical"), "{lines:?}");
+        assert!(lines[3].starts_with("ERROR wasi_logging:"), "{lines:?}");
+        assert!(lines[3].ends_with("critical=true"), "{lines:?}");
+    }
+
+    #[test]
+    fn span() {
+        let lines = log(
+            || tracing::info_span!(target: TARGET, "request", id = 7),
+            &[(Level::Info, "hello")],
+        );
+        assert_eq!(lines.len(), 1, "{lines:?}");
+        assert!(
+            lines[0].starts_with(" INFO request{id=7}: wasi_logging:"),
+            "{lines:?}"
+        );
+    }
+}
@@ -1,9 +1,11 @@
 mod clocks;
+mod config;
 mod env;
 mod exit;
 pub(crate) mod filesystem;
 mod instance_network;
 mod io;
+mod logging;
 pub(crate) mod network;
 mod random;
 mod tcp;
@@ -43,7 +43,7 @@ mod write_stream;
 pub use self::clocks::{HostMonotonicClock, HostWallClock};
 pub use self::ctx::{WasiCtx, WasiCtxBuilder, WasiView};
 pub use self::error::{I32Exit, TrappableError};
-pub use self::filesystem::{DirPerms, FilePerms, FsError, FsResult};
+pub use self::filesystem::{DirPerms, FilePerms, FsError, FsQuota, FsResult, FsUsage};
 pub use self::network::{Network, SocketError, SocketResult};
 pub use self::poll::{subscribe, ClosureFuture, MakeFuture, Pollable, PollableFuture, Subscribe};
 pub use self::random::{thread_rng, Deterministic};
@@ -178,6 +178,42 @@ pub mod bindings {
     });
 
     pub use wasi::*;
+
+    /// Bindings for the proposed `wasi:config` interfaces.
+    ///
+    /// These aren't part of the `wasi:cli/imports` world and must be added to
+    /// a linker separately with [`config::runtime::add_to_linker`].
+    pub mod config {
+        pub(crate) mod _internal {
+            wasmtime::component::bindgen!({
+                path: "wit",
+                interfaces: "
+                    import wasi:config/runtime@0.2.0-draft;
+                ",
+                // Tracing would log the values returned to the guest, which
+                // include secrets.
+                tracing: false,
+            });
+        }
+        pub use self::_internal::wasi::config::runtime;
+    }
+
+    /// Bindings for the proposed `wasi:logging` interfaces.
+    ///
+    /// These aren't part of the `wasi:cli/imports` world and must be added to
+    /// a linker separately with [`logging::logging::add_to_linker`].
+    pub mod logging {
+        pub(crate) mod _internal {
+            wasmtime::component::bindgen!({
+                path: "wit",
+                interfaces: "
+                    import wasi:logging/logging@0.1.0-draft;
+                ",
+                tracing: true,
+            });
+        }
+        pub use self::_internal::wasi::logging::logging;
+    }
 }
 
 pub(crate) static RUNTIME: once_cell::sync::Lazy<tokio::runtime::Runtime> =
@@ -14,7 +14,7 @@ use bytes::Bytes;
 use std::sync::{Arc, Mutex};
 use tokio::sync::mpsc;
 
-pub use crate::preview2::write_stream::AsyncWriteStream;
+pub use crate::preview2::write_stream::{AsyncWriteStream, WriteStreamMetrics};
 
 #[derive(Debug, Clone)]
 pub struct MemoryInputPipe {
@@ -752,6 +752,110 @@ mod test {
         writer.write(chunk.clone()).expect("write does not trap");
     }
 
+    #[test_log::test(tokio::test(flavor = "multi_thread"))]
+    async fn write_stream_metrics() {
+        // The simplex only holds a single byte, so the worker can't finish
+        // writing a chunk until the reader catches up.
+        let (mut reader, writer) = simplex(1);
+        let mut writer = AsyncWriteStream::new(1024, writer);
+        let metrics = writer.metrics();
+
+        let chunk = Bytes::from_static(&[0; 1024]);
+
+        let permit = resolves_immediately(writer.write_ready())
+            .await
+            .expect("write should be ready");
+        assert_eq!(permit, 1024);
+        writer.write(chunk.clone()).expect("write succeeds");
+        assert_eq!(metrics.buffered_bytes(), 1024);
+        assert_eq!(metrics.written_bytes(), 0);
+
+        // Drain the output in the background, only after the stream has had
+        // a chance to block on it.
+        let drain = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            let mut buf = [0; 1024];
+            reader.read_exact(&mut buf).await.unwrap();
+            reader
+        });
+
+        let permit = resolves_immediately(writer.write_ready())
+            .await
+            .expect("write should be ready");
+        assert_eq!(permit, 1024);
+        let _reader = drain.await.unwrap();
+
+        assert_eq!(metrics.buffered_bytes(), 0);
+        assert_eq!(metrics.written_bytes(), 1024);
+        assert_eq!(metrics.dropped_bytes(), 0);
+        assert!(metrics.blocked_time() > Duration::ZERO);
+    }
+
+    #[test_log::test(tokio::test(flavor = "multi_thread"))]
+    async fn lossy_write_stream() {
+        // Nothing reads from the simplex, so the first chunk never finishes
+        // writing and stays buffered.
+        let (_reader, writer) = simplex(1);
+        let mut writer = AsyncWriteStream::new_lossy(1024, writer);
+        let metrics = writer.metrics();
+
+        let chunk = Bytes::from_static(&[0; 1024]);
+
+        for _ in 0..4 {
+            // Lossy streams are always ready and never trap on writes.
+            let permit = resolves_immediately(writer.write_ready())
+                .await
+                .expect("write should be ready");
+            assert_eq!(permit, 1024);
+            writer.write(chunk.clone()).expect("write does not trap");
+            writer.flush().expect("flush succeeds");
+        }
+
+        assert_eq!(metrics.buffered_bytes(), 1024);
+        assert_eq!(metrics.dropped_bytes(), 3072);
+        assert_eq!(metrics.blocked_time(), Duration::ZERO);
+    }
+
+    #[test_log::test(tokio::test(flavor = "multi_thread"))]
+    async fn shared_write_stream() {
+        use crate::preview2::StdoutStream;
+
+        // Nothing reads from the simplex, so the first chunk never finishes
+        // writing and stays buffered.
+        let (_reader, writer) = simplex(1);
+        let stdout = AsyncWriteStream::new(1024, writer);
+        let metrics = stdout.metrics();
+        let mut a = stdout.stream();
+        let mut b = stdout.stream();
+
+        // A permit is taken from the buffer shared by all streams.
+        assert_eq!(a.check_write().unwrap(), 1024);
+        assert_eq!(b.check_write().unwrap(), 0);
+        assert!(matches!(
+            b.write(Bytes::from_static(&[0; 1])),
+            Err(StreamError::Trap(_))
+        ));
+        a.write(Bytes::from_static(&[0; 512])).unwrap();
+        assert_eq!(a.check_write().unwrap(), 512);
+        assert_eq!(b.check_write().unwrap(), 0);
+
+        // Dropping a stream gives its permit back.
+        drop(a);
+        assert_eq!(b.check_write().unwrap(), 512);
+
+        // A pending flush only affects the stream which requested it.
+        b.flush().unwrap();
+        assert_eq!(b.check_write().unwrap(), 0);
+        let mut c = stdout.stream();
+        assert_eq!(c.check_write().unwrap(), 512);
+        c.write(Bytes::from_static(&[0; 512])).unwrap();
+        assert!(matches!(
+            b.write(Bytes::from_static(&[0; 1])),
+            Err(StreamError::Trap(_))
+        ));
+        assert_eq!(metrics.buffered_bytes(), 1024);
+    }
+
     #[test_log::test(tokio::test(flavor = "multi_thread"))]
     async fn backpressure_write_stream_with_flush() {
         for n in 0..TEST_ITERATIONS {
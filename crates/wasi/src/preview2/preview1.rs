@@ -564,10 +564,13 @@ impl From<StreamError> for types::Error {
             StreamError::Closed => types::Errno::Io.into(),
             StreamError::LastOperationFailed(e) => match e.downcast::<std::io::Error>() {
                 Ok(err) => filesystem::ErrorCode::from(err).into(),
-                Err(e) => {
-                    log::debug!("dropping error {e:?}");
-                    types::Errno::Io.into()
-                }
+                Err(e) => match e.downcast::<filesystem::ErrorCode>() {
+                    Ok(code) => code.into(),
+                    Err(e) => {
+                        log::debug!("dropping error {e:?}");
+                        types::Errno::Io.into()
+                    }
+                },
             },
             StreamError::Trap(e) => types::Error::trap(e),
         }
@@ -112,7 +112,8 @@ impl StdoutStream for pipe::ClosedOutputStream {
 /// This implementation will yield output streams that block on writes, as they
 /// inherit the implementation directly from the rust std library. A different
 /// implementation of [`StdoutStream`] will be necessary if truly async output
-/// streams are required.
+/// streams are required, such as [`pipe::AsyncWriteStream`] wrapping
+/// `tokio::io::stdout()`.
 pub struct Stdout;
 
 pub fn stdout() -> Stdout {
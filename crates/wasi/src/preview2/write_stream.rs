@@ -1,15 +1,26 @@
-use crate::preview2::{HostOutputStream, StreamError, Subscribe};
+use crate::preview2::{HostOutputStream, StdoutStream, StreamError, Subscribe};
 use anyhow::anyhow;
 use bytes::Bytes;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 #[derive(Debug)]
 struct WorkerState {
     alive: bool,
-    items: std::collections::VecDeque<Bytes>,
-    write_budget: usize,
-    flush_pending: bool,
+    items: std::collections::VecDeque<Job>,
+    capacity: usize,
+    buffered: usize,
+    /// Bytes promised by `check_write` to the handles sharing this worker,
+    /// which they haven't written yet.
+    reserved: usize,
+    /// The number of flushes requested by the handles so far, and the number
+    /// of them which completed. Flushes complete in order.
+    flushes_requested: u64,
+    flushes_completed: u64,
     error: Option<anyhow::Error>,
+    written: u64,
+    dropped: u64,
+    blocked: Duration,
 }
 
 impl WorkerState {
@@ -22,96 +33,104 @@ impl WorkerState {
         }
         Ok(())
     }
+
+    /// The bytes which may still be promised to a handle.
+    fn write_budget(&self) -> usize {
+        self.capacity.saturating_sub(self.buffered + self.reserved)
+    }
 }
 
 struct Worker {
     state: Mutex<WorkerState>,
+    lossy: bool,
     new_work: tokio::sync::Notify,
     write_ready_changed: tokio::sync::Notify,
 }
 
+#[derive(Debug)]
 enum Job {
-    Flush,
+    /// Flushes the writer, completing the flush with this number.
+    Flush(u64),
     Write(Bytes),
 }
 
 impl Worker {
-    fn new(write_budget: usize) -> Self {
+    fn new(capacity: usize, lossy: bool) -> Self {
         Self {
             state: Mutex::new(WorkerState {
                 alive: true,
                 items: std::collections::VecDeque::new(),
-                write_budget,
-                flush_pending: false,
+                capacity,
+                buffered: 0,
+                reserved: 0,
+                flushes_requested: 0,
+                flushes_completed: 0,
                 error: None,
+                written: 0,
+                dropped: 0,
+                blocked: Duration::ZERO,
             }),
+            lossy,
             new_work: tokio::sync::Notify::new(),
             write_ready_changed: tokio::sync::Notify::new(),
         }
     }
-    async fn ready(&self) {
+    /// Waits until the handle which requested the flush `flush` and holds
+    /// the permit `permit` may write again.
+    async fn ready(&self, flush: u64, permit: usize) {
+        let mut blocked_since: Option<Instant> = None;
         loop {
+            // Register interest before inspecting the state so a notification
+            // sent in between isn't lost. Multiple streams may share this
+            // worker so all waiters are woken on changes.
+            let notified = self.write_ready_changed.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
             {
-                let state = self.state();
+                let mut state = self.state();
                 if state.error.is_some()
                     || !state.alive
-                    || (!state.flush_pending && state.write_budget > 0)
+                    || self.lossy
+                    || (state.flushes_completed >= flush && state.write_budget() + permit > 0)
                 {
+                    if let Some(start) = blocked_since {
+                        state.blocked += start.elapsed();
+                    }
                     return;
                 }
             }
-            self.write_ready_changed.notified().await;
-        }
-    }
-    fn check_write(&self) -> Result<usize, StreamError> {
-        let mut state = self.state();
-        if let Err(e) = state.check_error() {
-            return Err(e);
-        }
-
-        if state.flush_pending || state.write_budget == 0 {
-            return Ok(0);
+            blocked_since.get_or_insert_with(Instant::now);
+            notified.await;
         }
-
-        Ok(state.write_budget)
     }
     fn state(&self) -> std::sync::MutexGuard<WorkerState> {
         self.state.lock().unwrap()
     }
     fn pop(&self) -> Option<Job> {
-        let mut state = self.state();
-        if state.items.is_empty() {
-            if state.flush_pending {
-                return Some(Job::Flush);
-            }
-        } else if let Some(bytes) = state.items.pop_front() {
-            return Some(Job::Write(bytes));
-        }
-
-        None
+        self.state().items.pop_front()
     }
     fn report_error(&self, e: std::io::Error) {
         {
             let mut state = self.state();
             state.alive = false;
             state.error = Some(e.into());
-            state.flush_pending = false;
+            state.flushes_completed = state.flushes_requested;
         }
-        self.write_ready_changed.notify_one();
+        self.write_ready_changed.notify_waiters();
     }
     async fn work<T: tokio::io::AsyncWrite + Send + Sync + Unpin + 'static>(&self, mut writer: T) {
         use tokio::io::AsyncWriteExt;
         loop {
             while let Some(job) = self.pop() {
                 match job {
-                    Job::Flush => {
+                    Job::Flush(flush) => {
                         if let Err(e) = writer.flush().await {
                             self.report_error(e);
                             return;
                         }
 
                         tracing::debug!("worker marking flush complete");
-                        self.state().flush_pending = false;
+                        self.state().flushes_completed = flush;
                     }
 
                     Job::Write(mut bytes) => {
@@ -123,13 +142,15 @@ impl Worker {
                                 return;
                             }
                             Ok(_) => {
-                                self.state().write_budget += len;
+                                let mut state = self.state();
+                                state.buffered -= len;
+                                state.written += len as u64;
                             }
                         }
                     }
                 }
 
-                self.write_ready_changed.notify_one();
+                self.write_ready_changed.notify_waiters();
             }
             self.new_work.notified().await;
         }
@@ -137,9 +158,26 @@ impl Worker {
 }
 
 /// Provides a [`HostOutputStream`] impl from a [`tokio::io::AsyncWrite`] impl
+///
+/// At most `write_budget` bytes are buffered in memory between the guest and
+/// the writer. By default a full buffer applies backpressure to the guest:
+/// `check-write` reports no capacity and `blocking-write-and-flush` waits until
+/// the writer has caught up. Streams created with
+/// [`AsyncWriteStream::new_lossy`] instead drop output which doesn't fit.
+///
+/// This type can also be used as a [`StdoutStream`], in which case all streams
+/// handed out to the guest share the same buffer and writer. The permits
+/// granted by `check-write` are then taken from the shared buffer, so a permit
+/// held by one stream reduces those of the others until it's used, replaced
+/// by a new `check-write` or a flush, or the stream is dropped.
 pub struct AsyncWriteStream {
     worker: Arc<Worker>,
-    _join_handle: crate::preview2::AbortOnDropJoinHandle<()>,
+    /// What's left of the permit returned by `check_write` on this handle,
+    /// which is reserved in the worker's budget.
+    permit: usize,
+    /// The number of the last flush requested through this handle.
+    flush: u64,
+    _join_handle: Arc<crate::preview2::AbortOnDropJoinHandle<()>>,
 }
 
 impl AsyncWriteStream {
@@ -149,34 +187,93 @@ impl AsyncWriteStream {
         write_budget: usize,
         writer: T,
     ) -> Self {
-        let worker = Arc::new(Worker::new(write_budget));
+        Self::spawn(Worker::new(write_budget, false), writer)
+    }
+
+    /// Create a [`AsyncWriteStream`] which never applies backpressure.
+    ///
+    /// Writes which would grow the buffer beyond `write_budget` bytes are
+    /// discarded and counted in [`WriteStreamMetrics::dropped_bytes`], and
+    /// flushes complete without waiting for the writer. This is suitable for
+    /// output such as logs where a slow consumer shouldn't stall the guest.
+    pub fn new_lossy<T: tokio::io::AsyncWrite + Send + Sync + Unpin + 'static>(
+        write_budget: usize,
+        writer: T,
+    ) -> Self {
+        Self::spawn(Worker::new(write_budget, true), writer)
+    }
+
+    fn spawn<T: tokio::io::AsyncWrite + Send + Sync + Unpin + 'static>(
+        worker: Worker,
+        writer: T,
+    ) -> Self {
+        let worker = Arc::new(worker);
 
         let w = Arc::clone(&worker);
         let join_handle = crate::preview2::spawn(async move { w.work(writer).await });
 
         AsyncWriteStream {
             worker,
-            _join_handle: join_handle,
+            permit: 0,
+            flush: 0,
+            _join_handle: Arc::new(join_handle),
+        }
+    }
+
+    /// Returns a handle to this stream's metrics which remains usable after
+    /// the stream has been handed to a guest.
+    pub fn metrics(&self) -> WriteStreamMetrics {
+        WriteStreamMetrics {
+            worker: Arc::clone(&self.worker),
+        }
+    }
+}
+
+impl Clone for AsyncWriteStream {
+    fn clone(&self) -> Self {
+        AsyncWriteStream {
+            worker: Arc::clone(&self.worker),
+            permit: 0,
+            flush: 0,
+            _join_handle: Arc::clone(&self._join_handle),
         }
     }
 }
 
+impl Drop for AsyncWriteStream {
+    fn drop(&mut self) {
+        // Give the unused permit back to the other handles.
+        self.worker.state().reserved -= self.permit;
+    }
+}
+
 impl HostOutputStream for AsyncWriteStream {
     fn write(&mut self, bytes: Bytes) -> Result<(), StreamError> {
         let mut state = self.worker.state();
         state.check_error()?;
-        if state.flush_pending {
-            return Err(StreamError::Trap(anyhow!(
-                "write not permitted while flush pending"
-            )));
-        }
-        match state.write_budget.checked_sub(bytes.len()) {
-            Some(remaining_budget) => {
-                state.write_budget = remaining_budget;
-                state.items.push_back(bytes);
+        let len = bytes.len();
+        if self.worker.lossy {
+            if len > state.write_budget() {
+                state.dropped += len as u64;
+                return Ok(());
+            }
+        } else {
+            if state.flushes_completed < self.flush {
+                return Err(StreamError::Trap(anyhow!(
+                    "write not permitted while flush pending"
+                )));
+            }
+            // The write uses this handle's permit first, and then whatever
+            // wasn't promised to other handles.
+            if len > self.permit + state.write_budget() {
+                return Err(StreamError::Trap(anyhow!("write exceeded budget")));
             }
-            None => return Err(StreamError::Trap(anyhow!("write exceeded budget"))),
+            let from_permit = len.min(self.permit);
+            self.permit -= from_permit;
+            state.reserved -= from_permit;
         }
+        state.buffered += len;
+        state.items.push_back(Job::Write(bytes));
         drop(state);
         self.worker.new_work.notify_one();
         Ok(())
@@ -185,19 +282,88 @@ impl HostOutputStream for AsyncWriteStream {
         let mut state = self.worker.state();
         state.check_error()?;
 
-        state.flush_pending = true;
+        // Lossy streams don't wait for the writer to catch up.
+        if self.worker.lossy {
+            return Ok(());
+        }
+
+        // No more writes are permitted until the flush completes.
+        state.reserved -= self.permit;
+        self.permit = 0;
+        state.flushes_requested += 1;
+        self.flush = state.flushes_requested;
+        state.items.push_back(Job::Flush(self.flush));
+        drop(state);
         self.worker.new_work.notify_one();
 
         Ok(())
     }
 
     fn check_write(&mut self) -> Result<usize, StreamError> {
-        self.worker.check_write()
+        let mut state = self.worker.state();
+        state.check_error()?;
+
+        // Lossy streams always accept writes, and discard what doesn't fit.
+        if self.worker.lossy {
+            return Ok(state.capacity);
+        }
+
+        // The new permit replaces the previous one.
+        state.reserved -= self.permit;
+        self.permit = 0;
+        if state.flushes_completed < self.flush {
+            return Ok(0);
+        }
+        self.permit = state.write_budget();
+        state.reserved += self.permit;
+        Ok(self.permit)
     }
 }
 #[async_trait::async_trait]
 impl Subscribe for AsyncWriteStream {
     async fn ready(&mut self) {
-        self.worker.ready().await;
+        self.worker.ready(self.flush, self.permit).await;
+    }
+}
+
+impl StdoutStream for AsyncWriteStream {
+    fn stream(&self) -> Box<dyn HostOutputStream> {
+        Box::new(self.clone())
+    }
+
+    fn isatty(&self) -> bool {
+        false
+    }
+}
+
+/// A handle for observing the buffering behavior of an [`AsyncWriteStream`].
+#[derive(Clone)]
+pub struct WriteStreamMetrics {
+    worker: Arc<Worker>,
+}
+
+impl WriteStreamMetrics {
+    /// Number of bytes accepted from the guest but not yet written.
+    pub fn buffered_bytes(&self) -> usize {
+        self.worker.state().buffered
+    }
+
+    /// Total number of bytes written to the underlying writer.
+    pub fn written_bytes(&self) -> u64 {
+        self.worker.state().written
+    }
+
+    /// Total number of bytes discarded because the buffer was full.
+    ///
+    /// This is only ever non-zero for streams created with
+    /// [`AsyncWriteStream::new_lossy`].
+    pub fn dropped_bytes(&self) -> u64 {
+        self.worker.state().dropped
+    }
+
+    /// Total time the guest has spent waiting for this stream to become
+    /// writable because of backpressure.
+    pub fn blocked_time(&self) -> Duration {
+        self.worker.state().blocked
     }
 }
@@ -10,7 +10,8 @@ use wasmtime_wasi::preview2::bindings::wasi::clocks::wall_clock;
 use wasmtime_wasi::preview2::bindings::wasi::filesystem::types as filesystem;
 use wasmtime_wasi::preview2::command::{add_to_linker, Command};
 use wasmtime_wasi::preview2::{
-    self, DirPerms, FilePerms, HostMonotonicClock, HostWallClock, WasiCtx, WasiCtxBuilder, WasiView,
+    self, DirPerms, FilePerms, FsQuota, HostMonotonicClock, HostWallClock, WasiCtx, WasiCtxBuilder,
+    WasiView,
 };
 
 struct CommandCtx {
@@ -119,6 +120,40 @@ async fn api_read_only() -> Result<()> {
         .map_err(|()| anyhow::anyhow!("command returned with failing exit status"))
 }
 
+#[test_log::test(tokio::test(flavor = "multi_thread"))]
+async fn api_fs_quota() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+
+    let table = ResourceTable::new();
+    let open_dir = Dir::open_ambient_dir(dir.path(), ambient_authority())?;
+    let wasi = WasiCtxBuilder::new()
+        .preopened_dir(open_dir, DirPerms::all(), FilePerms::all(), "/")
+        .filesystem_quota(FsQuota {
+            max_bytes_read: Some(8),
+            max_bytes_written: Some(10),
+            max_files_created: Some(2),
+            max_dir_entries_listed: None,
+        })
+        .build();
+
+    let (mut store, command) =
+        instantiate(API_FS_QUOTA_COMPONENT, CommandCtx { table, wasi }).await?;
+
+    command
+        .wasi_cli_run()
+        .call_run(&mut store)
+        .await?
+        .map_err(|()| anyhow::anyhow!("command returned with failing exit status"))?;
+
+    let usage = store.data().wasi.filesystem_usage();
+    assert_eq!(usage.bytes_read, 8);
+    assert_eq!(usage.bytes_written, 10);
+    assert_eq!(usage.files_created, 2);
+    assert_eq!(std::fs::read(dir.path().join("bar.txt"))?, b"0123456789");
+    assert!(!dir.path().join("baz.txt").exists());
+    Ok(())
+}
+
 // This is tested in the wasi-http crate, but need to satisfy the `foreach_api!`
 // macro above.
 #[allow(dead_code)]
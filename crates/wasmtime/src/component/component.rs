@@ -290,6 +290,13 @@ impl Component {
         &self.inner.static_modules[idx]
     }
 
+    /// Returns all core modules defined within this component, in the order
+    /// they're defined.
+    #[cfg(feature = "profiling")]
+    pub(crate) fn static_modules(&self) -> impl ExactSizeIterator<Item = &Module> + '_ {
+        self.inner.static_modules.values()
+    }
+
     #[inline]
     pub(crate) fn types(&self) -> &Arc<ComponentTypes> {
         self.inner.component_types()
@@ -10,8 +10,6 @@ use std::str::FromStr;
 use std::sync::Arc;
 use target_lexicon::Architecture;
 use wasmparser::WasmFeatures;
-#[cfg(feature = "cache")]
-use wasmtime_cache::CacheConfig;
 use wasmtime_environ::Tunables;
 use wasmtime_jit::profiling::{self, ProfilingAgent};
 use wasmtime_runtime::{mpk, InstanceAllocator, OnDemandInstanceAllocator, RuntimeMemoryCreator};
@@ -21,6 +19,11 @@ use crate::stack::{StackCreator, StackCreatorProxy};
 #[cfg(feature = "async")]
 use wasmtime_fiber::RuntimeFiberStackCreator;
 
+#[cfg(feature = "cache")]
+pub use wasmtime_cache::{
+    CacheConfig, CacheConfigBuilder, CacheStatistics, CacheStore as ModuleCacheStore,
+    InMemoryCacheStore,
+};
 pub use wasmtime_environ::CacheStore;
 pub use wasmtime_runtime::MpkEnabled;
 
@@ -1053,6 +1056,56 @@ impl Config {
         Ok(self)
     }
 
+    /// Applies the given cache configuration.
+    ///
+    /// This is the programmatic equivalent of [`Config::cache_config_load`]:
+    /// the configuration is typically created with [`CacheConfig::builder`],
+    /// whose settings correspond to those of the [configuration file][docs].
+    ///
+    /// ```
+    /// # use wasmtime::{CacheConfig, Config};
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let dir = tempfile::tempdir()?;
+    /// let cache = CacheConfig::builder()
+    ///     .directory(dir.path())
+    ///     .files_total_size_soft_limit(256 << 20)
+    ///     .build()?;
+    /// let mut config = Config::new();
+    /// config.cache_config(cache);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// This method is only available when the `cache` feature of this crate is
+    /// enabled.
+    ///
+    /// [docs]: https://bytecodealliance.github.io/wasmtime/cli-cache.html
+    #[cfg(feature = "cache")]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "cache")))]
+    pub fn cache_config(&mut self, cache_config: CacheConfig) -> &mut Self {
+        self.cache_config = cache_config;
+        self
+    }
+
+    /// Caches compiled modules in the given `store` instead of a directory.
+    ///
+    /// This allows compiled artifacts to be kept in memory, e.g. with
+    /// [`InMemoryCacheStore`], or shared between machines. Like a cache
+    /// directory, the store is only consulted when compiling modules with
+    /// [`Module::new`](crate::Module::new) and similar methods.
+    ///
+    /// This replaces any cache configuration previously loaded with
+    /// [`Config::cache_config_load`].
+    ///
+    /// This method is only available when the `cache` feature of this crate is
+    /// enabled.
+    #[cfg(feature = "cache")]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "cache")))]
+    pub fn module_cache_store(&mut self, store: Arc<dyn ModuleCacheStore>) -> &mut Self {
+        self.cache_config = CacheConfig::new_cache_with_store(store);
+        self
+    }
+
     /// Disable caching.
     ///
     /// Every call to [`Module::new(my_wasm)`][crate::Module::new] will
@@ -10,7 +10,7 @@ use std::path::Path;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 #[cfg(feature = "cache")]
-use wasmtime_cache::CacheConfig;
+use wasmtime_cache::{CacheConfig, CacheStatistics};
 use wasmtime_environ::obj;
 use wasmtime_environ::{FlagValue, ObjectKind};
 use wasmtime_jit::{profiling::ProfilingAgent, CodeMemory};
@@ -151,6 +151,25 @@ impl Engine {
         &self.config().cache_config
     }
 
+    /// Returns the number of modules compiled with this engine which were
+    /// loaded from the cache and which had to be compiled, or `None` if the
+    /// cache is disabled.
+    ///
+    /// Unlike [`CacheConfig::statistics`], this only counts the modules of
+    /// this engine, not those of all processes using the cache directory.
+    #[cfg(feature = "cache")]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "cache")))]
+    pub fn cache_statistics(&self) -> Option<CacheStatistics> {
+        let cache_config = self.cache_config();
+        if !cache_config.enabled() {
+            return None;
+        }
+        Some(CacheStatistics {
+            hits: cache_config.cache_hits() as u64,
+            misses: cache_config.cache_misses() as u64,
+        })
+    }
+
     /// Returns whether the engine `a` and `b` refer to the same configuration.
     pub fn same(a: &Engine, b: &Engine) -> bool {
         Arc::ptr_eq(&a.inner, &b.inner)
@@ -652,6 +671,21 @@ impl Engine {
         serialization::detect_precompiled_bytes(bytes)
     }
 
+    /// Returns the compilation settings recorded in a precompiled artifact.
+    ///
+    /// These are the settings which are checked against this engine's when the
+    /// artifact is deserialized, which makes this useful to find out why an
+    /// artifact can't be loaded. Unlike deserialization this doesn't require
+    /// the artifact to be compatible with this engine.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `bytes` isn't a precompiled artifact produced by a
+    /// compatible version of Wasmtime's serialization format.
+    pub fn precompiled_settings(&self, bytes: &[u8]) -> Result<PrecompiledSettings> {
+        serialization::precompiled_settings(bytes)
+    }
+
     /// Like [`Engine::detect_precompiled`], but performs the detection on a file.
     pub fn detect_precompiled_file(&self, path: impl AsRef<Path>) -> Result<Option<Precompiled>> {
         serialization::detect_precompiled_file(path)
@@ -673,14 +707,34 @@ pub enum Precompiled {
     Component,
 }
 
+/// Return value from the [`Engine::precompiled_settings`] API.
+#[derive(Clone, Debug)]
+pub struct PrecompiledSettings {
+    /// Whether the artifact is a core wasm module or a component.
+    pub kind: Precompiled,
+    /// The Wasmtime version, or custom version string, which produced the
+    /// artifact.
+    pub version: String,
+    /// The target triple the artifact was compiled for.
+    pub target: String,
+    /// The target-independent compiler settings, by name.
+    pub flags: Vec<(String, String)>,
+    /// The target-specific compiler settings, by name.
+    pub isa_flags: Vec<(String, String)>,
+}
+
 #[cfg(test)]
 mod tests {
     use std::{
         collections::hash_map::DefaultHasher,
         hash::{Hash, Hasher},
+        sync::Arc,
     };
 
-    use crate::{Config, Engine, Module, ModuleVersionStrategy, OptLevel};
+    use crate::{
+        CacheStatistics, Config, Engine, InMemoryCacheStore, Module, ModuleVersionStrategy,
+        OptLevel,
+    };
 
     use anyhow::Result;
     use tempfile::TempDir;
@@ -711,6 +765,10 @@ mod tests {
         Module::new(&engine, "(module (func))")?;
         assert_eq!(engine.config().cache_config.cache_hits(), 1);
         assert_eq!(engine.config().cache_config.cache_misses(), 1);
+        assert_eq!(
+            engine.cache_statistics(),
+            Some(CacheStatistics { hits: 1, misses: 1 })
+        );
 
         let mut cfg = Config::new();
         cfg.cranelift_opt_level(OptLevel::Speed)
@@ -747,6 +805,28 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn cache_uses_custom_store() -> Result<()> {
+        let store = Arc::new(InMemoryCacheStore::new(1 << 20));
+        let mut cfg = Config::new();
+        cfg.module_cache_store(store.clone());
+        let engine = Engine::new(&cfg)?;
+        Module::new(&engine, "(module (func))")?;
+        assert_eq!(store.len(), 1);
+        assert_eq!(engine.config().cache_config.cache_misses(), 1);
+
+        // A different engine sharing the store doesn't compile the module
+        // again.
+        let mut cfg = Config::new();
+        cfg.module_cache_store(store.clone());
+        let engine = Engine::new(&cfg)?;
+        Module::new(&engine, "(module (func))")?;
+        assert_eq!(engine.config().cache_config.cache_hits(), 1);
+        assert_eq!(engine.config().cache_config.cache_misses(), 0);
+        Ok(())
+    }
+
     #[test]
     fn precompile_compatibility_key_accounts_for_opt_level() {
         fn hash_for_config(cfg: &Config) -> u64 {
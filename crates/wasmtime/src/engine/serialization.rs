@@ -21,7 +21,7 @@
 //! other random ELF files, as well as provide better error messages for
 //! using wasmtime artifacts across versions.
 
-use crate::{Engine, ModuleVersionStrategy, Precompiled};
+use crate::{Engine, ModuleVersionStrategy, Precompiled, PrecompiledSettings};
 use anyhow::{anyhow, bail, Context, Result};
 use object::write::{Object, StandardSegment};
 use object::{File, FileFlags, Object as _, ObjectSection, SectionKind};
@@ -100,25 +100,7 @@ pub fn check_compatible(engine: &Engine, mmap: &MmapVec, expected: ObjectKind) -
         _ => bail!("incompatible object file format"),
     }
 
-    let data = obj
-        .section_by_name(obj::ELF_WASM_ENGINE)
-        .ok_or_else(|| anyhow!("failed to find section `{}`", obj::ELF_WASM_ENGINE))?
-        .data()?;
-    let (first, data) = data
-        .split_first()
-        .ok_or_else(|| anyhow!("invalid engine section"))?;
-    if *first != VERSION {
-        bail!("mismatched version in engine section");
-    }
-    let (len, data) = data
-        .split_first()
-        .ok_or_else(|| anyhow!("invalid engine section"))?;
-    let len = usize::from(*len);
-    let (version, data) = if data.len() < len + 1 {
-        bail!("engine section too small")
-    } else {
-        data.split_at(len)
-    };
+    let (version, data) = engine_section(&obj)?;
 
     match &engine.config().module_version {
         ModuleVersionStrategy::WasmtimeVersion => {
@@ -144,8 +126,54 @@ pub fn check_compatible(engine: &Engine, mmap: &MmapVec, expected: ObjectKind) -
     bincode::deserialize::<Metadata<'_>>(data)?.check_compatible(engine)
 }
 
+/// Returns the version string and the encoded `Metadata` of the engine section
+/// of `obj`.
+fn engine_section<'data>(obj: &File<'data>) -> Result<(&'data [u8], &'data [u8])> {
+    let data = obj
+        .section_by_name(obj::ELF_WASM_ENGINE)
+        .ok_or_else(|| anyhow!("failed to find section `{}`", obj::ELF_WASM_ENGINE))?
+        .data()?;
+    let (first, data) = data
+        .split_first()
+        .ok_or_else(|| anyhow!("invalid engine section"))?;
+    if *first != VERSION {
+        bail!("mismatched version in engine section");
+    }
+    let (len, data) = data
+        .split_first()
+        .ok_or_else(|| anyhow!("invalid engine section"))?;
+    let len = usize::from(*len);
+    if data.len() < len + 1 {
+        bail!("engine section too small")
+    }
+    Ok(data.split_at(len))
+}
+
+pub fn precompiled_settings(bytes: &[u8]) -> Result<PrecompiledSettings> {
+    let obj = File::parse(bytes).context("failed to parse precompiled artifact as an ELF")?;
+    let kind = match detect_precompiled(&obj) {
+        Some(kind) => kind,
+        None => bail!("not a precompiled artifact produced by Wasmtime"),
+    };
+    let (version, data) = engine_section(&obj)?;
+    let metadata = bincode::deserialize::<Metadata<'_>>(data)?;
+    let flags = |flags: Vec<(&str, FlagValue<'_>)>| {
+        flags
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    };
+    Ok(PrecompiledSettings {
+        kind,
+        version: String::from_utf8_lossy(version).into_owned(),
+        target: metadata.target,
+        flags: flags(metadata.shared_flags),
+        isa_flags: flags(metadata.isa_flags),
+    })
+}
+
 fn detect_precompiled<'data, R: object::ReadRef<'data>>(
-    obj: File<'data, R>,
+    obj: &File<'data, R>,
 ) -> Option<Precompiled> {
     match obj.flags() {
         FileFlags::Elf {
@@ -163,13 +191,13 @@ fn detect_precompiled<'data, R: object::ReadRef<'data>>(
 }
 
 pub fn detect_precompiled_bytes(bytes: &[u8]) -> Option<Precompiled> {
-    detect_precompiled(File::parse(bytes).ok()?)
+    detect_precompiled(&File::parse(bytes).ok()?)
 }
 
 pub fn detect_precompiled_file(path: impl AsRef<std::path::Path>) -> Result<Option<Precompiled>> {
     let read_cache = object::ReadCache::new(std::fs::File::open(path)?);
     let obj = File::parse(&read_cache)?;
-    Ok(detect_precompiled(obj))
+    Ok(detect_precompiled(&obj))
 }
 
 #[derive(Serialize, Deserialize)]
@@ -654,4 +682,24 @@ Caused by:
 
         Ok(())
     }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_precompiled_settings() -> Result<()> {
+        let mut config = Config::new();
+        config.cranelift_opt_level(crate::OptLevel::SpeedAndSize);
+        let engine = Engine::new(&config)?;
+        let bytes = engine.precompile_module(b"(module)")?;
+
+        let settings = engine.precompiled_settings(&bytes)?;
+        assert_eq!(settings.kind, Precompiled::Module);
+        assert_eq!(settings.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(settings.target, engine.compiler().triple().to_string());
+        assert!(settings
+            .flags
+            .contains(&("opt_level".to_string(), "speed_and_size".to_string())));
+
+        assert!(engine.precompiled_settings(b"(module)").is_err());
+        Ok(())
+    }
 }
@@ -832,6 +832,19 @@ impl SharedMemory {
         self.0.atomic_notify(addr, count)
     }
 
+    /// Wakes up all threads blocked on this shared memory, whichever address
+    /// they are blocked on.
+    ///
+    /// The woken threads observe this as if they had been notified with
+    /// [`SharedMemory::atomic_notify`]. This is intended for embedders which
+    /// need blocked threads to make progress, e.g., to reach an epoch check
+    /// when stopping all threads.
+    ///
+    /// This function returns the number of threads awoken.
+    pub fn atomic_notify_all(&self) -> u32 {
+        self.0.atomic_notify_all()
+    }
+
     /// Equivalent of the WebAssembly `memory.atomic.wait32` instruction for
     /// this shared memory.
     ///
@@ -73,7 +73,13 @@ use wasmtime_runtime::Backtrace;
 #[derive(Debug)]
 pub struct GuestProfiler {
     profile: Profile,
-    modules: Vec<(Range<usize>, fxprof_processed_profile::LibraryHandle)>,
+    /// The address range of each function of the profiled modules, sorted by
+    /// address, along with the start of the text section containing it and
+    /// the library of its module.
+    ///
+    /// Functions are tracked individually rather than by text section since
+    /// the modules of a component share a single text section.
+    functions: Vec<(Range<usize>, usize, fxprof_processed_profile::LibraryHandle)>,
     process: fxprof_processed_profile::ProcessHandle,
     thread: fxprof_processed_profile::ThreadHandle,
     start: Instant,
@@ -99,17 +105,21 @@ impl GuestProfiler {
         let zero = ReferenceTimestamp::from_millis_since_unix_epoch(0.0);
         let mut profile = Profile::new(module_name, zero, interval.into());
 
-        let mut modules: Vec<_> = modules
-            .into_iter()
-            .filter_map(|(name, module)| {
-                let compiled = module.compiled_module();
-                let text = compiled.text().as_ptr_range();
-                let address_range = text.start as usize..text.end as usize;
-                module_symbols(name, compiled).map(|lib| (address_range, profile.add_lib(lib)))
-            })
-            .collect();
+        let mut functions = Vec::new();
+        for (name, module) in modules {
+            let compiled = module.compiled_module();
+            let text = compiled.text().as_ptr() as usize;
+            if let Some(lib) = module_symbols(name, compiled) {
+                let lib = profile.add_lib(lib);
+                functions.extend(compiled.finished_functions().map(|(defined_idx, _)| {
+                    let loc = compiled.func_loc(defined_idx);
+                    let start = text + loc.start as usize;
+                    (start..start + loc.length as usize, text, lib)
+                }));
+            }
+        }
 
-        modules.sort_unstable_by_key(|(range, _)| range.start);
+        functions.sort_unstable_by_key(|(range, _, _)| range.start);
 
         profile.set_reference_timestamp(std::time::SystemTime::now().into());
         let process = profile.add_process(module_name, 0, Timestamp::from_nanos_since_reference(0));
@@ -117,13 +127,43 @@ impl GuestProfiler {
         let start = Instant::now();
         Self {
             profile,
-            modules,
+            functions,
             process,
             thread,
             start,
         }
     }
 
+    /// Begin profiling a new guest instantiated from `component`.
+    ///
+    /// This is like [`GuestProfiler::new`] except that every core module
+    /// defined within `component` is included in the profile, in addition to
+    /// any `extra_modules`. Core modules are named relative to the component
+    /// as `component_name/name`, where `name` is taken from the module's name
+    /// section if it has one and is otherwise `module{N}` for the `N`th core
+    /// module in the component.
+    #[cfg(feature = "component-model")]
+    pub fn new_component(
+        component_name: &str,
+        interval: Duration,
+        component: crate::component::Component,
+        extra_modules: impl IntoIterator<Item = (String, Module)>,
+    ) -> Self {
+        let modules = component
+            .static_modules()
+            .enumerate()
+            .map(|(i, module)| {
+                let name = match module.name() {
+                    Some(name) => format!("{component_name}/{name}"),
+                    None => format!("{component_name}/module{i}"),
+                };
+                (name, module.clone())
+            })
+            .chain(extra_modules)
+            .collect();
+        Self::new(component_name, interval, modules)
+    }
+
     /// Add a sample to the profile. This function collects a backtrace from
     /// any stack frames for allowed modules on the current stack. It should
     /// typically be called from a callback registered using
@@ -140,23 +180,25 @@ impl GuestProfiler {
             // first, so iterate in reverse.
             .rev()
             .filter_map(|frame| {
-                // Find the first module whose start address includes this PC.
-                let module_idx = self
-                    .modules
-                    .partition_point(|(range, _)| range.start > frame.pc());
-                if let Some((range, lib)) = self.modules.get(module_idx) {
-                    if range.contains(&frame.pc()) {
-                        return Some(FrameInfo {
-                            frame: Frame::RelativeAddressFromReturnAddress(
-                                *lib,
-                                u32::try_from(frame.pc() - range.start).unwrap(),
-                            ),
-                            category_pair: CategoryHandle::OTHER.into(),
-                            flags: FrameFlags::empty(),
-                        });
-                    }
+                // Find the last function starting at or before this PC, and
+                // check that it includes it.
+                let pc = frame.pc();
+                let idx = self
+                    .functions
+                    .partition_point(|(range, _, _)| range.start <= pc);
+                let (range, text, lib) = self.functions.get(idx.checked_sub(1)?)?;
+                if !range.contains(&pc) {
+                    return None;
                 }
-                None
+                // Symbols are relative to the start of the text section.
+                Some(FrameInfo {
+                    frame: Frame::RelativeAddressFromReturnAddress(
+                        *lib,
+                        u32::try_from(pc - text).unwrap(),
+                    ),
+                    category_pair: CategoryHandle::OTHER.into(),
+                    flags: FrameFlags::empty(),
+                })
             });
 
         self.profile
@@ -210,3 +252,83 @@ fn module_symbols(name: String, compiled: &CompiledModule) -> Option<LibraryInfo
         symbol_table: Some(Arc::new(SymbolTable::new(symbols))),
     })
 }
+
+#[cfg(all(test, feature = "component-model"))]
+mod tests {
+    use super::GuestProfiler;
+    use crate::component::{Component, Linker};
+    use crate::{Config, Engine, Store};
+    use anyhow::Result;
+    use std::time::Duration;
+
+    #[test]
+    fn component_frames_resolve_to_their_module() -> Result<()> {
+        let mut config = Config::new();
+        config.wasm_component_model(true);
+        let engine = Engine::new(&config)?;
+        let component = Component::new(
+            &engine,
+            r#"
+                (component
+                    (import "sample" (func $sample))
+                    (core func $sample (canon lower (func $sample)))
+                    (core instance $host (export "sample" (func $sample)))
+                    (core module $a
+                        (import "host" "sample" (func $sample))
+                        (func $a_inner (export "a") call $sample)
+                    )
+                    (core instance $a (instantiate $a (with "host" (instance $host))))
+                    (core module $b
+                        (import "a" "a" (func $a))
+                        (func $b_outer (export "b") call $a)
+                    )
+                    (core instance $b (instantiate $b (with "a" (instance $a))))
+                    (func (export "run") (canon lift (core func $b "b")))
+                )
+            "#,
+        )?;
+
+        let profiler =
+            GuestProfiler::new_component("test", Duration::from_millis(1), component.clone(), []);
+        let mut store = Store::new(&engine, Some(profiler));
+        let mut linker = Linker::<Option<GuestProfiler>>::new(&engine);
+        linker.root().func_wrap("sample", |mut store, ()| {
+            let mut profiler = store.data_mut().take().unwrap();
+            profiler.sample(&store);
+            *store.data_mut() = Some(profiler);
+            Ok(())
+        })?;
+        let instance = linker.instantiate(&mut store, &component)?;
+        let run = instance.get_typed_func::<(), ()>(&mut store, "run")?;
+        run.call(&mut store, ())?;
+        run.post_return(&mut store)?;
+
+        let mut output = Vec::new();
+        store.data_mut().take().unwrap().finish(&mut output)?;
+        let profile: serde_json::Value = serde_json::from_slice(&output)?;
+
+        // Map the name of each sampled function to the name of its library.
+        let libs = profile["libs"].as_array().unwrap();
+        let thread = &profile["threads"][0];
+        let strings = thread["stringArray"].as_array().unwrap();
+        let funcs = &thread["funcTable"];
+        let resources = &thread["resourceTable"];
+        let mut frames = Vec::new();
+        for (name, resource) in funcs["name"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .zip(funcs["resource"].as_array().unwrap())
+        {
+            let name = strings[name.as_u64().unwrap() as usize].as_str().unwrap();
+            let lib = resources["lib"][resource.as_u64().unwrap() as usize]
+                .as_u64()
+                .unwrap();
+            let lib = libs[lib as usize]["name"].as_str().unwrap();
+            frames.push((name, lib));
+        }
+        frames.sort();
+        assert_eq!(frames, [("a_inner", "test/a"), ("b_outer", "test/b")]);
+        Ok(())
+    }
+}
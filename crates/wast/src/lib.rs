@@ -9,7 +9,7 @@ mod spectest;
 mod wast;
 
 pub use crate::spectest::link_spectest;
-pub use crate::wast::WastContext;
+pub use crate::wast::{DirectiveReport, WastContext};
 
 /// Version number of this crate.
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
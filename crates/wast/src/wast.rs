@@ -7,6 +7,7 @@ use std::collections::HashMap;
 use std::path::Path;
 use std::str;
 use std::thread;
+use std::time::{Duration, Instant};
 use wasmtime::*;
 use wast::lexer::Lexer;
 use wast::parser::{self, ParseBuffer};
@@ -24,6 +25,23 @@ pub struct WastContext<T> {
     store: Store<T>,
 }
 
+/// The outcome of a directive of a wast script, as returned by
+/// [`WastContext::run_file_with_report`].
+#[derive(Debug)]
+pub struct DirectiveReport {
+    /// The name of the directive, such as `assert_return`.
+    pub kind: &'static str,
+    /// The line of the directive in the script, starting at 1.
+    pub line: usize,
+    /// The column of the opening parenthesis of the directive in the script,
+    /// starting at 1.
+    pub column: usize,
+    /// The time it took to run the directive.
+    pub time: Duration,
+    /// The error of the directive if it failed.
+    pub error: Option<Error>,
+}
+
 enum Outcome<T = Results> {
     Ok(T),
     Trap(Error),
@@ -240,15 +258,7 @@ where
                     Outcome::Trap(e) => return Err(e).context("instantiation failed"),
                 };
                 if let Some(name) = name {
-                    // TODO: should ideally reflect more than just modules into
-                    // the linker's namespace but that's not easily supported
-                    // today for host functions due to the inability to take a
-                    // function from one instance and put it into the linker
-                    // (must go through the host right now).
-                    let mut linker = self.component_linker.instance(name.name())?;
-                    for (name, module) in instance.exports(&mut self.store).root().modules() {
-                        linker.module(name, module)?;
-                    }
+                    self.register_component(name.name(), instance)?;
                 }
                 self.current = Some(InstanceKind::Component(instance));
             }
@@ -258,6 +268,21 @@ where
         Ok(())
     }
 
+    /// Register a component instance under `name` so later components can
+    /// import it.
+    #[cfg(feature = "component-model")]
+    fn register_component(&mut self, name: &str, instance: component::Instance) -> Result<()> {
+        // TODO: should ideally reflect more than just modules into the
+        // linker's namespace but that's not easily supported today for host
+        // functions due to the inability to take a function from one instance
+        // and put it into the linker (must go through the host right now).
+        let mut linker = self.component_linker.instance(name)?;
+        for (name, module) in instance.exports(&mut self.store).root().modules() {
+            linker.module(name, module)?;
+        }
+        Ok(())
+    }
+
     /// Register an instance to make it available for performing actions.
     fn register(&mut self, name: Option<&str>, as_name: &str) -> Result<()> {
         match name {
@@ -273,8 +298,9 @@ where
                             .instance(&mut self.store, as_name, *current)?;
                     }
                     #[cfg(feature = "component-model")]
-                    InstanceKind::Component(_) => {
-                        bail!("register not implemented for components");
+                    InstanceKind::Component(current) => {
+                        let current = *current;
+                        self.register_component(as_name, current)?;
                     }
                 }
                 Ok(())
@@ -353,6 +379,32 @@ where
 
     /// Run a wast script from a byte buffer.
     pub fn run_buffer(&mut self, filename: &str, wast: &[u8]) -> Result<()> {
+        self.run_buffer_with(filename, wast, None)
+    }
+
+    /// Run a wast script from a byte buffer, returning the outcome of each of
+    /// its directives.
+    ///
+    /// Failing directives are recorded instead of being returned as an error,
+    /// which is only returned if the script can't be parsed. The script stops
+    /// at the first failing directive unless `keep_going` is true.
+    pub fn run_buffer_with_report(
+        &mut self,
+        filename: &str,
+        wast: &[u8],
+        keep_going: bool,
+    ) -> Result<Vec<DirectiveReport>> {
+        let mut reports = Vec::new();
+        self.run_buffer_with(filename, wast, Some((&mut reports, keep_going)))?;
+        Ok(reports)
+    }
+
+    fn run_buffer_with(
+        &mut self,
+        filename: &str,
+        wast: &[u8],
+        reports: Option<(&mut Vec<DirectiveReport>, bool)>,
+    ) -> Result<()> {
         let wast = str::from_utf8(wast)?;
 
         let adjust_wast = |mut err: wast::Error| {
@@ -366,7 +418,7 @@ where
         let buf = ParseBuffer::new_with_lexer(lexer).map_err(adjust_wast)?;
         let ast = parser::parse::<Wast>(&buf).map_err(adjust_wast)?;
 
-        self.run_directives(ast.directives, filename, wast)
+        self.run_directives(ast.directives, filename, wast, reports)
     }
 
     fn run_directives(
@@ -374,6 +426,7 @@ where
         directives: Vec<WastDirective<'_>>,
         filename: &str,
         wast: &str,
+        mut reports: Option<(&mut Vec<DirectiveReport>, bool)>,
     ) -> Result<()> {
         let adjust_wast = |mut err: wast::Error| {
             err.set_path(filename.as_ref());
@@ -383,13 +436,17 @@ where
 
         thread::scope(|scope| {
             let mut threads = HashMap::new();
+            let (mut line, mut counted) = (0, 0);
             for directive in directives {
                 let sp = directive.span();
+                let kind = directive_kind(&directive);
                 if log::log_enabled!(log::Level::Debug) {
                     let (line, col) = sp.linecol_in(wast);
                     log::debug!("running directive on {}:{}:{}", filename, line + 1, col);
                 }
-                self.run_directive(directive, filename, wast, &scope, &mut threads)
+                let start = Instant::now();
+                let result = self
+                    .run_directive(directive, filename, wast, &scope, &mut threads)
                     .map_err(|e| match e.downcast() {
                         Ok(err) => adjust_wast(err).into(),
                         Err(e) => e,
@@ -397,7 +454,40 @@ where
                     .with_context(|| {
                         let (line, col) = sp.linecol_in(wast);
                         format!("failed directive on {}:{}:{}", filename, line + 1, col)
-                    })?;
+                    });
+                let (reports, keep_going) = match &mut reports {
+                    Some((reports, keep_going)) => (reports, *keep_going),
+                    None => {
+                        result?;
+                        continue;
+                    }
+                };
+                let failed = result.is_err();
+                // Directives are in the order of the script, so lines are
+                // counted from the previous directive rather than with
+                // `linecol_in` which would scan the whole script each time.
+                let offset = sp.offset();
+                line += wast[counted..offset].matches('\n').count();
+                counted = offset;
+                // The span is that of the keyword following the opening
+                // parenthesis, so this is the 1-based column of the latter.
+                let col = offset - wast[..offset].rfind('\n').map_or(0, |i| i + 1);
+                reports.push(DirectiveReport {
+                    kind,
+                    line: line + 1,
+                    column: col,
+                    time: start.elapsed(),
+                    error: result.err(),
+                });
+                if failed && !keep_going {
+                    break;
+                }
+                if failed && matches!(kind, "module" | "component") {
+                    // Unnamed directives after this one refer to the module
+                    // which couldn't be instantiated, so they mustn't run
+                    // against the previous one instead.
+                    self.current = None;
+                }
             }
             Ok(())
         })
@@ -519,8 +609,9 @@ where
                     store: Store::new(self.store.engine(), self.store.data().clone()),
                 };
                 let name = thread.name.name();
-                let child =
-                    scope.spawn(move || child_cx.run_directives(thread.directives, filename, wast));
+                let child = scope.spawn(move || {
+                    child_cx.run_directives(thread.directives, filename, wast, None)
+                });
                 threads.insert(name, child);
             }
 
@@ -543,6 +634,38 @@ where
             std::fs::read(path).with_context(|| format!("failed to read `{}`", path.display()))?;
         self.run_buffer(path.to_str().unwrap(), &bytes)
     }
+
+    /// Run a wast script from a file, returning the outcome of each of its
+    /// directives as with [`WastContext::run_buffer_with_report`].
+    pub fn run_file_with_report(
+        &mut self,
+        path: &Path,
+        keep_going: bool,
+    ) -> Result<Vec<DirectiveReport>> {
+        let bytes =
+            std::fs::read(path).with_context(|| format!("failed to read `{}`", path.display()))?;
+        self.run_buffer_with_report(path.to_str().unwrap(), &bytes, keep_going)
+    }
+}
+
+fn directive_kind(directive: &WastDirective<'_>) -> &'static str {
+    match directive {
+        WastDirective::Wat(QuoteWat::Wat(Wat::Component(_)) | QuoteWat::QuoteComponent(..)) => {
+            "component"
+        }
+        WastDirective::Wat(_) => "module",
+        WastDirective::Register { .. } => "register",
+        WastDirective::Invoke(_) => "invoke",
+        WastDirective::AssertMalformed { .. } => "assert_malformed",
+        WastDirective::AssertInvalid { .. } => "assert_invalid",
+        WastDirective::AssertTrap { .. } => "assert_trap",
+        WastDirective::AssertReturn { .. } => "assert_return",
+        WastDirective::AssertExhaustion { .. } => "assert_exhaustion",
+        WastDirective::AssertUnlinkable { .. } => "assert_unlinkable",
+        WastDirective::AssertException { .. } => "assert_exception",
+        WastDirective::Thread(_) => "thread",
+        WastDirective::Wait { .. } => "wait",
+    }
 }
 
 fn is_matching_assert_invalid_error_message(expected: &str, actual: &str) -> bool {
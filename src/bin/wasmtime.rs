@@ -50,6 +50,14 @@ enum Subcommand {
     /// Runs a WebAssembly module
     Run(wasmtime_cli::commands::RunCommand),
 
+    /// Instantiates a WebAssembly module or component and interactively calls
+    /// its exports
+    Repl(wasmtime_cli::commands::ReplCommand),
+
+    /// Inspects and manages the compilation cache
+    #[cfg(feature = "cache")]
+    Cache(wasmtime_cli::commands::CacheCommand),
+
     /// Controls Wasmtime configuration settings
     #[cfg(feature = "cache")]
     Config(wasmtime_cli::commands::ConfigCommand),
@@ -62,6 +70,10 @@ enum Subcommand {
     #[cfg(feature = "explore")]
     Explore(wasmtime_cli::commands::ExploreCommand),
 
+    /// Disassembles a precompiled WebAssembly module or component.
+    #[cfg(feature = "objdump")]
+    Objdump(wasmtime_cli::commands::ObjdumpCommand),
+
     /// Serves requests from a wasi-http proxy component.
     #[cfg(feature = "serve")]
     Serve(wasmtime_cli::commands::ServeCommand),
@@ -82,6 +94,11 @@ impl Wasmtime {
         match subcommand {
             Subcommand::Run(c) => c.execute(),
 
+            Subcommand::Repl(c) => c.execute(),
+
+            #[cfg(feature = "cache")]
+            Subcommand::Cache(c) => c.execute(),
+
             #[cfg(feature = "cache")]
             Subcommand::Config(c) => c.execute(),
 
@@ -91,6 +108,9 @@ impl Wasmtime {
             #[cfg(feature = "explore")]
             Subcommand::Explore(c) => c.execute(),
 
+            #[cfg(feature = "objdump")]
+            Subcommand::Objdump(c) => c.execute(),
+
             #[cfg(feature = "serve")]
             Subcommand::Serve(c) => c.execute(),
 
@@ -3,6 +3,9 @@
 mod run;
 pub use self::run::*;
 
+mod repl;
+pub use self::repl::*;
+
 #[cfg(feature = "serve")]
 mod serve;
 #[cfg(feature = "serve")]
@@ -13,11 +16,21 @@ mod explore;
 #[cfg(feature = "explore")]
 pub use self::explore::*;
 
+#[cfg(feature = "objdump")]
+mod objdump;
+#[cfg(feature = "objdump")]
+pub use self::objdump::*;
+
 #[cfg(feature = "wast")]
 mod wast;
 #[cfg(feature = "wast")]
 pub use self::wast::*;
 
+#[cfg(feature = "cache")]
+mod cache;
+#[cfg(feature = "cache")]
+pub use self::cache::*;
+
 #[cfg(feature = "cache")]
 mod config;
 #[cfg(feature = "cache")]
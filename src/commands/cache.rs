@@ -0,0 +1,144 @@
+//! The module that implements the `wasmtime cache` command.
+
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use wasmtime::Engine;
+use wasmtime_cache::{CacheConfig, CachedModule};
+
+const CACHE_AFTER_HELP: &str = "If no configuration file is specified, the \
+     system configuration file is used, as with the `--config` option of other \
+     commands.";
+
+/// Inspects and manages the compilation cache
+#[derive(Parser, PartialEq)]
+#[command(after_help = CACHE_AFTER_HELP)]
+pub struct CacheCommand {
+    /// The cache configuration file to use
+    #[arg(long, value_name = "CONFIG_PATH", global = true)]
+    config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    subcommand: CacheSubcommand,
+}
+
+#[derive(Subcommand, PartialEq)]
+enum CacheSubcommand {
+    /// Displays hit and miss counters and the size of the cache
+    Stats,
+    /// Lists the compiled modules in the cache, most recently used first
+    List(CacheListCommand),
+    /// Cleans up the cache now, as is otherwise periodically done in the
+    /// background
+    Gc,
+    /// Deletes all compiled modules from the cache
+    Clear,
+}
+
+impl CacheCommand {
+    /// Executes the command.
+    pub fn execute(self) -> Result<()> {
+        // None of the subcommands compile anything, so there's no need for
+        // a worker, which could even recreate files while they're removed.
+        let config = CacheConfig::from_file_without_worker(self.config.as_deref())?;
+        match self.subcommand {
+            CacheSubcommand::Stats => stats(&config),
+            CacheSubcommand::List(c) => c.execute(&config),
+            CacheSubcommand::Gc => {
+                config.clean_up()?;
+                println!(
+                    "Cleaned up the cache at '{}'.",
+                    config.directory().display()
+                );
+                Ok(())
+            }
+            CacheSubcommand::Clear => {
+                config.clear()?;
+                println!("Cleared the cache at '{}'.", config.directory().display());
+                Ok(())
+            }
+        }
+    }
+}
+
+fn stats(config: &CacheConfig) -> Result<()> {
+    let statistics = config.statistics()?;
+    let modules = config.cached_modules()?;
+    let size = modules.iter().map(|m| m.size).sum::<u64>();
+    let lookups = statistics.hits + statistics.misses;
+
+    println!("directory: {}", config.directory().display());
+    println!("modules:   {}", modules.len());
+    println!("size:      {}", format_size(size));
+    println!("hits:      {}", statistics.hits);
+    println!("misses:    {}", statistics.misses);
+    if lookups > 0 {
+        let rate = statistics.hits as f64 * 100.0 / lookups as f64;
+        println!("hit rate:  {rate:.1}%");
+    }
+    Ok(())
+}
+
+/// Lists the compiled modules in the cache
+#[derive(Parser, PartialEq)]
+struct CacheListCommand {
+    /// Display all compiler flags of each module instead of only the target
+    /// and optimization level
+    #[arg(long)]
+    flags: bool,
+}
+
+impl CacheListCommand {
+    fn execute(self, config: &CacheConfig) -> Result<()> {
+        let engine = Engine::default();
+        let now = SystemTime::now();
+        for module in config.cached_modules()? {
+            let age = now.duration_since(module.last_used).unwrap_or_default();
+            println!(
+                "{} {:>10} {:>12} ago  {}",
+                module.hash,
+                format_size(module.size),
+                humantime::format_duration(Duration::from_secs(age.as_secs())).to_string(),
+                module.compiler,
+            );
+            self.print_settings(&engine, &module);
+        }
+        Ok(())
+    }
+
+    fn print_settings(&self, engine: &Engine, module: &CachedModule) {
+        // Entries are only a cache, so unreadable ones are reported but aren't
+        // an error.
+        let settings = match module
+            .read()
+            .and_then(|bytes| engine.precompiled_settings(&bytes))
+        {
+            Ok(settings) => settings,
+            Err(e) => {
+                println!("    unreadable: {e:#}");
+                return;
+            }
+        };
+        println!("    target: {}", settings.target);
+        for (name, value) in settings.flags.iter().chain(&settings.isa_flags) {
+            if self.flags || name == "opt_level" {
+                println!("    {name}: {value}");
+            }
+        }
+    }
+}
+
+fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if size < 1024 {
+        return format!("{size} B");
+    }
+    let mut size = size as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
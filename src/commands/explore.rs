@@ -1,11 +1,12 @@
 //! The module that implements the `wasmtime explore` command.
 
 use anyhow::{Context, Result};
-use clap::Parser;
-use std::path::PathBuf;
+use clap::{Parser, ValueEnum};
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use wasmtime_cli_flags::CommonOptions;
 
-/// Explore the compilation of a WebAssembly module to native code.
+/// Explore the compilation of a WebAssembly module or component to native code.
 #[derive(Parser, PartialEq)]
 pub struct ExploreCommand {
     #[command(flatten)]
@@ -15,14 +16,27 @@ pub struct ExploreCommand {
     #[arg(long, value_name = "TARGET")]
     target: Option<String>,
 
-    /// The path of the WebAssembly module to compile
+    /// The path of the WebAssembly module or component to compile
     #[arg(required = true, value_name = "MODULE")]
     module: PathBuf,
 
     /// The path of the explorer output (derived from the MODULE name if none
-    /// provided)
+    /// provided), or `-` for stdout
     #[arg(short, long)]
     output: Option<PathBuf>,
+
+    /// The format of the explorer output
+    #[arg(long, value_enum, default_value_t = Format::Html)]
+    format: Format,
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq)]
+enum Format {
+    /// A self-contained HTML page
+    Html,
+    /// Machine-readable JSON, e.g. to compare the code generated with different
+    /// settings
+    Json,
 }
 
 impl ExploreCommand {
@@ -35,16 +49,33 @@ impl ExploreCommand {
         let wasm = std::fs::read(&self.module)
             .with_context(|| format!("failed to read Wasm module: {}", self.module.display()))?;
 
+        let extension = match self.format {
+            Format::Html => "explore.html",
+            Format::Json => "explore.json",
+        };
         let output = self
             .output
             .clone()
-            .unwrap_or_else(|| self.module.with_extension("explore.html"));
-        let output_file = std::fs::File::create(&output)
-            .with_context(|| format!("failed to create file: {}", output.display()))?;
-        let mut output_file = std::io::BufWriter::new(output_file);
+            .unwrap_or_else(|| self.module.with_extension(extension));
+        let mut output_file: Box<dyn Write> = if output == Path::new("-") {
+            Box::new(std::io::stdout().lock())
+        } else {
+            let file = std::fs::File::create(&output)
+                .with_context(|| format!("failed to create file: {}", output.display()))?;
+            Box::new(std::io::BufWriter::new(file))
+        };
 
-        wasmtime_explorer::generate(&config, self.target.as_deref(), &wasm, &mut output_file)?;
-        println!("Exploration written to {}", output.display());
+        let target = self.target.as_deref();
+        match self.format {
+            Format::Html => wasmtime_explorer::generate(&config, target, &wasm, &mut output_file)?,
+            Format::Json => {
+                wasmtime_explorer::generate_json(&config, target, &wasm, &mut output_file)?
+            }
+        }
+        output_file.flush()?;
+        if output != Path::new("-") {
+            println!("Exploration written to {}", output.display());
+        }
         Ok(())
     }
 }
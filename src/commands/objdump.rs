@@ -0,0 +1,256 @@
+//! The module that implements the `wasmtime objdump` command.
+
+use anyhow::{anyhow, bail, Context, Result};
+use capstone::arch::BuildsCapstone;
+use capstone::Capstone;
+use clap::Parser;
+use object::{Object, ObjectSection, ObjectSymbol, RelocationTarget, SymbolKind};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::str::FromStr;
+use target_lexicon::{Architecture, Triple};
+use wasmtime::{Engine, Precompiled};
+use wasmtime_environ::{obj, StackMap, Trap};
+use wasmtime_jit::CompiledModuleInfo;
+
+/// Disassembles a precompiled WebAssembly module or component
+#[derive(Parser, PartialEq)]
+pub struct ObjdumpCommand {
+    /// The path of the precompiled artifact, as produced by `wasmtime compile`
+    #[arg(required = true, value_name = "CWASM")]
+    path: PathBuf,
+
+    /// Only disassemble functions whose name contains this string
+    #[arg(long, value_name = "STRING")]
+    filter: Option<String>,
+
+    /// Display the raw bytes of each instruction
+    #[arg(long)]
+    bytes: bool,
+}
+
+impl ObjdumpCommand {
+    /// Executes the command.
+    pub fn execute(self) -> Result<()> {
+        let bytes = std::fs::read(&self.path)
+            .with_context(|| format!("failed to read {}", self.path.display()))?;
+        let settings = Engine::default()
+            .precompiled_settings(&bytes)
+            .with_context(|| format!("{} is not a precompiled artifact", self.path.display()))?;
+        let triple = Triple::from_str(&settings.target).map_err(|e| anyhow!("{e}"))?;
+        let cs = capstone(&triple)?;
+
+        let file = object::File::parse(&bytes[..])?;
+        let text = file
+            .section_by_name(".text")
+            .ok_or_else(|| anyhow!("artifact has no text section"))?;
+        let text_data = text.data()?;
+        let annotations = Annotations::new(&file, &text, settings.kind)?;
+
+        let mut functions = file
+            .symbols()
+            .filter(|sym| {
+                sym.kind() == SymbolKind::Text
+                    && sym.section_index() == Some(text.index())
+                    && sym.size() > 0
+            })
+            .map(|sym| {
+                let name = sym.name()?;
+                match sym.address().checked_sub(text.address()) {
+                    Some(start) => Ok((start, sym.size(), name)),
+                    None => bail!("symbol `{name}` is outside of the text section"),
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        functions.sort();
+
+        let mut first = true;
+        for (start, size, name) in functions {
+            if let Some(filter) = &self.filter {
+                if !name.contains(filter.as_str()) {
+                    continue;
+                }
+            }
+            if !first {
+                println!();
+            }
+            first = false;
+
+            println!("{start:08x} <{name}>:");
+            let body = usize::try_from(start)
+                .ok()
+                .zip(usize::try_from(size).ok())
+                .and_then(|(start, size)| text_data.get(start..)?.get(..size))
+                .with_context(|| format!("symbol `{name}` is outside of the text section"))?;
+            let instructions = cs.disasm_all(body, start).map_err(|e| anyhow!("{e}"))?;
+            for inst in instructions.iter() {
+                let range = inst.address()..inst.address() + inst.bytes().len() as u64;
+                let mut line = format!("{:8x}:", inst.address());
+                if self.bytes {
+                    let bytes = inst.bytes().iter().fold(String::new(), |mut s, b| {
+                        let _ = write!(s, " {b:02x}");
+                        s
+                    });
+                    let _ = write!(line, "{bytes:<31}");
+                }
+                let _ = write!(
+                    line,
+                    " {} {}",
+                    inst.mnemonic().unwrap_or(""),
+                    inst.op_str().unwrap_or("")
+                );
+                let notes = annotations.for_instruction(range);
+                if notes.is_empty() {
+                    println!("{}", line.trim_end());
+                } else {
+                    println!("{line:<60} ; {}", notes.join(", "));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn capstone(triple: &Triple) -> Result<Capstone> {
+    let mut cs = match triple.architecture {
+        Architecture::Aarch64(_) => Capstone::new()
+            .arm64()
+            .mode(capstone::arch::arm64::ArchMode::Arm)
+            .build(),
+        Architecture::Riscv64(_) => Capstone::new()
+            .riscv()
+            .mode(capstone::arch::riscv::ArchMode::RiscV64)
+            .build(),
+        Architecture::S390x => Capstone::new()
+            .sysz()
+            .mode(capstone::arch::sysz::ArchMode::Default)
+            .build(),
+        Architecture::X86_64 => Capstone::new()
+            .x86()
+            .mode(capstone::arch::x86::ArchMode::Mode64)
+            .build(),
+        _ => bail!("unsupported target: {triple}"),
+    }
+    .map_err(|e| anyhow!("{e}"))?;
+
+    // Skip over anything that looks like data, such as inline constant pools
+    // and trapping instructions on AArch64.
+    cs.set_skipdata(true).map_err(|e| anyhow!("{e}"))?;
+    Ok(cs)
+}
+
+/// Wasmtime's metadata about the text section, keyed by text offset.
+struct Annotations {
+    /// Wasm offsets from the `ELF_WASMTIME_ADDRMAP` section.
+    address_map: BTreeMap<u64, Option<u32>>,
+    /// Trap codes from the `ELF_WASMTIME_TRAPS` section.
+    traps: BTreeMap<u64, Trap>,
+    /// Names of the libcalls that relocations in the text section refer to.
+    libcalls: BTreeMap<u64, String>,
+    /// The number of words and the live words of each stack map, keyed by
+    /// the return address of the call it describes.
+    stack_maps: BTreeMap<u64, (u32, Vec<usize>)>,
+}
+
+impl Annotations {
+    fn new<'a>(
+        file: &object::File<'a>,
+        text: &object::Section<'a, '_>,
+        kind: Precompiled,
+    ) -> Result<Self> {
+        let section = |name: &str| -> Result<Option<&'a [u8]>> {
+            match file.section_by_name(name) {
+                Some(section) => Ok(Some(section.data()?)),
+                None => Ok(None),
+            }
+        };
+
+        let mut address_map = BTreeMap::new();
+        if let Some(data) = section(obj::ELF_WASMTIME_ADDRMAP)? {
+            let entries = wasmtime_environ::iterate_address_map(data)
+                .ok_or_else(|| anyhow!("invalid address map section"))?;
+            for (offset, pos) in entries {
+                address_map.insert(u64::from(offset), pos.file_offset());
+            }
+        }
+
+        let mut traps = BTreeMap::new();
+        if let Some(data) = section(obj::ELF_WASMTIME_TRAPS)? {
+            let entries = wasmtime_environ::iterate_traps(data)
+                .ok_or_else(|| anyhow!("invalid trap section"))?;
+            for (offset, trap) in entries {
+                traps.insert(u64::from(offset), trap);
+            }
+        }
+
+        let mut libcalls = BTreeMap::new();
+        for (offset, reloc) in text.relocations() {
+            if let RelocationTarget::Symbol(index) = reloc.target() {
+                let name = file.symbol_by_index(index)?.name()?;
+                libcalls.insert(offset, name.to_string());
+            }
+        }
+
+        // Stack maps are only recorded in the bincode-encoded metadata of the
+        // artifact, whose format depends on its kind; components aren't
+        // supported at this time.
+        let mut stack_maps = BTreeMap::new();
+        if kind == Precompiled::Module {
+            if let Some(data) = section(obj::ELF_WASMTIME_INFO)? {
+                let info: CompiledModuleInfo =
+                    bincode::deserialize(data).context("failed to decode module metadata")?;
+                for (loc, maps) in info.stack_maps() {
+                    for map in maps.iter() {
+                        let offset = u64::from(loc.start + map.code_offset);
+                        let words = map.stack_map.mapped_words();
+                        stack_maps.insert(offset, (words, live_words(&map.stack_map)));
+                    }
+                }
+            }
+        }
+
+        Ok(Annotations {
+            address_map,
+            traps,
+            libcalls,
+            stack_maps,
+        })
+    }
+
+    /// Returns the notes for the instruction occupying `range` in the text
+    /// section.
+    fn for_instruction(&self, range: std::ops::Range<u64>) -> Vec<String> {
+        let mut notes = Vec::new();
+        for (_, pos) in self.address_map.range(range.clone()) {
+            if let Some(pos) = pos {
+                notes.push(format!("@{pos:#x}"));
+            }
+        }
+        for (_, trap) in self.traps.range(range.clone()) {
+            notes.push(format!("trap: {trap:?}"));
+        }
+        for (_, name) in self.libcalls.range(range.clone()) {
+            notes.push(format!("libcall: {name}"));
+        }
+        // Stack maps are recorded at the end of the call instruction, which is
+        // the return address.
+        for (_, (words, live)) in self.stack_maps.range(range.start + 1..range.end + 1) {
+            let live = live
+                .iter()
+                .map(|w| w.to_string())
+                .collect::<Vec<_>>()
+                .join(" ");
+            notes.push(format!("stack map: {words} words, live: [{live}]"));
+        }
+        notes
+    }
+}
+
+/// Returns the indices of the words in the stack map which hold live
+/// references.
+fn live_words(map: &StackMap) -> Vec<usize> {
+    (0..map.mapped_words() as usize)
+        .filter(|&i| map.get_bit(i))
+        .collect()
+}
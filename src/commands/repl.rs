@@ -0,0 +1,476 @@
+//! The module that implements the `wasmtime repl` command.
+
+#![cfg_attr(
+    not(feature = "component-model"),
+    allow(irrefutable_let_patterns, unreachable_patterns)
+)]
+
+use super::run::{display_val, parse_val, CliLinker, Host, RunCommand};
+use crate::common::{Profile, RunTarget};
+use anyhow::{anyhow, bail, Context as _, Error, Result};
+use clap::Parser;
+use std::fmt::Write as _;
+use std::io::{BufRead, IsTerminal, Write};
+use wasmtime::{Engine, ExternType, Instance, Module, Mutability, Store};
+use wasmtime_wasi::preview2;
+
+const REPL_AFTER_HELP: &str = "The following commands are available at the prompt:
+
+  exports                     Lists the exports of the module
+  call NAME [ARGS...]         Calls the exported function NAME with ARGS
+  run                         Runs the module as a WASI command in a new instance
+  global NAME [VALUE]         Displays the exported global NAME, or sets it to VALUE
+  read MEMORY OFFSET LEN      Displays LEN bytes of the exported memory MEMORY
+  write MEMORY OFFSET BYTES   Writes the hexadecimal BYTES to the exported memory MEMORY
+  help                        Displays the list of commands
+  quit                        Exits the REPL
+
+Arguments are separated by whitespace and can be quoted with double quotes. \
+Functions exported from an instance exported by a component are named \
+`INSTANCE#FUNC`; components don't export globals or memories.";
+
+/// Instantiates a WebAssembly module or component once and interactively
+/// calls its exports
+#[derive(Parser, PartialEq)]
+#[command(after_help = REPL_AFTER_HELP)]
+pub struct ReplCommand {
+    #[command(flatten)]
+    run: RunCommand,
+}
+
+enum ReplTarget {
+    Core(Module, Instance),
+    #[cfg(feature = "component-model")]
+    Component(wasmtime::component::Instance),
+}
+
+struct Repl<'a> {
+    cmd: &'a RunCommand,
+    main: RunTarget,
+    store: Store<Host>,
+    target: ReplTarget,
+}
+
+/// Instantiates the main module or component in a new store, invoking
+/// `_initialize` if it's a reactor.
+fn instantiate(
+    run: &RunCommand,
+    engine: &Engine,
+    main: &RunTarget,
+) -> Result<(Store<Host>, ReplTarget)> {
+    let (mut store, mut linker, _) = run.new_store_and_linker(engine, main)?;
+    run.define_unknown_imports(&mut linker, main)?;
+
+    let target = match &mut linker {
+        CliLinker::Core(linker) => {
+            let module = main.unwrap_core();
+            let instance = linker
+                .instantiate(&mut store, module)
+                .with_context(|| format!("failed to instantiate {:?}", run.module_and_args[0]))?;
+
+            // If `_initialize` is present, meaning a reactor, then invoke
+            // the function.
+            if let Some(func) = instance.get_func(&mut store, "_initialize") {
+                func.typed::<(), ()>(&store)?.call(&mut store, ())?;
+            }
+            ReplTarget::Core(module.clone(), instance)
+        }
+        #[cfg(feature = "component-model")]
+        CliLinker::Component(linker) => {
+            let instance = linker
+                .instantiate(&mut store, main.unwrap_component())
+                .with_context(|| format!("failed to instantiate {:?}", run.module_and_args[0]))?;
+            ReplTarget::Component(instance)
+        }
+    };
+    Ok((store, target))
+}
+
+impl ReplCommand {
+    /// Executes the command.
+    pub fn execute(mut self) -> Result<()> {
+        self.run.load_manifest()?;
+        self.run.run.common.init_logging()?;
+
+        let run = &self.run;
+        if run.invoke.is_some() {
+            bail!("`--invoke` is not supported by `wasmtime repl`, use the `call` command instead");
+        }
+        if let Some(Profile::Guest { .. }) = run.run.profile {
+            bail!("guest profiling is not supported by `wasmtime repl`");
+        }
+        if run.run.common.wasm.timeout.is_some() {
+            bail!("`-W timeout` is not supported by `wasmtime repl`");
+        }
+        if run.report.is_some() {
+            bail!("`--report` is not supported by `wasmtime repl`");
+        }
+
+        let engine = self.run.new_engine()?;
+        let run = &self.run;
+        let main = run
+            .run
+            .load_module(&engine, run.module_and_args[0].as_ref())?;
+        let (store, target) = instantiate(run, &engine, &main)?;
+
+        let mut repl = Repl {
+            cmd: run,
+            main,
+            store,
+            target,
+        };
+        let stdin = std::io::stdin();
+        let interactive = stdin.is_terminal();
+        let mut lines = stdin.lock().lines();
+        loop {
+            if interactive {
+                print!("> ");
+                std::io::stdout().flush()?;
+            }
+            let line = match lines.next() {
+                Some(line) => line?,
+                None => break,
+            };
+            let words = split_words(&line)?;
+            let (command, args) = match words.split_first() {
+                Some((command, args)) => (command.as_str(), args),
+                None => continue,
+            };
+            let result = match command {
+                "quit" | "exit" => break,
+                "help" => {
+                    println!("{REPL_AFTER_HELP}");
+                    Ok(())
+                }
+                "exports" => repl.exports(),
+                "call" => match args.split_first() {
+                    Some((name, args)) => repl.call(name, args),
+                    None => Err(anyhow!("usage: call NAME [ARGS...]")),
+                },
+                "run" => repl.run(),
+                "global" => match args {
+                    [name] => repl.global(name, None),
+                    [name, value] => repl.global(name, Some(value)),
+                    _ => Err(anyhow!("usage: global NAME [VALUE]")),
+                },
+                "read" => match args {
+                    [memory, offset, len] => repl.read(memory, offset, len),
+                    _ => Err(anyhow!("usage: read MEMORY OFFSET LEN")),
+                },
+                "write" => match args {
+                    [memory, offset, bytes @ ..] if !bytes.is_empty() => {
+                        repl.write(memory, offset, &bytes.concat())
+                    }
+                    _ => Err(anyhow!("usage: write MEMORY OFFSET BYTES")),
+                },
+                _ => Err(anyhow!("unknown command `{command}`, try `help`")),
+            };
+            if let Err(e) = result {
+                eprintln!("error: {e:?}");
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Repl<'_> {
+    fn core_instance(&self) -> Result<(&Module, Instance)> {
+        match &self.target {
+            ReplTarget::Core(module, instance) => Ok((module, *instance)),
+            #[cfg(feature = "component-model")]
+            ReplTarget::Component(_) => bail!("this command is only supported for core modules"),
+        }
+    }
+
+    fn exports(&mut self) -> Result<()> {
+        let (module, _) = self.core_instance()?;
+        for export in module.exports() {
+            let ty = match export.ty() {
+                ExternType::Func(ty) => {
+                    let params = ty.params().map(|t| t.to_string()).collect::<Vec<_>>();
+                    let results = ty.results().map(|t| t.to_string()).collect::<Vec<_>>();
+                    format!("func ({}) -> ({})", params.join(", "), results.join(", "))
+                }
+                ExternType::Global(ty) => match ty.mutability() {
+                    Mutability::Const => format!("global {}", ty.content()),
+                    Mutability::Var => format!("global mut {}", ty.content()),
+                },
+                ExternType::Table(ty) => format!("table {}", ty.element()),
+                ExternType::Memory(ty) => format!("memory {} pages", ty.minimum()),
+            };
+            println!("{}: {ty}", export.name());
+        }
+        Ok(())
+    }
+
+    fn call(&mut self, name: &str, args: &[String]) -> Result<()> {
+        match &self.target {
+            ReplTarget::Core(_, instance) => {
+                let func = instance
+                    .get_func(&mut self.store, name)
+                    .ok_or_else(|| anyhow!("no func export named `{name}` found"))?;
+                let ty = func.ty(&self.store);
+                if args.len() != ty.params().len() {
+                    bail!("`{name}` takes {} arguments", ty.params().len());
+                }
+                let values = ty
+                    .params()
+                    .zip(args)
+                    .map(|(ty, arg)| parse_val(&ty, arg))
+                    .collect::<Result<Vec<_>>>()?;
+                let mut results = vec![wasmtime::Val::null(); ty.results().len()];
+                func.call(&mut self.store, &values, &mut results)
+                    .map_err(report_exit)?;
+                for result in results {
+                    println!("{}", display_val(&result));
+                }
+                Ok(())
+            }
+            #[cfg(feature = "component-model")]
+            ReplTarget::Component(instance) => {
+                let func = component::get_func(&mut self.store, instance, name)?;
+                component::call(&mut self.store, func, name, args)
+            }
+        }
+    }
+
+    /// Runs the program as a WASI command in a new instance, as the entry
+    /// point of a command may only be called once, leaving the instance the
+    /// other commands use untouched.
+    fn run(&mut self) -> Result<()> {
+        let engine = self.store.engine().clone();
+        let (mut store, target) = instantiate(self.cmd, &engine, &self.main)?;
+        match target {
+            ReplTarget::Core(_, instance) => {
+                let func = instance
+                    .get_func(&mut store, "")
+                    .or_else(|| instance.get_func(&mut store, "_start"))
+                    .ok_or_else(|| anyhow!("the module isn't a WASI command"))?;
+                func.typed::<(), ()>(&store)?
+                    .call(&mut store, ())
+                    .map_err(report_exit)
+            }
+            #[cfg(feature = "component-model")]
+            ReplTarget::Component(instance) => {
+                let command = preview2::command::sync::Command::new(&mut store, &instance)
+                    .context("the component isn't a WASI command")?;
+                match command.wasi_cli_run().call_run(&mut store)? {
+                    Ok(()) => Ok(()),
+                    Err(()) => Err(report_exit(preview2::I32Exit(1).into())),
+                }
+            }
+        }
+    }
+
+    fn global(&mut self, name: &str, value: Option<&String>) -> Result<()> {
+        let (_, instance) = self.core_instance()?;
+        let global = instance
+            .get_global(&mut self.store, name)
+            .ok_or_else(|| anyhow!("no global export named `{name}` found"))?;
+        match value {
+            Some(value) => {
+                let ty = global.ty(&self.store);
+                global.set(&mut self.store, parse_val(ty.content(), value)?)
+            }
+            None => {
+                println!("{}", display_val(&global.get(&mut self.store)));
+                Ok(())
+            }
+        }
+    }
+
+    fn read(&mut self, memory: &str, offset: &str, len: &str) -> Result<()> {
+        let (_, instance) = self.core_instance()?;
+        let memory = instance
+            .get_memory(&mut self.store, memory)
+            .ok_or_else(|| anyhow!("no memory export named `{memory}` found"))?;
+        let offset = parse_usize(offset)?;
+        let len = parse_usize(len)?;
+        let bytes = offset
+            .checked_add(len)
+            .and_then(|end| memory.data(&self.store).get(offset..end))
+            .ok_or_else(|| anyhow!("out of bounds of the memory"))?;
+        for (i, line) in bytes.chunks(16).enumerate() {
+            let mut hex = String::new();
+            for byte in line {
+                let _ = write!(hex, "{byte:02x} ");
+            }
+            let ascii = line
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect::<String>();
+            println!("{:08x}: {hex:<48} |{ascii}|", offset + i * 16);
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, memory: &str, offset: &str, bytes: &str) -> Result<()> {
+        let (_, instance) = self.core_instance()?;
+        let memory = instance
+            .get_memory(&mut self.store, memory)
+            .ok_or_else(|| anyhow!("no memory export named `{memory}` found"))?;
+        let offset = parse_usize(offset)?;
+        let bytes = bytes
+            .split_whitespace()
+            .map(|s| s.strip_prefix("0x").unwrap_or(s))
+            .collect::<String>();
+        if bytes.len() % 2 != 0 {
+            bail!("expected an even number of hexadecimal digits");
+        }
+        let bytes = (0..bytes.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&bytes[i..i + 2], 16))
+            .collect::<Result<Vec<_>, _>>()
+            .context("invalid hexadecimal bytes")?;
+        memory
+            .write(&mut self.store, offset, &bytes)
+            .map_err(|_| anyhow!("out of bounds of the memory"))
+    }
+}
+
+/// Reports the exit status of a WASI program which exits, as the REPL keeps
+/// going afterwards.
+fn report_exit(e: Error) -> Error {
+    let code = e
+        .downcast_ref::<wasmtime_wasi::I32Exit>()
+        .map(|e| e.0)
+        .or_else(|| e.downcast_ref::<preview2::I32Exit>().map(|e| e.0));
+    match code {
+        Some(code) => anyhow!("exited with status {code}"),
+        None => e,
+    }
+}
+
+fn parse_usize(s: &str) -> Result<usize> {
+    let result = match s.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    result.with_context(|| format!("invalid number `{s}`"))
+}
+
+/// Splits a line into words separated by whitespace, where double quotes
+/// group words and backslashes escape the next character.
+fn split_words(line: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word = None::<String>;
+    let mut quoted = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                word.get_or_insert_with(String::new);
+            }
+            '\\' => {
+                let c = chars.next().ok_or_else(|| anyhow!("trailing backslash"))?;
+                word.get_or_insert_with(String::new).push(c);
+            }
+            c if c.is_whitespace() && !quoted => words.extend(word.take()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quoted {
+        bail!("unterminated quote");
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+#[cfg(feature = "component-model")]
+mod component {
+    use super::report_exit;
+    use crate::commands::run::Host;
+    use anyhow::{anyhow, bail, Result};
+    use wasmtime::component::{Func, Instance, Type, Val};
+    use wasmtime::Store;
+
+    pub(super) fn get_func(
+        store: &mut Store<Host>,
+        instance: &Instance,
+        name: &str,
+    ) -> Result<Func> {
+        let mut exports = instance.exports(&mut *store);
+        let func = match name.split_once('#') {
+            Some((instance, func)) => exports.instance(instance).and_then(|mut i| i.func(func)),
+            None => exports.root().func(name),
+        };
+        func.ok_or_else(|| anyhow!("no func export named `{name}` found"))
+    }
+
+    pub(super) fn call(
+        store: &mut Store<Host>,
+        func: Func,
+        name: &str,
+        args: &[String],
+    ) -> Result<()> {
+        let params = func.params(&*store);
+        if args.len() != params.len() {
+            bail!("`{name}` takes {} arguments", params.len());
+        }
+        let values = params
+            .iter()
+            .zip(args)
+            .map(|(ty, arg)| parse_val(ty, arg))
+            .collect::<Result<Vec<_>>>()?;
+        let mut results = vec![Val::Bool(false); func.results(&*store).len()];
+        func.call(&mut *store, &values, &mut results)
+            .map_err(report_exit)?;
+        for result in results.iter() {
+            println!("{}", display_val(result));
+        }
+        func.post_return(&mut *store)
+    }
+
+    fn parse_val(ty: &Type, val: &str) -> Result<Val> {
+        Ok(match ty {
+            Type::Bool => Val::Bool(val.parse()?),
+            Type::S8 => Val::S8(val.parse()?),
+            Type::U8 => Val::U8(val.parse()?),
+            Type::S16 => Val::S16(val.parse()?),
+            Type::U16 => Val::U16(val.parse()?),
+            Type::S32 => Val::S32(val.parse()?),
+            Type::U32 => Val::U32(val.parse()?),
+            Type::S64 => Val::S64(val.parse()?),
+            Type::U64 => Val::U64(val.parse()?),
+            Type::Float32 => Val::Float32(val.parse()?),
+            Type::Float64 => Val::Float64(val.parse()?),
+            Type::Char => {
+                let mut chars = val.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Val::Char(c),
+                    _ => bail!("expected a single character"),
+                }
+            }
+            Type::String => Val::String(val.into()),
+            Type::Enum(ty) => ty.new_val(val)?,
+            t => bail!("unsupported argument type {:?}", t),
+        })
+    }
+
+    fn display_val(val: &Val) -> String {
+        match val {
+            Val::Bool(b) => b.to_string(),
+            Val::S8(i) => i.to_string(),
+            Val::U8(i) => i.to_string(),
+            Val::S16(i) => i.to_string(),
+            Val::U16(i) => i.to_string(),
+            Val::S32(i) => i.to_string(),
+            Val::U32(i) => i.to_string(),
+            Val::S64(i) => i.to_string(),
+            Val::U64(i) => i.to_string(),
+            Val::Float32(f) => f.to_string(),
+            Val::Float64(f) => f.to_string(),
+            Val::Char(c) => format!("{c:?}"),
+            Val::String(s) => format!("{s:?}"),
+            Val::Enum(e) => e.discriminant().to_string(),
+            val => format!("{val:?}"),
+        }
+    }
+}
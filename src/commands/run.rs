@@ -6,6 +6,8 @@
 )]
 
 use crate::common::{Profile, RunCommon, RunTarget};
+use crate::manifest;
+use crate::report::{PeakMemoryLimits, Report};
 
 use anyhow::{anyhow, bail, Context as _, Error, Result};
 use clap::Parser;
@@ -13,7 +15,8 @@ use std::ffi::OsString;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::thread;
-use wasmtime::{Engine, Func, Module, Store, StoreLimits, Val, ValType};
+use std::time::Instant;
+use wasmtime::{Engine, Func, Module, Store, Val, ValType};
 use wasmtime_wasi::maybe_exit_on_error;
 use wasmtime_wasi::preview2;
 use wasmtime_wasi::sync::{ambient_authority, Dir, TcpListener, WasiCtxBuilder};
@@ -27,6 +30,9 @@ use wasmtime_wasi_threads::WasiThreadsCtx;
 #[cfg(feature = "wasi-http")]
 use wasmtime_wasi_http::WasiHttpCtx;
 
+#[cfg(feature = "wasi-keyvalue")]
+use wasmtime_wasi_keyvalue::WasiKeyValueCtx;
+
 fn parse_env_var(s: &str) -> Result<(String, Option<String>)> {
     let mut parts = s.splitn(2, '=');
     Ok((
@@ -92,16 +98,32 @@ pub struct RunCommand {
     )]
     pub preloads: Vec<(String, PathBuf)>,
 
+    /// Write a JSON report of the execution to the given path.
+    ///
+    /// The report describes how the program exited, including the trap and
+    /// its backtrace if it trapped, along with the fuel and memory it used
+    /// and the time spent compiling, instantiating and running it.
+    ///
+    /// This doesn't change how the program is compiled: source locations are
+    /// only included in the backtrace when `WASMTIME_BACKTRACE_DETAILS=1` is
+    /// set.
+    #[arg(long, value_name = "PATH")]
+    pub report: Option<PathBuf>,
+
     /// The WebAssembly module to run and arguments to pass to it.
     ///
     /// Arguments passed to the wasm module will be configured as WASI CLI
     /// arguments unless the `--invoke` CLI argument is passed in which case
     /// arguments will be interpreted as arguments to the function specified.
-    #[arg(value_name = "WASM", trailing_var_arg = true, required = true)]
+    #[arg(
+        value_name = "WASM",
+        trailing_var_arg = true,
+        required_unless_present = "manifest"
+    )]
     pub module_and_args: Vec<OsString>,
 }
 
-enum CliLinker {
+pub(crate) enum CliLinker {
     Core(wasmtime::Linker<Host>),
     #[cfg(feature = "component-model")]
     Component(wasmtime::component::Linker<Host>),
@@ -110,30 +132,30 @@ enum CliLinker {
 impl RunCommand {
     /// Executes the command.
     pub fn execute(mut self) -> Result<()> {
+        self.load_manifest()?;
         self.run.common.init_logging()?;
 
-        let mut config = self.run.common.config(None)?;
-
-        if self.run.common.wasm.timeout.is_some() {
-            config.epoch_interruption(true);
-        }
-        match self.run.profile {
-            Some(Profile::Native(s)) => {
-                config.profiler(s);
-            }
-            Some(Profile::Guest { .. }) => {
-                // Further configured down below as well.
-                config.epoch_interruption(true);
-            }
-            None => {}
+        let mut report = Report::start();
+        let result = self.run_and_report(&mut report);
+        if let Some(path) = &self.report {
+            report.finish(&result, path)?;
         }
 
-        let engine = Engine::new(&config)?;
+        // Exit the process if Wasmtime understands the error; otherwise, fall
+        // back on Rust's default error printing/return code.
+        result.map_err(maybe_exit_on_error)
+    }
+
+    fn run_and_report(&mut self, report: &mut Report) -> Result<()> {
+        let engine = self.new_engine()?;
 
         // Read the wasm module binary either as `*.wat` or a raw binary.
+        report.compile_started(&engine);
+        let start = Instant::now();
         let main = self
             .run
             .load_module(&engine, self.module_and_args[0].as_ref())?;
+        report.compile_finished(&engine, start.elapsed());
 
         // Validate coredump-on-trap argument
         if let Some(path) = &self.run.common.debug.coredump {
@@ -142,11 +164,108 @@ impl RunCommand {
             }
         }
 
-        let mut linker = match &main {
-            RunTarget::Core(_) => CliLinker::Core(wasmtime::Linker::new(&engine)),
+        let (mut store, mut linker, modules) = self.new_store_and_linker(&engine, &main)?;
+
+        // Load the main wasm module.
+        let result = self
+            .load_main_module(&mut store, &mut linker, &main, modules, report)
+            .with_context(|| {
+                format!(
+                    "failed to run main module `{}`",
+                    self.module_and_args[0].to_string_lossy()
+                )
+            });
+
+        // Failing to read the fuel left must not replace the error of the
+        // program, so it's left out of the report instead.
+        let fuel_consumed = self
+            .run
+            .common
+            .wasm
+            .fuel
+            .and_then(|fuel| Some(fuel - store.get_fuel().ok()?));
+        report.store_finished(fuel_consumed, store.data().limits.peak_memory_size());
+
+        result
+    }
+
+    /// Combines the options passed on the command line with the ones of the
+    /// manifest passed with `--manifest`, if any.
+    pub(crate) fn load_manifest(&mut self) -> Result<()> {
+        let path = match self.run.manifest.take() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let module_and_args = std::mem::take(&mut self.module_and_args);
+        let RunCommand {
+            run,
+            dirs,
+            vars,
+            invoke,
+            preloads,
+            report,
+            module_and_args,
+        } = manifest::parse("run", &path, module_and_args)?;
+        self.run.merge_defaults(run);
+        // Entries of the manifest come first, unless the command line has one
+        // with the same guest directory or name, which wins.
+        let dirs = dirs
+            .into_iter()
+            .filter(|(_, guest)| !self.dirs.iter().any(|(_, g)| g == guest))
+            .collect::<Vec<_>>();
+        self.dirs.splice(0..0, dirs);
+        let vars = vars
+            .into_iter()
+            .filter(|(name, _)| !self.vars.iter().any(|(n, _)| n == name))
+            .collect::<Vec<_>>();
+        self.vars.splice(0..0, vars);
+        self.invoke = self.invoke.take().or(invoke);
+        let preloads = preloads
+            .into_iter()
+            .filter(|(name, _)| !self.preloads.iter().any(|(n, _)| n == name))
+            .collect::<Vec<_>>();
+        self.preloads.splice(0..0, preloads);
+        self.report = self.report.take().or(report);
+        self.module_and_args = module_and_args;
+        Ok(())
+    }
+
+    /// Creates the engine configured by the command line options.
+    pub(crate) fn new_engine(&mut self) -> Result<Engine> {
+        let mut config = self.run.common.config(None)?;
+
+        // Epochs are also used to stop all threads once one of them failed.
+        if self.run.common.wasm.timeout.is_some() || self.run.common.wasi.threads == Some(true) {
+            config.epoch_interruption(true);
+        }
+        match self.run.profile {
+            Some(Profile::Native(s)) => {
+                config.profiler(s);
+            }
+            Some(Profile::Guest { .. }) => {
+                // Further configured down below as well.
+                config.epoch_interruption(true);
+            }
+            None => {}
+        }
+        Engine::new(&config)
+    }
+
+    /// Creates the store and the linker to instantiate `main` with, populated
+    /// with WASI and the preloaded modules.
+    ///
+    /// Also returns the preloaded modules along with the main module, if it's
+    /// a core module.
+    pub(crate) fn new_store_and_linker(
+        &self,
+        engine: &Engine,
+        main: &RunTarget,
+    ) -> Result<(Store<Host>, CliLinker, Vec<(String, Module)>)> {
+        let mut linker = match main {
+            RunTarget::Core(_) => CliLinker::Core(wasmtime::Linker::new(engine)),
             #[cfg(feature = "component-model")]
             RunTarget::Component(_) => {
-                CliLinker::Component(wasmtime::component::Linker::new(&engine))
+                CliLinker::Component(wasmtime::component::Linker::new(engine))
             }
         };
         if let Some(enable) = self.run.common.wasm.unknown_exports_allow {
@@ -162,10 +281,10 @@ impl RunCommand {
         }
 
         let host = Host::default();
-        let mut store = Store::new(&engine, host);
-        self.populate_with_wasi(&mut linker, &mut store, &main)?;
+        let mut store = Store::new(engine, host);
+        self.populate_with_wasi(&mut linker, &mut store, main)?;
 
-        store.data_mut().limits = self.run.store_limits();
+        store.data_mut().limits = PeakMemoryLimits::new(self.run.store_limits());
         store.limiter(|t| &mut t.limits);
 
         // If fuel has been configured, we want to add the configured
@@ -176,12 +295,12 @@ impl RunCommand {
 
         // Load the preload wasm modules.
         let mut modules = Vec::new();
-        if let RunTarget::Core(m) = &main {
+        if let RunTarget::Core(m) = main {
             modules.push((String::new(), m.clone()));
         }
         for (name, path) in self.preloads.iter() {
             // Read the wasm module binary either as `*.wat` or a raw binary
-            let module = match self.run.load_module(&engine, path)? {
+            let module = match self.run.load_module(engine, path)? {
                 RunTarget::Core(m) => m,
                 #[cfg(feature = "component-model")]
                 RunTarget::Component(_) => bail!("components cannot be loaded with `--preload`"),
@@ -209,25 +328,7 @@ impl RunCommand {
             }
         }
 
-        // Load the main wasm module.
-        match self
-            .load_main_module(&mut store, &mut linker, &main, modules)
-            .with_context(|| {
-                format!(
-                    "failed to run main module `{}`",
-                    self.module_and_args[0].to_string_lossy()
-                )
-            }) {
-            Ok(()) => (),
-            Err(e) => {
-                // Exit the process if Wasmtime understands the error;
-                // otherwise, fall back on Rust's default error printing/return
-                // code.
-                return Err(maybe_exit_on_error(e));
-            }
-        }
-
-        Ok(())
+        Ok((store, linker, modules))
     }
 
     fn compute_preopen_dirs(&self) -> Result<Vec<(String, Dir)>> {
@@ -282,14 +383,15 @@ impl RunCommand {
     fn setup_epoch_handler(
         &self,
         store: &mut Store<Host>,
+        main: &RunTarget,
         modules: Vec<(String, Module)>,
     ) -> Result<Box<dyn FnOnce(&mut Store<Host>)>> {
         if let Some(Profile::Guest { path, interval }) = &self.run.profile {
             #[cfg(feature = "profiling")]
-            return Ok(self.setup_guest_profiler(store, modules, path, *interval));
+            return Ok(self.setup_guest_profiler(store, main, modules, path, *interval));
             #[cfg(not(feature = "profiling"))]
             {
-                let _ = (modules, path, interval);
+                let _ = (main, modules, path, interval);
                 bail!("support for profiling disabled at compile time");
             }
         }
@@ -303,6 +405,25 @@ impl RunCommand {
             });
         }
 
+        // The main thread stops once a spawned thread failed, whose error is
+        // then reported instead.
+        #[cfg(feature = "wasi-threads")]
+        if self.run.common.wasi.threads == Some(true) {
+            let timeout = self.run.common.wasm.timeout.is_some();
+            store.epoch_deadline_callback(move |store| {
+                if let Some(threads) = &store.data().wasi_threads {
+                    if threads.failed() {
+                        bail!("stopped because a spawned thread failed");
+                    }
+                }
+                if timeout {
+                    return Err(wasmtime::Trap::Interrupt.into());
+                }
+                Ok(wasmtime::UpdateDeadline::Continue(1))
+            });
+            store.set_epoch_deadline(1);
+        }
+
         Ok(Box::new(|_store| {}))
     }
 
@@ -310,6 +431,7 @@ impl RunCommand {
     fn setup_guest_profiler(
         &self,
         store: &mut Store<Host>,
+        main: &RunTarget,
         modules: Vec<(String, Module)>,
         path: &str,
         interval: std::time::Duration,
@@ -317,8 +439,14 @@ impl RunCommand {
         use wasmtime::{AsContextMut, GuestProfiler, UpdateDeadline};
 
         let module_name = self.module_and_args[0].to_str().unwrap_or("<main module>");
-        store.data_mut().guest_profiler =
-            Some(Arc::new(GuestProfiler::new(module_name, interval, modules)));
+        let profiler = match main {
+            RunTarget::Core(_) => GuestProfiler::new(module_name, interval, modules),
+            #[cfg(feature = "component-model")]
+            RunTarget::Component(component) => {
+                GuestProfiler::new_component(module_name, interval, component.clone(), modules)
+            }
+        };
+        store.data_mut().guest_profiler = Some(Arc::new(profiler));
 
         fn sample(mut store: impl AsContextMut<Data = Host>) {
             let mut profiler = store
@@ -375,12 +503,12 @@ impl RunCommand {
         });
     }
 
-    fn load_main_module(
+    /// Defines the unknown imports of `module` as requested on the command
+    /// line.
+    pub(crate) fn define_unknown_imports(
         &self,
-        store: &mut Store<Host>,
         linker: &mut CliLinker,
         module: &RunTarget,
-        modules: Vec<(String, Module)>,
     ) -> Result<()> {
         // The main module might be allowed to have unknown imports, which
         // should be defined as traps:
@@ -409,15 +537,30 @@ impl RunCommand {
             bail!("support for `unknown-imports-trap` disabled at compile time");
         }
 
-        let finish_epoch_handler = self.setup_epoch_handler(store, modules)?;
+        Ok(())
+    }
+
+    fn load_main_module(
+        &self,
+        store: &mut Store<Host>,
+        linker: &mut CliLinker,
+        module: &RunTarget,
+        modules: Vec<(String, Module)>,
+        report: &mut Report,
+    ) -> Result<()> {
+        self.define_unknown_imports(linker, module)?;
+
+        let finish_epoch_handler = self.setup_epoch_handler(store, module, modules)?;
 
         let result = match linker {
             CliLinker::Core(linker) => {
                 let module = module.unwrap_core();
+                let start = Instant::now();
                 let instance = linker.instantiate(&mut *store, &module).context(format!(
                     "failed to instantiate {:?}",
                     self.module_and_args[0]
                 ))?;
+                report.instantiated(start.elapsed());
 
                 // If `_initialize` is present, meaning a reactor, then invoke
                 // the function.
@@ -452,8 +595,10 @@ impl RunCommand {
 
                 let component = module.unwrap_component();
 
+                let start = Instant::now();
                 let (command, _instance) =
                     preview2::command::sync::Command::instantiate(&mut *store, component, linker)?;
+                report.instantiated(start.elapsed());
                 let result = command
                     .wasi_cli_run()
                     .call_run(&mut *store)
@@ -470,6 +615,13 @@ impl RunCommand {
         };
         finish_epoch_handler(store);
 
+        #[cfg(feature = "wasi-threads")]
+        if let Some(threads) = &store.data().wasi_threads {
+            if let Some(e) = threads.take_error() {
+                return Err(e);
+            }
+        }
+
         result
     }
 
@@ -497,16 +649,7 @@ impl RunCommand {
             let val = val
                 .to_str()
                 .ok_or_else(|| anyhow!("argument is not valid utf-8: {val:?}"))?;
-            values.push(match ty {
-                // TODO: integer parsing here should handle hexadecimal notation
-                // like `0x0...`, but the Rust standard library currently only
-                // parses base-10 representations.
-                ValType::I32 => Val::I32(val.parse()?),
-                ValType::I64 => Val::I64(val.parse()?),
-                ValType::F32 => Val::F32(val.parse::<f32>()?.to_bits()),
-                ValType::F64 => Val::F64(val.parse::<f64>()?.to_bits()),
-                t => bail!("unsupported argument type {:?}", t),
-            });
+            values.push(parse_val(&ty, val)?);
         }
 
         // Invoke the function and then afterwards print all the results that came
@@ -534,15 +677,7 @@ impl RunCommand {
         }
 
         for result in results {
-            match result {
-                Val::I32(i) => println!("{}", i),
-                Val::I64(i) => println!("{}", i),
-                Val::F32(f) => println!("{}", f32::from_bits(f)),
-                Val::F64(f) => println!("{}", f64::from_bits(f)),
-                Val::ExternRef(_) => println!("<externref>"),
-                Val::FuncRef(_) => println!("<funcref>"),
-                Val::V128(i) => println!("{}", i.as_u128()),
-            }
+            println!("{}", display_val(&result));
         }
 
         Ok(())
@@ -584,6 +719,9 @@ impl RunCommand {
         if self.run.common.wasi.common != Some(false) {
             match linker {
                 CliLinker::Core(linker) => {
+                    if !self.run.config_vars.is_empty() || self.run.config_file.is_some() {
+                        bail!("runtime configuration is only supported for components");
+                    }
                     match (self.run.common.wasi.preview2, self.run.common.wasi.threads) {
                         // If preview2 is explicitly disabled, or if threads
                         // are enabled, then use the historical preview1
@@ -612,6 +750,9 @@ impl RunCommand {
                 #[cfg(feature = "component-model")]
                 CliLinker::Component(linker) => {
                     preview2::command::sync::add_to_linker(linker)?;
+                    if self.run.runtime_config_enabled() {
+                        preview2::bindings::config::runtime::add_to_linker(linker, |t| t)?;
+                    }
                     self.set_preview2_ctx(store)?;
                 }
             }
@@ -640,22 +781,11 @@ impl RunCommand {
                     }
                     #[cfg(feature = "component-model")]
                     CliLinker::Component(linker) => {
-                        wasmtime_wasi_nn::wit::ML::add_to_linker(linker, |host| {
-                            Arc::get_mut(host.wasi_nn.as_mut().unwrap())
-                                .expect("wasi-nn is not implemented with multi-threading support")
-                        })?;
+                        wasmtime_wasi_nn::wit::sync::add_to_linker(linker)?;
                     }
                 }
-                let graphs = self
-                    .run
-                    .common
-                    .wasi
-                    .nn_graph
-                    .iter()
-                    .map(|g| (g.format.clone(), g.dir.clone()))
-                    .collect::<Vec<_>>();
-                let (backends, registry) = wasmtime_wasi_nn::preload(&graphs)?;
-                store.data_mut().wasi_nn = Some(Arc::new(WasiNnCtx::new(backends, registry)));
+                let ctx = self.run.wasi_nn_ctx()?;
+                store.data_mut().wasi_nn = Some(Arc::new(ctx));
             }
         }
 
@@ -680,10 +810,12 @@ impl RunCommand {
                 wasmtime_wasi_threads::add_to_linker(linker, store, &module, |host| {
                     host.wasi_threads.as_ref().unwrap()
                 })?;
-                store.data_mut().wasi_threads = Some(Arc::new(WasiThreadsCtx::new(
-                    module.clone(),
-                    Arc::new(linker.clone()),
-                )?));
+                let mut ctx = WasiThreadsCtx::new(module.clone(), Arc::new(linker.clone()))?
+                    .exit_on_error(false);
+                if let Some(max) = self.run.common.wasi.max_threads {
+                    ctx = ctx.max_threads(max);
+                }
+                store.data_mut().wasi_threads = Some(Arc::new(ctx));
             }
         }
 
@@ -703,7 +835,42 @@ impl RunCommand {
                     }
                 }
 
-                store.data_mut().wasi_http = Some(Arc::new(WasiHttpCtx {}));
+                store.data_mut().wasi_http = Some(Arc::new(WasiHttpCtx::new()));
+            }
+        }
+
+        if self.run.common.wasi.logging == Some(true) {
+            if self.run.common.wasi.common == Some(false) {
+                bail!("Cannot enable wasi-logging without the common WASI APIs");
+            }
+            match linker {
+                CliLinker::Core(_) => {
+                    bail!("Cannot enable wasi-logging for core wasm modules");
+                }
+                #[cfg(feature = "component-model")]
+                CliLinker::Component(linker) => {
+                    preview2::bindings::logging::logging::add_to_linker(linker, |t| t)?;
+                }
+            }
+        }
+
+        if self.run.common.wasi.keyvalue == Some(true) {
+            #[cfg(not(all(feature = "wasi-keyvalue", feature = "component-model")))]
+            {
+                bail!("Cannot enable wasi-keyvalue when the binary is not compiled with this feature.");
+            }
+            #[cfg(all(feature = "wasi-keyvalue", feature = "component-model"))]
+            {
+                match linker {
+                    CliLinker::Core(_) => {
+                        bail!("Cannot enable wasi-keyvalue for core wasm modules");
+                    }
+                    CliLinker::Component(linker) => {
+                        wasmtime_wasi_keyvalue::add_to_linker(linker)?;
+                    }
+                }
+
+                store.data_mut().wasi_keyvalue = Some(Arc::new(WasiKeyValueCtx::default()));
             }
         }
 
@@ -755,6 +922,13 @@ impl RunCommand {
             builder.env(key, &value);
         }
 
+        self.run.runtime_config()?.apply(&mut builder);
+        builder.logging_span(tracing::info_span!(
+            target: "wasi_logging",
+            "run",
+            module = %Path::new(&self.module_and_args[0]).display()
+        ));
+
         if self.run.common.wasi.listenfd == Some(true) {
             bail!("components do not support --listenfd");
         }
@@ -789,8 +963,35 @@ impl RunCommand {
     }
 }
 
+/// Parses an argument of an invoked function from the command line.
+pub(crate) fn parse_val(ty: &ValType, val: &str) -> Result<Val> {
+    Ok(match ty {
+        // TODO: integer parsing here should handle hexadecimal notation
+        // like `0x0...`, but the Rust standard library currently only
+        // parses base-10 representations.
+        ValType::I32 => Val::I32(val.parse()?),
+        ValType::I64 => Val::I64(val.parse()?),
+        ValType::F32 => Val::F32(val.parse::<f32>()?.to_bits()),
+        ValType::F64 => Val::F64(val.parse::<f64>()?.to_bits()),
+        t => bail!("unsupported argument type {:?}", t),
+    })
+}
+
+/// Formats a result of an invoked function for the command line.
+pub(crate) fn display_val(val: &Val) -> String {
+    match val {
+        Val::I32(i) => i.to_string(),
+        Val::I64(i) => i.to_string(),
+        Val::F32(f) => f32::from_bits(*f).to_string(),
+        Val::F64(f) => f64::from_bits(*f).to_string(),
+        Val::ExternRef(_) => "<externref>".to_string(),
+        Val::FuncRef(_) => "<funcref>".to_string(),
+        Val::V128(i) => i.as_u128().to_string(),
+    }
+}
+
 #[derive(Default, Clone)]
-struct Host {
+pub(crate) struct Host {
     preview1_ctx: Option<wasmtime_wasi::WasiCtx>,
     preview2_ctx: Option<Arc<preview2::WasiCtx>>,
 
@@ -809,7 +1010,9 @@ struct Host {
     wasi_threads: Option<Arc<WasiThreadsCtx<Host>>>,
     #[cfg(feature = "wasi-http")]
     wasi_http: Option<Arc<WasiHttpCtx>>,
-    limits: StoreLimits,
+    #[cfg(feature = "wasi-keyvalue")]
+    wasi_keyvalue: Option<Arc<WasiKeyValueCtx>>,
+    limits: PeakMemoryLimits,
     #[cfg(feature = "profiling")]
     guest_profiler: Option<Arc<wasmtime::GuestProfiler>>,
 }
@@ -855,6 +1058,30 @@ impl wasmtime_wasi_http::types::WasiHttpView for Host {
     }
 }
 
+#[cfg(feature = "wasi-nn")]
+impl wasmtime_wasi_nn::wit::WasiNnView for Host {
+    fn ctx(&mut self) -> &mut WasiNnCtx {
+        let ctx = self.wasi_nn.as_mut().unwrap();
+        Arc::get_mut(ctx).expect("wasi-nn is not implemented with multi-threading support")
+    }
+
+    fn table(&mut self) -> &mut wasmtime::component::ResourceTable {
+        Arc::get_mut(&mut self.preview2_table).expect("preview2 is not compatible with threads")
+    }
+}
+
+#[cfg(feature = "wasi-keyvalue")]
+impl wasmtime_wasi_keyvalue::WasiKeyValueView for Host {
+    fn ctx(&mut self) -> &mut WasiKeyValueCtx {
+        let ctx = self.wasi_keyvalue.as_mut().unwrap();
+        Arc::get_mut(ctx).expect("wasi-keyvalue is not compatible with threads")
+    }
+
+    fn table(&mut self) -> &mut wasmtime::component::ResourceTable {
+        Arc::get_mut(&mut self.preview2_table).expect("preview2 is not compatible with threads")
+    }
+}
+
 #[cfg(not(unix))]
 fn ctx_set_listenfd(num_fd: usize, _builder: &mut WasiCtxBuilder) -> Result<usize> {
     Ok(num_fd)
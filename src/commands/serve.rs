@@ -1,35 +1,53 @@
-use crate::common::{Profile, RunCommon, RunTarget};
-use anyhow::{anyhow, bail, Result};
+use crate::common::{Profile, RunCommon, RunTarget, RuntimeConfig};
+use crate::manifest;
+use anyhow::{anyhow, bail, Context, Result};
 use clap::Parser;
 use std::{
     path::PathBuf,
     pin::Pin,
     sync::{
         atomic::{AtomicBool, AtomicU64, Ordering},
-        Arc,
+        Arc, Mutex,
     },
+    time::Instant,
 };
-use wasmtime::component::{InstancePre, Linker};
+use wasmtime::component::{InstancePre, Linker, TypedFunc};
 use wasmtime::{Engine, Store, StoreLimits};
 use wasmtime_wasi::preview2::{self, StreamError, StreamResult, WasiCtx, WasiCtxBuilder, WasiView};
 use wasmtime_wasi_http::io::TokioIo;
+use wasmtime_wasi_http::proxy::Proxy;
 use wasmtime_wasi_http::{
-    bindings::http::types as http_types, body::HyperOutgoingBody, hyper_response_error,
+    bindings::http::types as http_types, body::HyperOutgoingBody, hyper_response_error, HttpClient,
     WasiHttpCtx, WasiHttpView,
 };
 
 #[cfg(feature = "wasi-nn")]
-use wasmtime_wasi_nn::WasiNnCtx;
+use wasmtime_wasi_nn::{wit::WasiNnView, WasiNnCtx};
+
+mod metrics;
+
+#[cfg(feature = "wasi-keyvalue")]
+use wasmtime_wasi_keyvalue::{InMemoryStore, KeyValueStore, WasiKeyValueCtx, WasiKeyValueView};
 
 struct Host {
     table: wasmtime::component::ResourceTable,
     ctx: WasiCtx,
     http: WasiHttpCtx,
 
+    /// The request currently handled by the store, which is updated when the
+    /// instance is reused for another request.
+    req_id: Arc<AtomicU64>,
+
     limits: StoreLimits,
 
+    #[cfg(feature = "profiling")]
+    guest_profiler: Option<wasmtime::GuestProfiler>,
+
     #[cfg(feature = "wasi-nn")]
     nn: Option<WasiNnCtx>,
+
+    #[cfg(feature = "wasi-keyvalue")]
+    keyvalue: Option<WasiKeyValueCtx>,
 }
 
 impl WasiView for Host {
@@ -60,6 +78,28 @@ impl WasiHttpView for Host {
     }
 }
 
+#[cfg(feature = "wasi-nn")]
+impl WasiNnView for Host {
+    fn table(&mut self) -> &mut wasmtime::component::ResourceTable {
+        &mut self.table
+    }
+
+    fn ctx(&mut self) -> &mut WasiNnCtx {
+        self.nn.as_mut().unwrap()
+    }
+}
+
+#[cfg(feature = "wasi-keyvalue")]
+impl WasiKeyValueView for Host {
+    fn table(&mut self) -> &mut wasmtime::component::ResourceTable {
+        &mut self.table
+    }
+
+    fn ctx(&mut self) -> &mut WasiKeyValueCtx {
+        self.keyvalue.as_mut().unwrap()
+    }
+}
+
 const DEFAULT_ADDR: std::net::SocketAddr = std::net::SocketAddr::new(
     std::net::IpAddr::V4(std::net::Ipv4Addr::new(0, 0, 0, 0)),
     8080,
@@ -75,14 +115,82 @@ pub struct ServeCommand {
     #[arg(long = "addr", value_name = "SOCKADDR", default_value_t = DEFAULT_ADDR )]
     addr: std::net::SocketAddr,
 
+    /// Path to a PEM-encoded certificate chain to serve HTTPS with.
+    ///
+    /// When this and `--tls-key` are specified connections are accepted over
+    /// TLS, with HTTP/2 or HTTP/1.1 negotiated through ALPN.
+    #[arg(long = "tls-cert", value_name = "PATH", requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    /// Path to the PEM-encoded private key for `--tls-cert`.
+    #[arg(long = "tls-key", value_name = "PATH", requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// Reuse each instance of the component for up to this many requests.
+    ///
+    /// By default every request is handled by a fresh instance. Reusing
+    /// instances avoids the cost of instantiation for components with heavy
+    /// startup, at the cost of state persisting between requests: each request
+    /// gets its own environment, output prefixes and fuel and timeout budgets,
+    /// but linear memory and the resources the guest holds on to, such as
+    /// streams, are kept from one request to the next. An instance is never
+    /// reused after it traps, fails to handle a request or holds on to the
+    /// request or its response outparam.
+    #[arg(long = "instance-reuse", value_name = "N", default_value_t = 1)]
+    instance_reuse: u64,
+
+    /// The maximum number of idle instances kept around for reuse.
+    #[arg(long = "max-idle-instances", value_name = "N", default_value_t = 16)]
+    max_idle_instances: usize,
+
+    /// Name of a function exported by the component which is called before an
+    /// instance is reused.
+    ///
+    /// The function must take no parameters and return nothing. It can be
+    /// used to reset or verify the instance's state; if it traps the instance
+    /// is discarded instead of being reused.
+    #[arg(long = "instance-reset", value_name = "EXPORT")]
+    instance_reset: Option<String>,
+
+    /// How long to wait for in-flight requests to finish when shutting down.
+    ///
+    /// On Ctrl-C or SIGTERM the server stops accepting connections and waits
+    /// up to this long for requests already being handled before exiting.
+    #[arg(
+        long = "shutdown-timeout",
+        value_name = "DURATION",
+        default_value = "30s",
+        value_parser = humantime::parse_duration,
+    )]
+    shutdown_timeout: std::time::Duration,
+
+    /// The maximum number of requests handled concurrently.
+    ///
+    /// Requests received while this many are already in flight are rejected
+    /// with a `503 Service Unavailable` response. The resources of each
+    /// request are capped by `-W max-memory-size`, `-W fuel` and `-W timeout`,
+    /// which apply to every request separately, even with `--instance-reuse`.
+    #[arg(long = "max-concurrent-requests", value_name = "N")]
+    max_concurrent_requests: Option<usize>,
+
+    /// Socket address to serve Prometheus metrics on, at `/metrics`.
+    #[arg(long = "metrics-addr", value_name = "SOCKADDR")]
+    metrics_addr: Option<std::net::SocketAddr>,
+
     /// The WebAssembly component to run.
-    #[arg(value_name = "WASM", required = true)]
-    component: PathBuf,
+    #[arg(value_name = "WASM", required_unless_present = "manifest")]
+    component: Option<PathBuf>,
 }
 
 impl ServeCommand {
     /// Start a server to run the given wasi-http proxy component
     pub fn execute(mut self) -> Result<()> {
+        if let Some(path) = self.run.manifest.take() {
+            let component = self.component.take().into_iter().map(Into::into).collect();
+            let manifest: ServeCommand = manifest::parse("serve", &path, component)?;
+            self.run.merge_defaults(manifest.run);
+            self.component = manifest.component;
+        }
         self.run.common.init_logging()?;
 
         // We force cli errors before starting to listen for connections so tha we don't
@@ -95,7 +203,10 @@ impl ServeCommand {
         }
 
         if let Some(Profile::Guest { .. }) = &self.run.profile {
-            bail!("Cannot use the guest profiler with components");
+            #[cfg(not(feature = "profiling"))]
+            {
+                bail!("support for profiling disabled at compile time");
+            }
         }
 
         if self.run.common.wasi.nn == Some(true) {
@@ -105,10 +216,21 @@ impl ServeCommand {
             }
         }
 
+        if self.run.common.wasi.keyvalue == Some(true) {
+            #[cfg(not(feature = "wasi-keyvalue"))]
+            {
+                bail!("Cannot enable wasi-keyvalue when the binary is not compiled with this feature.");
+            }
+        }
+
         if self.run.common.wasi.threads == Some(true) {
             bail!("wasi-threads does not support components yet")
         }
 
+        if self.instance_reuse == 0 {
+            bail!("`--instance-reuse` must be at least 1");
+        }
+
         // The serve command requires both wasi-http and the component model, so we enable those by
         // default here.
         if self.run.common.wasi.http.replace(true) == Some(false) {
@@ -123,69 +245,42 @@ impl ServeCommand {
             .enable_io()
             .build()?;
 
-        runtime.block_on(async move {
-            tokio::select! {
-                _ = tokio::signal::ctrl_c() => {
-                    Ok::<_, anyhow::Error>(())
-                }
+        let result = runtime.block_on(self.serve());
 
-                res = self.serve() => {
-                    res
-                }
-            }
-        })?;
-
-        Ok(())
+        // Guests still running past the shutdown timeout can't be cancelled,
+        // so don't wait for them.
+        runtime.shutdown_background();
+        result
     }
 
-    fn new_store(&self, engine: &Engine, req_id: u64) -> Result<Store<Host>> {
-        let mut builder = WasiCtxBuilder::new();
-
-        builder.envs(&[("REQUEST_ID", req_id.to_string())]);
-
-        builder.stdout(LogStream {
-            prefix: format!("stdout [{req_id}] :: "),
-            output: Output::Stdout,
-        });
-
-        builder.stderr(LogStream {
-            prefix: format!("stderr [{req_id}] :: "),
-            output: Output::Stderr,
-        });
-
-        let mut host = Host {
+    fn new_store(
+        &self,
+        engine: &Engine,
+        req_id: u64,
+        runtime_config: &RuntimeConfig,
+        http: WasiHttpCtx,
+    ) -> Result<Store<Host>> {
+        let req_id = Arc::new(AtomicU64::new(req_id));
+        let host = Host {
             table: wasmtime::component::ResourceTable::new(),
-            ctx: builder.build(),
-            http: WasiHttpCtx,
+            ctx: self.new_wasi_ctx(&req_id, runtime_config),
+            http,
+            req_id,
 
             limits: StoreLimits::default(),
 
+            #[cfg(feature = "profiling")]
+            guest_profiler: None,
+
             #[cfg(feature = "wasi-nn")]
             nn: None,
-        };
 
-        if self.run.common.wasi.nn == Some(true) {
-            #[cfg(feature = "wasi-nn")]
-            {
-                let graphs = self
-                    .run
-                    .common
-                    .wasi
-                    .nn_graph
-                    .iter()
-                    .map(|g| (g.format.clone(), g.dir.clone()))
-                    .collect::<Vec<_>>();
-                let (backends, registry) = wasmtime_wasi_nn::preload(&graphs)?;
-                host.nn.replace(WasiNnCtx::new(backends, registry));
-            }
-        }
+            #[cfg(feature = "wasi-keyvalue")]
+            keyvalue: None,
+        };
 
         let mut store = Store::new(engine, host);
 
-        if self.run.common.wasm.timeout.is_some() {
-            store.set_epoch_deadline(1);
-        }
-
         store.data_mut().limits = self.run.store_limits();
         store.limiter(|t| &mut t.limits);
 
@@ -198,6 +293,34 @@ impl ServeCommand {
         Ok(store)
     }
 
+    /// Creates the WASI context for handling the request `req_id`, whose
+    /// output is prefixed with the store's current request.
+    fn new_wasi_ctx(&self, req_id: &Arc<AtomicU64>, runtime_config: &RuntimeConfig) -> WasiCtx {
+        let mut builder = WasiCtxBuilder::new();
+        let stream_req_id = req_id.clone();
+        let req_id = req_id.load(Ordering::Relaxed);
+
+        builder.envs(&[("REQUEST_ID", req_id.to_string())]);
+        runtime_config.apply(&mut builder);
+        builder.logging_span(tracing::info_span!(
+            target: "wasi_logging",
+            "request",
+            id = req_id
+        ));
+
+        builder.stdout(LogStream {
+            req_id: stream_req_id.clone(),
+            output: Output::Stdout,
+        });
+
+        builder.stderr(LogStream {
+            req_id: stream_req_id,
+            output: Output::Stderr,
+        });
+
+        builder.build()
+    }
+
     fn add_to_linker(&self, linker: &mut Linker<Host>) -> Result<()> {
         // Repurpose the `-Scommon` flag of `wasmtime run` for `wasmtime serve`
         // to serve as a signal to enable all WASI interfaces instead of just
@@ -214,6 +337,13 @@ impl ServeCommand {
         } else {
             wasmtime_wasi_http::proxy::add_to_linker(linker)?;
         }
+        if self.run.runtime_config_enabled() {
+            preview2::bindings::config::runtime::add_to_linker(linker, |t| t)?;
+        }
+
+        if self.run.common.wasi.logging == Some(true) {
+            preview2::bindings::logging::logging::add_to_linker(linker, |t| t)?;
+        }
 
         if self.run.common.wasi.nn == Some(true) {
             #[cfg(not(feature = "wasi-nn"))]
@@ -222,7 +352,18 @@ impl ServeCommand {
             }
             #[cfg(feature = "wasi-nn")]
             {
-                wasmtime_wasi_nn::wit::ML::add_to_linker(linker, |host| host.nn.as_mut().unwrap())?;
+                wasmtime_wasi_nn::wit::add_to_linker(linker)?;
+            }
+        }
+
+        if self.run.common.wasi.keyvalue == Some(true) {
+            #[cfg(not(feature = "wasi-keyvalue"))]
+            {
+                bail!("support for wasi-keyvalue was disabled at compile time");
+            }
+            #[cfg(feature = "wasi-keyvalue")]
+            {
+                wasmtime_wasi_keyvalue::add_to_linker(linker)?;
             }
         }
 
@@ -238,8 +379,6 @@ impl ServeCommand {
     }
 
     async fn serve(mut self) -> Result<()> {
-        use hyper::server::conn::http1;
-
         let mut config = self.run.common.config(None)?;
         config.wasm_component_model(true);
         config.async_support(true);
@@ -253,8 +392,11 @@ impl ServeCommand {
                 config.profiler(s);
             }
 
-            // We bail early in `execute` if the guest profiler is configured.
-            Some(Profile::Guest { .. }) => unreachable!(),
+            // Profiles are collected for each request, see
+            // `ProxyHandlerInner::setup_epoch_handler`.
+            Some(Profile::Guest { .. }) => {
+                config.epoch_interruption(true);
+            }
 
             None => {}
         }
@@ -264,18 +406,41 @@ impl ServeCommand {
 
         self.add_to_linker(&mut linker)?;
 
-        let component = match self.run.load_module(&engine, &self.component)? {
+        let path = self
+            .component
+            .as_deref()
+            .context("no component to serve was provided")?;
+        let component = match self.run.load_module(&engine, path)? {
             RunTarget::Core(_) => bail!("The serve command currently requires a component"),
             RunTarget::Component(c) => c,
         };
 
         let instance = linker.instantiate_pre(&component)?;
 
+        // Read the runtime configuration once up front so errors are reported
+        // before listening for connections.
+        let runtime_config = self.run.runtime_config()?;
+
+        #[cfg(feature = "wasi-nn")]
+        let nn = if self.run.common.wasi.nn == Some(true) {
+            Some(self.run.wasi_nn_ctx()?)
+        } else {
+            None
+        };
+
+        let tls = match (&self.tls_cert, &self.tls_key) {
+            (Some(cert), Some(key)) => Some(tls::acceptor(cert, key)?),
+            _ => None,
+        };
+
         let listener = tokio::net::TcpListener::bind(self.addr).await?;
 
-        eprintln!("Serving HTTP on http://{}/", listener.local_addr()?);
+        let scheme = if tls.is_some() { "https" } else { "http" };
+        eprintln!("Serving HTTP on {scheme}://{}/", listener.local_addr()?);
 
-        let _epoch_thread = if let Some(timeout) = self.run.common.wasm.timeout {
+        let _epoch_thread = if let Some(Profile::Guest { interval, .. }) = &self.run.profile {
+            Some(EpochThread::spawn(*interval, engine.clone()))
+        } else if let Some(timeout) = self.run.common.wasm.timeout {
             Some(EpochThread::spawn(timeout, engine.clone()))
         } else {
             None
@@ -283,22 +448,284 @@ impl ServeCommand {
 
         log::info!("Listening on {}", self.addr);
 
-        let handler = ProxyHandler::new(self, engine, instance);
+        // Each connection and each request task holds a clone of `draining`
+        // until it's done, so once all of them have been dropped `drained`
+        // completes.
+        let (shutdown, _) = tokio::sync::broadcast::channel::<()>(1);
+        let (draining, mut drained) = tokio::sync::mpsc::channel::<()>(1);
+
+        let shutdown_timeout = self.shutdown_timeout;
+        let metrics_addr = self.metrics_addr;
+        let handler = ProxyHandler::new(
+            self,
+            engine,
+            instance,
+            runtime_config,
+            #[cfg(feature = "wasi-nn")]
+            nn,
+            draining.clone(),
+        );
+
+        if let Some(addr) = metrics_addr {
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            eprintln!(
+                "Serving metrics on http://{}/metrics",
+                listener.local_addr()?
+            );
+            tokio::task::spawn(serve_metrics(listener, handler.0.metrics.clone()));
+        }
+
+        let signal = shutdown_signal();
+        tokio::pin!(signal);
 
         loop {
-            let (stream, _) = listener.accept().await?;
-            let stream = TokioIo::new(stream);
+            let stream = tokio::select! {
+                res = listener.accept() => res?.0,
+                res = &mut signal => {
+                    res?;
+                    break;
+                }
+            };
             let h = handler.clone();
+            let tls = tls.clone();
+            let shutdown = shutdown.subscribe();
+            let draining = draining.clone();
             tokio::task::spawn(async move {
-                if let Err(e) = http1::Builder::new()
-                    .keep_alive(true)
-                    .serve_connection(stream, h)
-                    .await
-                {
+                if let Err(e) = serve_connection(stream, tls, h, shutdown).await {
                     eprintln!("error: {e:?}");
                 }
+                drop(draining);
             });
         }
+
+        drop(listener);
+        eprintln!("Shutting down, waiting up to {shutdown_timeout:?} for in-flight requests");
+        let _ = shutdown.send(());
+        drop(draining);
+        drop(handler);
+        if tokio::time::timeout(shutdown_timeout, drained.recv())
+            .await
+            .is_err()
+        {
+            eprintln!("Timed out waiting for in-flight requests to finish");
+        }
+
+        Ok(())
+    }
+}
+
+/// Completes once the process is asked to shut down, either through Ctrl-C
+/// or, on Unix, SIGTERM.
+async fn shutdown_signal() -> Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut term = signal(SignalKind::terminate())?;
+        tokio::select! {
+            res = tokio::signal::ctrl_c() => res?,
+            _ = term.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        tokio::signal::ctrl_c().await?;
+    }
+    Ok(())
+}
+
+/// Serves the Prometheus metrics endpoint on `listener`.
+async fn serve_metrics(listener: tokio::net::TcpListener, metrics: Arc<metrics::Metrics>) {
+    use http_body_util::Full;
+    use hyper::body::Bytes;
+
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                // Errors such as running out of file descriptors persist for
+                // a while, so back off instead of retrying in a busy loop.
+                eprintln!("error: failed to accept metrics connection: {e}");
+                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+        let metrics = metrics.clone();
+        let service = hyper::service::service_fn(move |req: Request| {
+            let resp = if req.uri().path() == "/metrics" {
+                hyper::Response::builder()
+                    .header(
+                        hyper::header::CONTENT_TYPE,
+                        "text/plain; version=0.0.4; charset=utf-8",
+                    )
+                    .body(Full::new(Bytes::from(metrics.render())))
+            } else {
+                hyper::Response::builder()
+                    .status(hyper::StatusCode::NOT_FOUND)
+                    .body(Full::new(Bytes::new()))
+            };
+            async move { resp }
+        });
+        tokio::task::spawn(async move {
+            if let Err(e) = hyper::server::conn::http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                eprintln!("error: {e:?}");
+            }
+        });
+    }
+}
+
+/// Serves all requests made on a single accepted connection.
+///
+/// Plaintext connections speak HTTP/1.1 while TLS connections use whichever
+/// protocol was negotiated through ALPN. Once `shutdown` fires the connection
+/// stops accepting new requests and completes after in-flight ones finish.
+async fn serve_connection(
+    stream: tokio::net::TcpStream,
+    tls: Option<tls::TlsAcceptor>,
+    handler: ProxyHandler,
+    shutdown: tokio::sync::broadcast::Receiver<()>,
+) -> Result<()> {
+    use hyper::server::conn::{http1, http2};
+
+    let tls = match tls {
+        Some(tls) => tls,
+        None => {
+            let conn = http1::Builder::new()
+                .keep_alive(true)
+                .serve_connection(TokioIo::new(stream), handler);
+            return drive_connection(conn, shutdown, |c| c.graceful_shutdown()).await;
+        }
+    };
+
+    let stream = tls::accept(&tls, stream).await?;
+    if tls::negotiated_h2(&stream) {
+        let conn =
+            http2::Builder::new(TokioExecutor).serve_connection(TokioIo::new(stream), handler);
+        drive_connection(conn, shutdown, |c| c.graceful_shutdown()).await
+    } else {
+        let conn = http1::Builder::new()
+            .keep_alive(true)
+            .serve_connection(TokioIo::new(stream), handler);
+        drive_connection(conn, shutdown, |c| c.graceful_shutdown()).await
+    }
+}
+
+/// Runs `conn` to completion, starting a graceful shutdown of it through
+/// `graceful_shutdown` once `shutdown` fires.
+async fn drive_connection<C>(
+    conn: C,
+    mut shutdown: tokio::sync::broadcast::Receiver<()>,
+    graceful_shutdown: fn(Pin<&mut C>),
+) -> Result<()>
+where
+    C: std::future::Future<Output = hyper::Result<()>>,
+{
+    tokio::pin!(conn);
+    tokio::select! {
+        res = conn.as_mut() => return Ok(res?),
+        _ = shutdown.recv() => {}
+    }
+    graceful_shutdown(conn.as_mut());
+    conn.await?;
+    Ok(())
+}
+
+/// An executor for the background tasks spawned by hyper's HTTP/2
+/// connections.
+#[derive(Clone, Copy)]
+struct TokioExecutor;
+
+impl<F> hyper::rt::Executor<F> for TokioExecutor
+where
+    F: std::future::Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    fn execute(&self, fut: F) {
+        tokio::task::spawn(fut);
+    }
+}
+
+// The `ring` crate, used to implement TLS, does not build on riscv64 or s390x
+#[cfg(not(any(target_arch = "riscv64", target_arch = "s390x")))]
+mod tls {
+    use anyhow::{bail, Context, Result};
+    use std::path::Path;
+    use std::sync::Arc;
+    use tokio::net::TcpStream;
+    use tokio_rustls::server::TlsStream;
+
+    pub use tokio_rustls::TlsAcceptor;
+
+    /// Builds the TLS configuration for the server from PEM files on disk.
+    pub fn acceptor(cert: &Path, key: &Path) -> Result<TlsAcceptor> {
+        let certs = read_pem(cert)?
+            .into_iter()
+            .filter_map(|item| match item {
+                rustls_pemfile::Item::X509Certificate(der) => Some(rustls::Certificate(der)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if certs.is_empty() {
+            bail!("no certificates found in `{}`", cert.display());
+        }
+
+        let key = read_pem(key)?
+            .into_iter()
+            .find_map(|item| match item {
+                rustls_pemfile::Item::RSAKey(der)
+                | rustls_pemfile::Item::PKCS8Key(der)
+                | rustls_pemfile::Item::ECKey(der) => Some(rustls::PrivateKey(der)),
+                _ => None,
+            })
+            .with_context(|| format!("no private key found in `{}`", key.display()))?;
+
+        let mut config = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .context("invalid TLS certificate or key")?;
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+
+    fn read_pem(path: &Path) -> Result<Vec<rustls_pemfile::Item>> {
+        let file = std::fs::File::open(path)
+            .with_context(|| format!("failed to open `{}`", path.display()))?;
+        rustls_pemfile::read_all(&mut std::io::BufReader::new(file))
+            .with_context(|| format!("failed to parse `{}`", path.display()))
+    }
+
+    pub async fn accept(tls: &TlsAcceptor, stream: TcpStream) -> Result<TlsStream<TcpStream>> {
+        Ok(tls.accept(stream).await?)
+    }
+
+    pub fn negotiated_h2(stream: &TlsStream<TcpStream>) -> bool {
+        stream.get_ref().1.alpn_protocol() == Some(b"h2")
+    }
+}
+
+#[cfg(any(target_arch = "riscv64", target_arch = "s390x"))]
+mod tls {
+    use anyhow::{bail, Result};
+    use std::path::Path;
+    use tokio::net::TcpStream;
+
+    #[derive(Clone)]
+    pub enum TlsAcceptor {}
+
+    pub fn acceptor(_cert: &Path, _key: &Path) -> Result<TlsAcceptor> {
+        bail!("TLS is not supported on this platform")
+    }
+
+    pub async fn accept(tls: &TlsAcceptor, _stream: TcpStream) -> Result<TcpStream> {
+        match *tls {}
+    }
+
+    pub fn negotiated_h2(_stream: &TcpStream) -> bool {
+        false
     }
 }
 
@@ -339,24 +766,260 @@ struct ProxyHandlerInner {
     engine: Engine,
     instance_pre: InstancePre<Host>,
     next_id: AtomicU64,
+    runtime_config: RuntimeConfig,
+
+    /// Client shared by all requests so outgoing connections can be reused.
+    http_client: HttpClient,
+
+    /// Key-value data shared by all requests when `-Skeyvalue` is enabled.
+    #[cfg(feature = "wasi-keyvalue")]
+    keyvalue: Arc<dyn KeyValueStore>,
+
+    /// The wasi-nn context, whose backends and models are shared by all
+    /// requests, cloned into each store when `-Snn` is enabled.
+    #[cfg(feature = "wasi-nn")]
+    nn: Option<WasiNnCtx>,
+
+    /// Idle instances available for reuse when `--instance-reuse` is greater
+    /// than one.
+    idle: Mutex<Vec<ProxyInstance>>,
+
+    /// Limits the number of concurrent requests when
+    /// `--max-concurrent-requests` is specified.
+    permits: Option<Arc<tokio::sync::Semaphore>>,
+
+    metrics: Arc<metrics::Metrics>,
+
+    /// Cloned by each request task until it's done, so that shutting down
+    /// waits for requests whose responses were already sent but whose guest
+    /// is still running, e.g. to stream the body.
+    draining: tokio::sync::mpsc::Sender<()>,
+}
+
+/// An instance of the proxy component along with its store.
+struct ProxyInstance {
+    store: Store<Host>,
+    proxy: Proxy,
+    reset: Option<TypedFunc<(), ()>>,
+    uses: u64,
 }
 
 impl ProxyHandlerInner {
     fn next_req_id(&self) -> u64 {
         self.next_id.fetch_add(1, Ordering::Relaxed)
     }
+
+    fn new_store(&self, req_id: u64) -> Result<Store<Host>> {
+        let mut store = self.cmd.new_store(
+            &self.engine,
+            req_id,
+            &self.runtime_config,
+            WasiHttpCtx::with_client(self.http_client.clone()),
+        )?;
+
+        #[cfg(feature = "wasi-keyvalue")]
+        if self.cmd.run.common.wasi.keyvalue == Some(true) {
+            store.data_mut().keyvalue = Some(WasiKeyValueCtx::new(self.keyvalue.clone()));
+        }
+
+        #[cfg(feature = "wasi-nn")]
+        {
+            store.data_mut().nn = self.nn.clone();
+        }
+
+        Ok(store)
+    }
+
+    /// Returns an instance to handle the request `req_id`, reusing an idle
+    /// one if available.
+    async fn instance(&self, req_id: u64) -> Result<ProxyInstance> {
+        let idle = self.idle.lock().unwrap().pop();
+        if let Some(mut instance) = idle {
+            // Give the request its own environment and output prefixes, also
+            // used by the streams the guest kept from previous requests, and
+            // reset the per-request budgets.
+            let store = &mut instance.store;
+            let host = store.data_mut();
+            host.req_id.store(req_id, Ordering::Relaxed);
+            host.ctx = self.cmd.new_wasi_ctx(&host.req_id, &self.runtime_config);
+            host.limits = self.cmd.run.store_limits();
+            self.setup_epoch_handler(store);
+            if let Some(fuel) = self.cmd.run.common.wasm.fuel {
+                store.set_fuel(fuel)?;
+            }
+            return Ok(instance);
+        }
+
+        let start = Instant::now();
+        let mut store = self.new_store(req_id)?;
+        self.setup_epoch_handler(&mut store);
+        let (proxy, instance) = Proxy::instantiate_pre(&mut store, &self.instance_pre).await?;
+        self.metrics.instantiation_duration.observe(start.elapsed());
+        let reset = match &self.cmd.instance_reset {
+            Some(name) => Some(
+                instance
+                    .get_typed_func::<(), ()>(&mut store, name)
+                    .with_context(|| format!("failed to find reset export `{name}`"))?,
+            ),
+            None => None,
+        };
+
+        Ok(ProxyInstance {
+            store,
+            proxy,
+            reset,
+            uses: 0,
+        })
+    }
+
+    /// Arms the epoch deadline of `store` for a new request, starting a new
+    /// guest profile for it if the guest profiler is enabled.
+    fn setup_epoch_handler(&self, store: &mut Store<Host>) {
+        #[cfg(feature = "profiling")]
+        if let Some(Profile::Guest { interval, .. }) = &self.cmd.run.profile {
+            use wasmtime::{GuestProfiler, UpdateDeadline};
+
+            let name = self
+                .cmd
+                .component
+                .as_deref()
+                .and_then(|p| p.to_str())
+                .unwrap_or("<component>");
+            let component = self.instance_pre.component().clone();
+            store.data_mut().guest_profiler =
+                Some(GuestProfiler::new_component(name, *interval, component, []));
+
+            // Epochs tick at the sampling interval, so the timeout is
+            // counted in ticks.
+            let mut ticks = self
+                .cmd
+                .run
+                .common
+                .wasm
+                .timeout
+                .map(|timeout| (timeout.as_secs_f64() / interval.as_secs_f64()).ceil() as u64);
+            store.epoch_deadline_callback(move |mut store| {
+                let mut profiler = store.data_mut().guest_profiler.take();
+                if let Some(profiler) = &mut profiler {
+                    profiler.sample(&store);
+                }
+                store.data_mut().guest_profiler = profiler;
+
+                if let Some(ticks) = &mut ticks {
+                    *ticks = ticks.saturating_sub(1);
+                    if *ticks == 0 {
+                        bail!("timeout exceeded");
+                    }
+                }
+                Ok(UpdateDeadline::Continue(1))
+            });
+            store.set_epoch_deadline(1);
+            return;
+        }
+
+        if self.cmd.run.common.wasm.timeout.is_some() {
+            store.set_epoch_deadline(1);
+        }
+    }
+
+    /// Writes out the guest profile collected while handling `req_id`, if
+    /// any.
+    fn finish_profile(&self, store: &mut Store<Host>, req_id: u64) {
+        #[cfg(feature = "profiling")]
+        if let Some(Profile::Guest { path, .. }) = &self.cmd.run.profile {
+            let profiler = match store.data_mut().guest_profiler.take() {
+                Some(profiler) => profiler,
+                None => return,
+            };
+
+            // Each request gets its own profile, named after the request.
+            let path = std::path::Path::new(path);
+            let mut name = path.file_stem().unwrap_or_default().to_os_string();
+            name.push(format!("-{req_id}"));
+            if let Some(ext) = path.extension() {
+                name.push(".");
+                name.push(ext);
+            }
+            let path = path.with_file_name(name);
+
+            match std::fs::File::create(&path)
+                .map_err(anyhow::Error::new)
+                .and_then(|output| profiler.finish(std::io::BufWriter::new(output)))
+            {
+                Ok(()) => log::info!("[{req_id}] :: profile written to {}", path.display()),
+                Err(e) => eprintln!("failed writing profile at {}: {e:#}", path.display()),
+            }
+        }
+        #[cfg(not(feature = "profiling"))]
+        let _ = (store, req_id);
+    }
+
+    /// Makes `instance`, which has successfully handled a request, available
+    /// to future requests if it may still be reused.
+    ///
+    /// `leaked` is whether the guest still holds on to the request or its
+    /// response outparam.
+    async fn recycle(&self, mut instance: ProxyInstance, req_id: u64, leaked: bool) {
+        instance.uses += 1;
+        if instance.uses >= self.cmd.instance_reuse {
+            return;
+        }
+
+        // A guest which didn't drop the request's resources may hold on to
+        // them forever, which for the outparam would leave the client waiting
+        // for a response. Dropping the store is what releases them.
+        if leaked {
+            log::warn!("[{req_id}] :: discarding instance which leaked request resources");
+            return;
+        }
+
+        if let Some(reset) = instance.reset {
+            let result = match reset.call_async(&mut instance.store, ()).await {
+                Ok(()) => reset.post_return_async(&mut instance.store).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                log::warn!("[{req_id}] :: discarding instance after failed reset: {e:?}");
+                return;
+            }
+        }
+
+        let mut idle = self.idle.lock().unwrap();
+        if idle.len() < self.cmd.max_idle_instances {
+            idle.push(instance);
+        }
+    }
 }
 
 #[derive(Clone)]
 struct ProxyHandler(Arc<ProxyHandlerInner>);
 
 impl ProxyHandler {
-    fn new(cmd: ServeCommand, engine: Engine, instance_pre: InstancePre<Host>) -> Self {
+    fn new(
+        cmd: ServeCommand,
+        engine: Engine,
+        instance_pre: InstancePre<Host>,
+        runtime_config: RuntimeConfig,
+        #[cfg(feature = "wasi-nn")] nn: Option<WasiNnCtx>,
+        draining: tokio::sync::mpsc::Sender<()>,
+    ) -> Self {
         Self(Arc::new(ProxyHandlerInner {
-            cmd,
             engine,
             instance_pre,
+            runtime_config,
             next_id: AtomicU64::from(0),
+            http_client: HttpClient::default(),
+            #[cfg(feature = "wasi-keyvalue")]
+            keyvalue: Arc::new(InMemoryStore::new()),
+            #[cfg(feature = "wasi-nn")]
+            nn,
+            idle: Mutex::new(Vec::new()),
+            permits: cmd
+                .max_concurrent_requests
+                .map(|n| Arc::new(tokio::sync::Semaphore::new(n))),
+            metrics: Arc::default(),
+            draining,
+            cmd,
         }))
     }
 }
@@ -373,68 +1036,51 @@ impl hyper::service::Service<Request> for ProxyHandler {
 
         let ProxyHandler(inner) = self.clone();
 
+        // Reject requests beyond the concurrency limit up front, without
+        // involving the guest.
+        let permit = match &inner.permits {
+            Some(permits) => match permits.clone().try_acquire_owned() {
+                Ok(permit) => Some(permit),
+                Err(_) => {
+                    inner.metrics.rejected.fetch_add(1, Ordering::Relaxed);
+                    let resp = hyper::Response::builder()
+                        .status(hyper::StatusCode::SERVICE_UNAVAILABLE)
+                        .body(
+                            http_body_util::Empty::new()
+                                .map_err(|_| unreachable!("Infallible error"))
+                                .boxed(),
+                        )
+                        .map_err(anyhow::Error::from);
+                    return Box::pin(async move { resp });
+                }
+            },
+            None => None,
+        };
+
         let (sender, receiver) = tokio::sync::oneshot::channel();
 
-        // TODO: need to track the join handle, but don't want to block the response on it
+        // The request task keeps running after the response is sent, so it's
+        // not awaited here but tracked through `draining` for the shutdown.
         tokio::task::spawn(async move {
-            let req_id = inner.next_req_id();
-            let (mut parts, body) = req.into_parts();
-
-            parts.uri = {
-                let uri_parts = parts.uri.into_parts();
-
-                let scheme = uri_parts.scheme.unwrap_or(http::uri::Scheme::HTTP);
-
-                let host = if let Some(val) = parts.headers.get(hyper::header::HOST) {
-                    std::str::from_utf8(val.as_bytes())
-                        .map_err(|_| http_types::ErrorCode::HttpRequestUriInvalid)?
-                } else {
-                    uri_parts
-                        .authority
-                        .as_ref()
-                        .ok_or(http_types::ErrorCode::HttpRequestUriInvalid)?
-                        .host()
-                };
-
-                let path_with_query = uri_parts
-                    .path_and_query
-                    .ok_or(http_types::ErrorCode::HttpRequestUriInvalid)?;
-
-                hyper::Uri::builder()
-                    .scheme(scheme)
-                    .authority(host)
-                    .path_and_query(path_with_query)
-                    .build()
-                    .map_err(|_| http_types::ErrorCode::HttpRequestUriInvalid)?
-            };
-
-            let req = hyper::Request::from_parts(parts, body.map_err(hyper_response_error).boxed());
-
-            log::info!(
-                "Request {req_id} handling {} to {}",
-                req.method(),
-                req.uri()
-            );
-
-            let mut store = inner.cmd.new_store(&inner.engine, req_id)?;
-
-            let req = store.data_mut().new_incoming_request(req)?;
-            let out = store.data_mut().new_response_outparam(sender)?;
-
-            let (proxy, _inst) =
-                wasmtime_wasi_http::proxy::Proxy::instantiate_pre(&mut store, &inner.instance_pre)
-                    .await?;
-
-            if let Err(e) = proxy
-                .wasi_http_incoming_handler()
-                .call_handle(store, req, out)
-                .await
-            {
-                log::error!("[{req_id}] :: {:#?}", e);
-                return Err(e);
+            let draining = inner.draining.clone();
+            let metrics = inner.metrics.clone();
+            metrics.in_flight.fetch_add(1, Ordering::Relaxed);
+            let start = Instant::now();
+
+            let result = handle_request(inner, req, sender).await;
+
+            metrics.in_flight.fetch_sub(1, Ordering::Relaxed);
+            metrics.requests.fetch_add(1, Ordering::Relaxed);
+            metrics.request_duration.observe(start.elapsed());
+            if let Err(e) = &result {
+                metrics.errors.fetch_add(1, Ordering::Relaxed);
+                if e.downcast_ref::<wasmtime::Trap>().is_some() {
+                    metrics.traps.fetch_add(1, Ordering::Relaxed);
+                }
             }
-
-            Ok(())
+            drop(permit);
+            drop(draining);
+            result
         });
 
         Box::pin(async move {
@@ -447,6 +1093,131 @@ impl hyper::service::Service<Request> for ProxyHandler {
     }
 }
 
+/// Handles a single request by passing it to an instance of the guest.
+async fn handle_request(
+    inner: Arc<ProxyHandlerInner>,
+    req: Request,
+    sender: tokio::sync::oneshot::Sender<
+        Result<hyper::Response<HyperOutgoingBody>, http_types::ErrorCode>,
+    >,
+) -> Result<()> {
+    use http_body_util::BodyExt;
+
+    let req_id = inner.next_req_id();
+    let (mut parts, body) = req.into_parts();
+
+    parts.uri = {
+        let uri_parts = parts.uri.into_parts();
+
+        // Requests received over TLS are always `https`, regardless
+        // of what the client claims.
+        let scheme = if inner.cmd.tls_cert.is_some() {
+            http::uri::Scheme::HTTPS
+        } else {
+            uri_parts.scheme.unwrap_or(http::uri::Scheme::HTTP)
+        };
+
+        let host = if let Some(val) = parts.headers.get(hyper::header::HOST) {
+            std::str::from_utf8(val.as_bytes())
+                .map_err(|_| http_types::ErrorCode::HttpRequestUriInvalid)?
+        } else {
+            uri_parts
+                .authority
+                .as_ref()
+                .ok_or(http_types::ErrorCode::HttpRequestUriInvalid)?
+                .host()
+        };
+
+        let path_with_query = uri_parts
+            .path_and_query
+            .ok_or(http_types::ErrorCode::HttpRequestUriInvalid)?;
+
+        hyper::Uri::builder()
+            .scheme(scheme)
+            .authority(host)
+            .path_and_query(path_with_query)
+            .build()
+            .map_err(|_| http_types::ErrorCode::HttpRequestUriInvalid)?
+    };
+
+    // The body lives as long as the request, or the body resources taken from
+    // it, so this tracks whether the guest still holds on to them.
+    let request_alive = Arc::new(());
+    let body_token = request_alive.clone();
+    let body = body.map_err(move |e| {
+        let _ = &body_token;
+        hyper_response_error(e)
+    });
+    let req = hyper::Request::from_parts(parts, body.boxed());
+
+    log::info!(
+        "Request {req_id} handling {} to {}",
+        req.method(),
+        req.uri()
+    );
+
+    let mut instance = inner.instance(req_id).await?;
+
+    // The guest sets the response through its own channel, which is
+    // forwarded to `sender` as soon as it's set, so that it's known whether
+    // the guest still holds on to the outparam once it returns.
+    let (guest_sender, mut guest_receiver) = tokio::sync::oneshot::channel();
+    let mut sender = Some(sender);
+    let mut forward = |resp| {
+        if let Some(sender) = sender.take() {
+            let _ = sender.send(resp);
+        }
+    };
+
+    let req = instance.store.data_mut().new_incoming_request(req)?;
+    let out = instance
+        .store
+        .data_mut()
+        .new_response_outparam(guest_sender)?;
+
+    let mut response_set = false;
+    let result = {
+        let call =
+            instance
+                .proxy
+                .wasi_http_incoming_handler()
+                .call_handle(&mut instance.store, req, out);
+        tokio::pin!(call);
+        loop {
+            tokio::select! {
+                result = &mut call => break result,
+                resp = &mut guest_receiver, if !response_set => {
+                    response_set = true;
+                    if let Ok(resp) = resp {
+                        forward(resp);
+                    }
+                }
+            }
+        }
+    };
+    let outparam_alive = !response_set
+        && match guest_receiver.try_recv() {
+            Ok(resp) => {
+                forward(resp);
+                false
+            }
+            Err(tokio::sync::oneshot::error::TryRecvError::Closed) => false,
+            Err(tokio::sync::oneshot::error::TryRecvError::Empty) => true,
+        };
+
+    inner.finish_profile(&mut instance.store, req_id);
+
+    if let Err(e) = result {
+        log::error!("[{req_id}] :: {:#?}", e);
+        return Err(e);
+    }
+
+    let leaked = outparam_alive || Arc::strong_count(&request_alive) > 1;
+    inner.recycle(instance, req_id, leaked).await;
+
+    Ok(())
+}
+
 #[derive(Clone)]
 enum Output {
     Stdout,
@@ -467,7 +1238,7 @@ impl Output {
 
 #[derive(Clone)]
 struct LogStream {
-    prefix: String,
+    req_id: Arc<AtomicU64>,
     output: Output,
 }
 
@@ -488,11 +1259,16 @@ impl preview2::StdoutStream for LogStream {
 
 impl preview2::HostOutputStream for LogStream {
     fn write(&mut self, bytes: bytes::Bytes) -> StreamResult<()> {
+        let name = match self.output {
+            Output::Stdout => "stdout",
+            Output::Stderr => "stderr",
+        };
+        let prefix = format!("{name} [{}] :: ", self.req_id.load(Ordering::Relaxed));
         let mut msg = Vec::new();
 
         for line in bytes.split(|c| *c == b'\n') {
             if !line.is_empty() {
-                msg.extend_from_slice(&self.prefix.as_bytes());
+                msg.extend_from_slice(prefix.as_bytes());
                 msg.extend_from_slice(line);
                 msg.push(b'\n');
             }
@@ -0,0 +1,121 @@
+//! Metrics collected by `wasmtime serve` and exposed in the Prometheus text
+//! format through `--metrics-addr`.
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Upper bounds, in seconds, of the buckets used by all histograms.
+const BUCKETS: [f64; 12] = [
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Default)]
+pub struct Metrics {
+    pub requests: AtomicU64,
+    pub rejected: AtomicU64,
+    pub errors: AtomicU64,
+    pub traps: AtomicU64,
+    pub in_flight: AtomicU64,
+    pub request_duration: Histogram,
+    pub instantiation_duration: Histogram,
+}
+
+impl Metrics {
+    /// Renders all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        counter(
+            &mut out,
+            "wasmtime_serve_requests_total",
+            "Requests handled by the guest.",
+            &self.requests,
+        );
+        counter(
+            &mut out,
+            "wasmtime_serve_requests_rejected_total",
+            "Requests rejected because too many were already in flight.",
+            &self.rejected,
+        );
+        counter(
+            &mut out,
+            "wasmtime_serve_request_errors_total",
+            "Requests which failed with an error, including traps.",
+            &self.errors,
+        );
+        counter(
+            &mut out,
+            "wasmtime_serve_traps_total",
+            "Requests during which the guest trapped.",
+            &self.traps,
+        );
+        gauge(
+            &mut out,
+            "wasmtime_serve_requests_in_flight",
+            "Requests currently being handled by the guest.",
+            &self.in_flight,
+        );
+        self.request_duration.render(
+            &mut out,
+            "wasmtime_serve_request_duration_seconds",
+            "Time spent handling a request, including instantiation.",
+        );
+        self.instantiation_duration.render(
+            &mut out,
+            "wasmtime_serve_instantiation_duration_seconds",
+            "Time spent creating a new instance of the component.",
+        );
+        out
+    }
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: &AtomicU64) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} counter");
+    let _ = writeln!(out, "{name} {}", value.load(Ordering::Relaxed));
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: &AtomicU64) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} gauge");
+    let _ = writeln!(out, "{name} {}", value.load(Ordering::Relaxed));
+}
+
+/// A histogram of durations with fixed buckets.
+#[derive(Default)]
+pub struct Histogram {
+    buckets: [AtomicU64; BUCKETS.len()],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    pub fn observe(&self, duration: Duration) {
+        let secs = duration.as_secs_f64();
+        for (bucket, bound) in self.buckets.iter().zip(BUCKETS) {
+            if secs <= bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} histogram");
+        for (bucket, bound) in self.buckets.iter().zip(BUCKETS) {
+            let _ = writeln!(
+                out,
+                "{name}_bucket{{le=\"{bound}\"}} {}",
+                bucket.load(Ordering::Relaxed)
+            );
+        }
+        let count = self.count.load(Ordering::Relaxed);
+        let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {count}");
+        let sum = self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let _ = writeln!(out, "{name}_sum {sum}");
+        let _ = writeln!(out, "{name}_count {count}");
+    }
+}
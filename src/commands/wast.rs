@@ -1,11 +1,14 @@
 //! The module that implements the `wasmtime wast` command.
 
-use anyhow::{Context as _, Result};
-use clap::Parser;
-use std::path::PathBuf;
+use anyhow::{bail, Context as _, Error, Result};
+use clap::{Parser, ValueEnum};
+use serde_derive::Serialize;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use wasmtime::{Engine, Store};
 use wasmtime_cli_flags::CommonOptions;
-use wasmtime_wast::WastContext;
+use wasmtime_wast::{DirectiveReport, WastContext};
 
 /// Runs a WebAssembly test script file
 #[derive(Parser, PartialEq)]
@@ -13,11 +16,55 @@ pub struct WastCommand {
     #[command(flatten)]
     common: CommonOptions,
 
+    /// Keep running directives and scripts after a failure, and print a
+    /// summary of the failures at the end
+    #[arg(long)]
+    keep_going: bool,
+
+    /// Write a report of the outcome of each directive to the given path
+    #[arg(long, value_name = "PATH")]
+    report: Option<PathBuf>,
+
+    /// The format of the report written with `--report`
+    #[arg(long, value_enum, default_value_t = ReportFormat::Json)]
+    report_format: ReportFormat,
+
     /// The path of the WebAssembly test script to run
     #[arg(required = true, value_name = "SCRIPT_FILE")]
     scripts: Vec<PathBuf>,
 }
 
+#[derive(ValueEnum, Clone, Copy, PartialEq)]
+enum ReportFormat {
+    /// JSON with the outcome of each directive of each script
+    Json,
+    /// JUnit XML, with a test suite per script and a test case per directive
+    Junit,
+}
+
+/// The outcome of a script: either the outcome of its directives, or the error
+/// which prevented running them, such as a syntax error.
+struct ScriptOutcome<'a> {
+    path: &'a Path,
+    time: Duration,
+    result: Result<Vec<DirectiveReport>>,
+}
+
+impl ScriptOutcome<'_> {
+    /// The number of directives of the script, where an error running the
+    /// script counts as one failed directive.
+    fn directives(&self) -> usize {
+        self.result.as_ref().map_or(1, |d| d.len())
+    }
+
+    fn failures(&self) -> usize {
+        match &self.result {
+            Ok(directives) => directives.iter().filter(|d| d.error.is_some()).count(),
+            Err(_) => 1,
+        }
+    }
+}
+
 impl WastCommand {
     /// Executes the command.
     pub fn execute(mut self) -> Result<()> {
@@ -31,12 +78,191 @@ impl WastCommand {
             .register_spectest(true)
             .expect("error instantiating \"spectest\"");
 
+        let mut outcomes = Vec::new();
         for script in self.scripts.iter() {
-            wast_context
-                .run_file(script)
-                .with_context(|| format!("failed to run script file '{}'", script.display()))?
+            let start = Instant::now();
+            let result = wast_context.run_file_with_report(script, self.keep_going);
+            let outcome = ScriptOutcome {
+                path: script,
+                time: start.elapsed(),
+                result,
+            };
+            let failed = outcome.failures() > 0;
+            outcomes.push(outcome);
+            if failed && !self.keep_going {
+                break;
+            }
+        }
+
+        if let Some(path) = &self.report {
+            let report = match self.report_format {
+                ReportFormat::Json => json_report(&outcomes)?,
+                ReportFormat::Junit => junit_report(&outcomes),
+            };
+            std::fs::write(path, report)
+                .with_context(|| format!("failed to write report: {}", path.display()))?;
         }
 
+        let directives: usize = outcomes.iter().map(|o| o.directives()).sum();
+        let failures: usize = outcomes.iter().map(|o| o.failures()).sum();
+        for outcome in outcomes {
+            let errors = match outcome.result {
+                Ok(reports) => reports.into_iter().filter_map(|d| d.error).collect(),
+                Err(e) => vec![e],
+            };
+            for error in errors {
+                let error = error.context(format!(
+                    "failed to run script file '{}'",
+                    outcome.path.display()
+                ));
+                if !self.keep_going {
+                    return Err(error);
+                }
+                eprintln!("error: {error:?}\n");
+            }
+        }
+
+        if failures > 0 {
+            bail!("{failures} of {directives} wast directives failed");
+        }
         Ok(())
     }
 }
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    directives: usize,
+    failures: usize,
+    scripts: Vec<JsonScript<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonScript<'a> {
+    path: &'a Path,
+    /// The time to run the script, in seconds.
+    time: f64,
+    /// The error which prevented running the script, if any.
+    error: Option<String>,
+    directives: Vec<JsonDirective>,
+}
+
+#[derive(Serialize)]
+struct JsonDirective {
+    kind: &'static str,
+    line: usize,
+    column: usize,
+    /// The time to run the directive, in seconds.
+    time: f64,
+    error: Option<String>,
+}
+
+fn json_report(outcomes: &[ScriptOutcome<'_>]) -> Result<String> {
+    let scripts = outcomes
+        .iter()
+        .map(|outcome| {
+            let (error, directives) = match &outcome.result {
+                Ok(directives) => (None, &directives[..]),
+                Err(e) => (Some(format!("{e:#}")), &[][..]),
+            };
+            JsonScript {
+                path: outcome.path,
+                time: outcome.time.as_secs_f64(),
+                error,
+                directives: directives
+                    .iter()
+                    .map(|d| JsonDirective {
+                        kind: d.kind,
+                        line: d.line,
+                        column: d.column,
+                        time: d.time.as_secs_f64(),
+                        error: d.error.as_ref().map(|e| format!("{e:#}")),
+                    })
+                    .collect(),
+            }
+        })
+        .collect::<Vec<_>>();
+    let report = JsonReport {
+        directives: outcomes.iter().map(|o| o.directives()).sum(),
+        failures: outcomes.iter().map(|o| o.failures()).sum(),
+        scripts,
+    };
+    let mut json = serde_json::to_string_pretty(&report)?;
+    json.push('\n');
+    Ok(json)
+}
+
+fn junit_report(outcomes: &[ScriptOutcome<'_>]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let total_tests: usize = outcomes.iter().map(|o| o.directives()).sum();
+    let total_failures: usize = outcomes.iter().map(|o| o.failures()).sum();
+    let total_time: f64 = outcomes.iter().map(|o| o.time.as_secs_f64()).sum();
+    writeln!(
+        xml,
+        "<testsuites name=\"wasmtime wast\" tests=\"{total_tests}\" failures=\"{total_failures}\" time=\"{total_time:.6}\">"
+    )
+    .unwrap();
+    for outcome in outcomes {
+        let name = xml_escape(&outcome.path.display().to_string());
+        writeln!(
+            xml,
+            "  <testsuite name=\"{name}\" tests=\"{}\" failures=\"{}\" time=\"{:.6}\">",
+            outcome.directives(),
+            outcome.failures(),
+            outcome.time.as_secs_f64(),
+        )
+        .unwrap();
+        match &outcome.result {
+            Ok(directives) => {
+                for d in directives {
+                    let case = format!("{} at {}:{}", d.kind, d.line, d.column);
+                    junit_case(&mut xml, &name, &case, d.time, d.error.as_ref());
+                }
+            }
+            Err(e) => junit_case(&mut xml, &name, "script", outcome.time, Some(e)),
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+fn junit_case(xml: &mut String, suite: &str, name: &str, time: Duration, error: Option<&Error>) {
+    write!(
+        xml,
+        "    <testcase classname=\"{suite}\" name=\"{}\" time=\"{:.6}\"",
+        xml_escape(name),
+        time.as_secs_f64(),
+    )
+    .unwrap();
+    match error {
+        Some(e) => {
+            writeln!(
+                xml,
+                ">\n      <failure message=\"{}\">{}</failure>\n    </testcase>",
+                xml_escape(&format!("{e:#}")),
+                xml_escape(&format!("{e:?}")),
+            )
+            .unwrap();
+        }
+        None => xml.push_str("/>\n"),
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            // Other control characters aren't allowed in XML 1.0 documents,
+            // even escaped.
+            c if c.is_control() => escaped.push_str(&c.escape_unicode().to_string()),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
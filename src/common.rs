@@ -2,9 +2,13 @@
 
 use anyhow::{bail, Context, Result};
 use clap::Parser;
-use std::{path::Path, time::Duration};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 use wasmtime::{Engine, Module, Precompiled, StoreLimits, StoreLimitsBuilder};
 use wasmtime_cli_flags::{opt::WasmtimeOptionValue, CommonOptions};
+use wasmtime_wasi::preview2::WasiCtxBuilder;
 
 #[cfg(feature = "component-model")]
 use wasmtime::component::Component;
@@ -70,9 +74,59 @@ pub struct RunCommon {
         value_parser = Profile::parse,
     )]
     pub profile: Option<Profile>,
+
+    /// Set a runtime configuration value available to components through the
+    /// `wasi:config/runtime` interface.
+    ///
+    /// Values passed here take precedence over those read from
+    /// `--config-file`; overriding a secret of the file keeps it secret.
+    #[arg(long = "config", number_of_values = 1, value_name = "KEY=VAL", value_parser = parse_config_var)]
+    pub config_vars: Vec<(String, String)>,
+
+    /// Read runtime configuration values from a TOML file.
+    ///
+    /// Each top-level key of the file becomes a value available through
+    /// `wasi:config/runtime`. Keys within a `[secrets]` table can be looked
+    /// up individually by the guest but aren't listed by `get-all`.
+    #[arg(long = "config-file", value_name = "PATH")]
+    pub config_file: Option<PathBuf>,
+
+    /// Read the module or component to run and the options to run it with
+    /// from a TOML manifest file.
+    ///
+    /// Options passed on the command line are combined with the ones of the
+    /// manifest and take precedence over them. A module passed on the command
+    /// line replaces the one of the manifest along with its arguments.
+    #[arg(long, value_name = "PATH")]
+    pub manifest: Option<PathBuf>,
+}
+
+fn parse_config_var(s: &str) -> Result<(String, String)> {
+    match s.split_once('=') {
+        Some((key, value)) => Ok((key.to_string(), value.to_string())),
+        None => bail!("must be of the form `KEY=VAL`"),
+    }
 }
 
 impl RunCommon {
+    /// Uses the options of `defaults`, as read from a manifest, where they
+    /// aren't overridden by the options of `self`.
+    pub fn merge_defaults(&mut self, defaults: RunCommon) {
+        let RunCommon {
+            common,
+            allow_precompiled,
+            profile,
+            config_vars,
+            config_file,
+            manifest: _,
+        } = defaults;
+        self.common.prepend(common);
+        self.allow_precompiled |= allow_precompiled;
+        self.profile = self.profile.take().or(profile);
+        self.config_vars.splice(0..0, config_vars);
+        self.config_file = self.config_file.take().or(config_file);
+    }
+
     pub fn store_limits(&self) -> StoreLimits {
         let mut limits = StoreLimitsBuilder::new();
         if let Some(max) = self.common.wasm.max_memory_size {
@@ -97,6 +151,58 @@ impl RunCommon {
         limits.build()
     }
 
+    /// Loads the runtime configuration specified by `--config-file` and
+    /// `--config`.
+    pub fn runtime_config(&self) -> Result<RuntimeConfig> {
+        let mut config = match &self.config_file {
+            Some(path) => RuntimeConfig::from_file(path)?,
+            None => RuntimeConfig::default(),
+        };
+        for (key, value) in self.config_vars.iter() {
+            match config.secrets.iter_mut().find(|(k, _)| k == key) {
+                Some((_, secret)) => *secret = value.clone(),
+                None => config.vars.push((key.clone(), value.clone())),
+            }
+        }
+        Ok(config)
+    }
+
+    /// Whether `wasi:config/runtime` should be made available to components.
+    pub fn runtime_config_enabled(&self) -> bool {
+        self.common.wasi.runtime_config == Some(true)
+            || !self.config_vars.is_empty()
+            || self.config_file.is_some()
+    }
+
+    /// Creates a wasi-nn context which loads graphs by name from the manifest
+    /// passed with `-Snn-manifest`, if any, or else from the graphs preloaded
+    /// with `-Snn-graph`.
+    #[cfg(feature = "wasi-nn")]
+    pub fn wasi_nn_ctx(&self) -> Result<wasmtime_wasi_nn::WasiNnCtx> {
+        if let Some(path) = &self.common.wasi.nn_manifest {
+            if !self.common.wasi.nn_graph.is_empty() {
+                bail!("`-Snn-manifest` cannot be combined with `-Snn-graph`");
+            }
+            let registry = wasmtime_wasi_nn::ManifestRegistry::from_file(
+                Path::new(path),
+                wasmtime_wasi_nn::backend::list(),
+            )?;
+            return Ok(wasmtime_wasi_nn::WasiNnCtx::new(
+                wasmtime_wasi_nn::backend::list(),
+                registry.into(),
+            ));
+        }
+        let graphs = self
+            .common
+            .wasi
+            .nn_graph
+            .iter()
+            .map(|g| (g.format.clone(), g.dir.clone()))
+            .collect::<Vec<_>>();
+        let (backends, registry) = wasmtime_wasi_nn::preload(&graphs)?;
+        Ok(wasmtime_wasi_nn::WasiNnCtx::new(backends, registry))
+    }
+
     pub fn ensure_allow_precompiled(&self) -> Result<()> {
         if self.allow_precompiled {
             Ok(())
@@ -216,6 +322,65 @@ impl RunCommon {
     }
 }
 
+/// Runtime configuration exposed to guests through `wasi:config/runtime`.
+#[derive(Default, Clone)]
+pub struct RuntimeConfig {
+    pub vars: Vec<(String, String)>,
+    pub secrets: Vec<(String, String)>,
+}
+
+impl RuntimeConfig {
+    fn from_file(path: &Path) -> Result<RuntimeConfig> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file: {}", path.display()))?;
+        let table: toml::value::Table = toml::from_str(&contents)
+            .with_context(|| format!("failed to parse config file: {}", path.display()))?;
+
+        fn to_string(key: &str, value: toml::Value) -> Result<String> {
+            Ok(match value {
+                toml::Value::String(s) => s,
+                toml::Value::Integer(_)
+                | toml::Value::Float(_)
+                | toml::Value::Boolean(_)
+                | toml::Value::Datetime(_) => value.to_string(),
+                toml::Value::Array(_) | toml::Value::Table(_) => {
+                    bail!("config value `{key}` must be a string, number or boolean")
+                }
+            })
+        }
+
+        let mut config = RuntimeConfig::default();
+        for (key, value) in table {
+            match value {
+                toml::Value::Table(secrets) if key == "secrets" => {
+                    for (key, value) in secrets {
+                        let value = to_string(&key, value)?;
+                        config.secrets.push((key, value));
+                    }
+                }
+                value => {
+                    let value = to_string(&key, value)?;
+                    config.vars.push((key, value));
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    /// Adds all values to the context being built.
+    ///
+    /// Secrets are added last so that a value which is also a secret stays
+    /// secret.
+    pub fn apply(&self, builder: &mut WasiCtxBuilder) {
+        for (key, value) in self.vars.iter() {
+            builder.config_var(key, value);
+        }
+        for (key, value) in self.secrets.iter() {
+            builder.config_secret(key, value);
+        }
+    }
+}
+
 #[derive(Clone, PartialEq)]
 pub enum Profile {
     Native(wasmtime::ProfilingStrategy),
@@ -8,5 +8,9 @@ pub mod commands;
 
 pub(crate) mod common;
 
+pub(crate) mod manifest;
+
+pub(crate) mod report;
+
 #[cfg(feature = "old-cli")]
 pub mod old_cli;
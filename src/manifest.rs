@@ -0,0 +1,190 @@
+//! Support for the manifest files passed with `--manifest`.
+//!
+//! A manifest declares the module or component to run along with the options
+//! to run it with, as an alternative to passing them all on the command line.
+//! It is translated to the equivalent command line arguments, which are then
+//! validated by the same parser as the command line itself.
+
+use anyhow::{bail, Context, Result};
+use serde_derive::Deserialize;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// The version of the manifest format supported by this version of Wasmtime.
+const VERSION: u32 = 1;
+
+/// The contents of a manifest file.
+///
+/// Relative paths are relative to the directory of the manifest.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Manifest {
+    /// The version of the manifest format.
+    version: u32,
+    /// The module or component to run.
+    module: Option<PathBuf>,
+    /// The arguments passed to the module or component.
+    #[serde(default)]
+    args: Vec<String>,
+    /// The function to run, as with `--invoke`.
+    invoke: Option<String>,
+    /// Allow running precompiled modules, as with `--allow-precompiled`.
+    #[serde(default)]
+    allow_precompiled: bool,
+    /// Host directories to preopen, as `HOST[::GUEST]` like with `--dir`.
+    #[serde(default)]
+    dirs: Vec<String>,
+    /// Environment variables to set.
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    /// Environment variables to inherit from the calling process.
+    #[serde(default)]
+    inherit_env: Vec<String>,
+    /// Modules to load before the main module, keyed by their name.
+    #[serde(default)]
+    preload: BTreeMap<String, PathBuf>,
+    /// Runtime configuration values, as with `--config`.
+    #[serde(default)]
+    config: BTreeMap<String, String>,
+    /// `-O` options.
+    #[serde(default)]
+    optimize: BTreeMap<String, toml::Value>,
+    /// `-C` options.
+    #[serde(default)]
+    codegen: BTreeMap<String, toml::Value>,
+    /// `-D` options.
+    #[serde(default)]
+    debug: BTreeMap<String, toml::Value>,
+    /// `-W` options.
+    #[serde(default)]
+    wasm: BTreeMap<String, toml::Value>,
+    /// `-S` options.
+    #[serde(default)]
+    wasi: BTreeMap<String, toml::Value>,
+}
+
+impl Manifest {
+    /// Reads the manifest at `path`.
+    pub fn from_file(path: &Path) -> Result<Manifest> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read manifest: {}", path.display()))?;
+        let manifest: Manifest = toml::from_str(&contents)
+            .with_context(|| format!("failed to parse manifest: {}", path.display()))?;
+        if manifest.version != VERSION {
+            bail!(
+                "unsupported manifest version {} in {}, expected version {VERSION}",
+                manifest.version,
+                path.display()
+            );
+        }
+        Ok(manifest)
+    }
+
+    /// Returns the command line options equivalent to this manifest, with
+    /// relative paths resolved against `dir`.
+    pub fn options(&self, dir: &Path) -> Result<Vec<OsString>> {
+        let mut args = Vec::new();
+        let mut push = |flag: &str, value: OsString| {
+            args.push(OsString::from(flag));
+            args.push(value);
+        };
+
+        if let Some(invoke) = &self.invoke {
+            push("--invoke", invoke.into());
+        }
+        for host_and_guest in self.dirs.iter() {
+            let (host, guest) = match host_and_guest.split_once("::") {
+                Some((host, guest)) => (host, guest),
+                None => (host_and_guest.as_str(), host_and_guest.as_str()),
+            };
+            let mut dir_arg = dir.join(host).into_os_string();
+            dir_arg.push("::");
+            dir_arg.push(guest);
+            push("--dir", dir_arg);
+        }
+        for (key, value) in self.env.iter() {
+            push("--env", format!("{key}={value}").into());
+        }
+        for key in self.inherit_env.iter() {
+            push("--env", key.into());
+        }
+        for (name, path) in self.preload.iter() {
+            let mut preload = OsString::from(format!("{name}="));
+            preload.push(dir.join(path));
+            push("--preload", preload);
+        }
+        for (key, value) in self.config.iter() {
+            push("--config", format!("{key}={value}").into());
+        }
+        for (flag, options) in [
+            ("-O", &self.optimize),
+            ("-C", &self.codegen),
+            ("-D", &self.debug),
+            ("-W", &self.wasm),
+            ("-S", &self.wasi),
+        ] {
+            for (key, value) in options {
+                // Options which may be specified more than once, such as
+                // `tcplisten`, are given as arrays.
+                let values = match value {
+                    toml::Value::Array(values) => values.iter().collect(),
+                    value => vec![value],
+                };
+                for value in values {
+                    let value = match value {
+                        toml::Value::String(s) => s.clone(),
+                        toml::Value::Integer(i) => i.to_string(),
+                        toml::Value::Float(f) => f.to_string(),
+                        toml::Value::Boolean(true) => "y".to_string(),
+                        toml::Value::Boolean(false) => "n".to_string(),
+                        _ => bail!("manifest option `{key}` must be a string, number or boolean"),
+                    };
+                    push(flag, format!("{key}={value}").into());
+                }
+            }
+        }
+        if self.allow_precompiled {
+            args.push("--allow-precompiled".into());
+        }
+        Ok(args)
+    }
+
+    /// Returns the module or component declared by this manifest followed by
+    /// its arguments, with its path resolved against `dir`.
+    pub fn module_and_args(&self, dir: &Path) -> Vec<OsString> {
+        let mut module_and_args = Vec::new();
+        if let Some(module) = &self.module {
+            module_and_args.push(dir.join(module).into_os_string());
+            module_and_args.extend(self.args.iter().map(OsString::from));
+        }
+        module_and_args
+    }
+}
+
+/// Reads the manifest at `path` and parses it, followed by `module_and_args`
+/// if not empty, as the command line of the subcommand `name` with the parser
+/// `P`.
+pub fn parse<P: clap::Parser>(
+    name: &str,
+    path: &Path,
+    module_and_args: Vec<OsString>,
+) -> Result<P> {
+    let manifest = Manifest::from_file(path)?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut args = vec![OsString::from(name)];
+    args.extend(manifest.options(dir)?);
+    args.push("--".into());
+    if module_and_args.is_empty() {
+        if manifest.module.is_none() {
+            bail!(
+                "no module or component to run: {} doesn't declare a `module`",
+                path.display()
+            );
+        }
+        args.extend(manifest.module_and_args(dir));
+    } else {
+        args.extend(module_and_args);
+    }
+    P::try_parse_from(args).with_context(|| format!("invalid manifest: {}", path.display()))
+}
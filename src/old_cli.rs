@@ -234,8 +234,9 @@ pub struct RunCommand {
 fn parse_module(s: OsString) -> anyhow::Result<PathBuf> {
     // Do not accept wasmtime subcommand names as the module name
     match s.to_str() {
-        Some("help") | Some("run") | Some("compile") | Some("serve") | Some("explore")
-        | Some("settings") | Some("wast") | Some("config") => {
+        Some("help") | Some("run") | Some("repl") | Some("compile") | Some("serve")
+        | Some("explore") | Some("settings") | Some("wast") | Some("config") | Some("cache")
+        | Some("objdump") => {
             bail!("module name cannot be the same as a subcommand")
         }
         _ => Ok(s.into()),
@@ -816,6 +817,9 @@ impl RunCommand {
             common,
             allow_precompiled,
             profile: profile.map(|p| p.convert()),
+            config_vars: Vec::new(),
+            config_file: None,
+            manifest: None,
         };
 
         let mut module_and_args = vec![module.into()];
@@ -826,6 +830,7 @@ impl RunCommand {
             vars,
             invoke,
             preloads,
+            report: None,
             module_and_args,
         }
     }
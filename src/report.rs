@@ -0,0 +1,281 @@
+//! Support for the execution reports written with `wasmtime run --report`.
+
+use anyhow::{Context, Error, Result};
+use serde_derive::Serialize;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use wasmtime::{Engine, ResourceLimiter, StoreLimits, Trap, WasmBacktrace};
+
+/// A machine-readable summary of the execution of a program, written as JSON.
+///
+/// Durations are in seconds and sizes in bytes.
+#[derive(Serialize)]
+pub struct Report {
+    /// The exit code of the `wasmtime` process.
+    exit_code: i32,
+    /// The error which ended the execution, if any.
+    error: Option<String>,
+    /// The trap which ended the execution, if any.
+    trap: Option<TrapReport>,
+    /// The fuel consumed by the program, if fuel is enabled.
+    fuel_consumed: Option<u64>,
+    /// The largest size reached by a linear memory of the program.
+    peak_memory_size: usize,
+    /// The time between the start and the end of the execution.
+    wall_time: f64,
+    /// The CPU time of the process, if known on this platform.
+    cpu_time: Option<f64>,
+    /// The time to compile the main module, or to load it if precompiled.
+    compile_time: Option<f64>,
+    /// The time to instantiate the main module.
+    instantiate_time: Option<f64>,
+    /// Whether the compilation of the main module was found in the cache.
+    cache: CacheStatus,
+
+    #[serde(skip)]
+    start: Instant,
+    #[serde(skip)]
+    cache_before: Option<(u64, u64)>,
+}
+
+#[derive(Serialize)]
+struct TrapReport {
+    /// The kind of trap, such as `UnreachableCodeReached`.
+    kind: String,
+    /// The description of the trap.
+    message: String,
+    /// The wasm frames of the trap, innermost first.
+    backtrace: Vec<FrameReport>,
+}
+
+#[derive(Serialize)]
+struct FrameReport {
+    module: Option<String>,
+    func_index: u32,
+    func_name: Option<String>,
+    module_offset: Option<usize>,
+    func_offset: Option<usize>,
+    /// Source locations from the DWARF debug information of the module.
+    symbols: Vec<SymbolReport>,
+}
+
+#[derive(Serialize)]
+struct SymbolReport {
+    name: Option<String>,
+    file: Option<String>,
+    line: Option<u32>,
+    column: Option<u32>,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+enum CacheStatus {
+    /// The compiled module was loaded from the cache.
+    Hit,
+    /// The module was compiled and stored in the cache.
+    Miss,
+    /// The cache is disabled.
+    Disabled,
+    /// The cache wasn't used, as for precompiled modules and components.
+    Unused,
+}
+
+impl Report {
+    /// Starts the report of an execution.
+    pub fn start() -> Report {
+        Report {
+            exit_code: 0,
+            error: None,
+            trap: None,
+            fuel_consumed: None,
+            peak_memory_size: 0,
+            wall_time: 0.0,
+            cpu_time: None,
+            compile_time: None,
+            instantiate_time: None,
+            cache: CacheStatus::Disabled,
+            start: Instant::now(),
+            cache_before: None,
+        }
+    }
+
+    /// Records the start of the compilation of the main module with `engine`.
+    pub fn compile_started(&mut self, engine: &Engine) {
+        self.cache_before = cache_statistics(engine);
+    }
+
+    /// Records the end of the compilation of the main module, which started
+    /// `elapsed` ago.
+    pub fn compile_finished(&mut self, engine: &Engine, elapsed: Duration) {
+        self.compile_time = Some(elapsed.as_secs_f64());
+        self.cache = match (self.cache_before, cache_statistics(engine)) {
+            (Some((hits, _)), Some((new_hits, _))) if new_hits > hits => CacheStatus::Hit,
+            (Some((_, misses)), Some((_, new_misses))) if new_misses > misses => CacheStatus::Miss,
+            (Some(_), Some(_)) => CacheStatus::Unused,
+            _ => CacheStatus::Disabled,
+        };
+    }
+
+    /// Records the instantiation of the main module, which took `elapsed`.
+    pub fn instantiated(&mut self, elapsed: Duration) {
+        self.instantiate_time = Some(elapsed.as_secs_f64());
+    }
+
+    /// Records the resources used by the store of the program.
+    pub fn store_finished(&mut self, fuel_consumed: Option<u64>, peak_memory_size: usize) {
+        self.fuel_consumed = fuel_consumed;
+        self.peak_memory_size = peak_memory_size;
+    }
+
+    /// Records the outcome of the execution and writes the report to `path`.
+    pub fn finish(mut self, result: &Result<()>, path: &Path) -> Result<()> {
+        self.wall_time = self.start.elapsed().as_secs_f64();
+        self.cpu_time = cpu_time().map(|t| t.as_secs_f64());
+        if let Err(e) = result {
+            self.record_error(e);
+        }
+
+        let mut json = serde_json::to_string_pretty(&self)?;
+        json.push('\n');
+        std::fs::write(path, json)
+            .with_context(|| format!("failed to write report: {}", path.display()))
+    }
+
+    fn record_error(&mut self, e: &Error) {
+        // These mirror the exit codes of `maybe_exit_on_error`.
+        let exit = e
+            .downcast_ref::<wasmtime_wasi::I32Exit>()
+            .map(|e| e.0)
+            .or_else(|| {
+                e.downcast_ref::<wasmtime_wasi::preview2::I32Exit>()
+                    .map(|e| e.0)
+            });
+        if let Some(code) = exit {
+            self.exit_code = if cfg!(windows) && code >= 3 { 1 } else { code };
+            return;
+        }
+
+        self.error = Some(format!("{e:#}"));
+        let trap = match e.downcast_ref::<Trap>() {
+            Some(trap) => trap,
+            None => {
+                self.exit_code = 1;
+                return;
+            }
+        };
+        // The exit code of an abort on Unix, that is `128 + SIGABRT`.
+        self.exit_code = if cfg!(windows) { 3 } else { 134 };
+        let backtrace = e
+            .downcast_ref::<WasmBacktrace>()
+            .map(|bt| bt.frames())
+            .unwrap_or_default();
+        self.trap = Some(TrapReport {
+            kind: format!("{trap:?}"),
+            message: trap.to_string(),
+            backtrace: backtrace
+                .iter()
+                .map(|frame| FrameReport {
+                    module: frame.module().name().map(|s| s.to_string()),
+                    func_index: frame.func_index(),
+                    func_name: frame.func_name().map(|s| s.to_string()),
+                    module_offset: frame.module_offset(),
+                    func_offset: frame.func_offset(),
+                    symbols: frame
+                        .symbols()
+                        .iter()
+                        .map(|symbol| SymbolReport {
+                            name: symbol.name().map(|s| s.to_string()),
+                            file: symbol.file().map(|s| s.to_string()),
+                            line: symbol.line(),
+                            column: symbol.column(),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        });
+    }
+}
+
+#[cfg(feature = "cache")]
+fn cache_statistics(engine: &Engine) -> Option<(u64, u64)> {
+    engine.cache_statistics().map(|s| (s.hits, s.misses))
+}
+
+#[cfg(not(feature = "cache"))]
+fn cache_statistics(_engine: &Engine) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+fn cpu_time() -> Option<Duration> {
+    use rustix::time::{clock_gettime, ClockId};
+    let time = clock_gettime(ClockId::ProcessCPUTime);
+    Some(Duration::new(time.tv_sec as u64, time.tv_nsec as u32))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+fn cpu_time() -> Option<Duration> {
+    None
+}
+
+/// Store limits which also record the largest size reached by a linear
+/// memory.
+#[derive(Default, Clone)]
+pub struct PeakMemoryLimits {
+    limits: StoreLimits,
+    peak_memory_size: usize,
+}
+
+impl PeakMemoryLimits {
+    /// Enforces `limits` and records the peak memory size.
+    pub fn new(limits: StoreLimits) -> PeakMemoryLimits {
+        PeakMemoryLimits {
+            limits,
+            peak_memory_size: 0,
+        }
+    }
+
+    /// Returns the largest size reached by a linear memory so far.
+    pub fn peak_memory_size(&self) -> usize {
+        self.peak_memory_size
+    }
+}
+
+impl ResourceLimiter for PeakMemoryLimits {
+    fn memory_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> Result<bool> {
+        let allowed = self.limits.memory_growing(current, desired, maximum)?;
+        if allowed {
+            self.peak_memory_size = self.peak_memory_size.max(desired);
+        }
+        Ok(allowed)
+    }
+
+    fn memory_grow_failed(&mut self, error: Error) -> Result<()> {
+        self.limits.memory_grow_failed(error)
+    }
+
+    fn table_growing(&mut self, current: u32, desired: u32, maximum: Option<u32>) -> Result<bool> {
+        self.limits.table_growing(current, desired, maximum)
+    }
+
+    fn table_grow_failed(&mut self, error: Error) -> Result<()> {
+        self.limits.table_grow_failed(error)
+    }
+
+    fn instances(&self) -> usize {
+        self.limits.instances()
+    }
+
+    fn tables(&self) -> usize {
+        self.limits.tables()
+    }
+
+    fn memories(&self) -> usize {
+        self.limits.memories()
+    }
+}
@@ -588,6 +588,88 @@ fn run_threads() -> Result<()> {
     Ok(())
 }
 
+#[cfg(feature = "wasi-threads")]
+#[test]
+fn run_threads_with_max_threads() -> Result<()> {
+    let wasm = build_wasm("tests/all/cli_tests/threads-max.wat")?;
+    let stdout = run_wasmtime(&[
+        "run",
+        "-Wthreads",
+        "-Sthreads,max-threads=1",
+        "-Ccache=n",
+        wasm.path().to_str().unwrap(),
+    ])?;
+    assert_eq!(stdout, "Spawn failed\nDone\n");
+    Ok(())
+}
+
+#[cfg(feature = "wasi-threads")]
+#[test]
+fn run_threads_failure_ends_program() -> Result<()> {
+    // The exit status of a spawned thread becomes the program's, even though
+    // the main thread is blocked forever.
+    let wasm = build_wasm("tests/all/cli_tests/threads-exit.wat")?;
+    let output = run_wasmtime_for_output(
+        &[
+            "run",
+            "-Wthreads",
+            "-Sthreads",
+            "-Ccache=n",
+            wasm.path().to_str().unwrap(),
+        ],
+        None,
+    )?;
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+
+    // So does a trap.
+    let dir = tempfile::tempdir()?;
+    let trap = dir.path().join("threads-trap.wat");
+    let wat = std::fs::read_to_string("tests/all/cli_tests/threads-exit.wat")?;
+    let spawn = "(call $__wasi_thread_spawn (i32.const 0))";
+    assert!(wat.contains(spawn));
+    std::fs::write(
+        &trap,
+        wat.replace(spawn, "(call $__wasi_thread_spawn (i32.const 1))"),
+    )?;
+    let output = run_wasmtime_for_output(
+        &[
+            "run",
+            "-Wthreads",
+            "-Sthreads",
+            "-Ccache=n",
+            trap.to_str().unwrap(),
+        ],
+        None,
+    )?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(stderr.contains("wasm trap: wasm `unreachable`"), "{stderr}");
+    assert!(!stderr.contains("stopped because"), "{stderr}");
+    Ok(())
+}
+
+#[cfg(feature = "wasi-threads")]
+#[test]
+#[cfg_attr(not(feature = "component-model"), ignore)]
+fn run_threads_with_component() -> Result<()> {
+    let output = run_wasmtime_for_output(
+        &[
+            "run",
+            "-Wthreads,component-model",
+            "-Sthreads",
+            "tests/all/cli_tests/component-basic.wat",
+        ],
+        None,
+    )?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(
+        stderr.contains("wasi-threads does not support components"),
+        "{stderr}"
+    );
+    Ok(())
+}
+
 #[cfg(feature = "wasi-threads")]
 #[test]
 fn run_simple_with_wasi_threads() -> Result<()> {
@@ -842,6 +924,226 @@ fn run_precompiled_component() -> Result<()> {
     Ok(())
 }
 
+#[test]
+#[cfg_attr(not(all(feature = "component-model", feature = "logging")), ignore)]
+fn run_wasi_logging() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let wat = dir.path().join("logging.wat");
+    std::fs::write(
+        &wat,
+        r#"(component
+            (import "wasi:logging/logging@0.1.0-draft" (instance $logging
+                (type $level' (enum "trace" "debug" "info" "warn" "error" "critical"))
+                (export $level "level" (type (eq $level')))
+                (export "log" (func (param "level" $level) (param "context" string) (param "message" string)))))
+            (alias export $logging "log" (func $log))
+            (core module $libc (memory (export "memory") 1))
+            (core instance $libc (instantiate $libc))
+            (core func $log (canon lower (func $log) (memory $libc "memory")))
+            (core module $m
+                (import "libc" "memory" (memory 1))
+                (import "logging" "log" (func $log (param i32 i32 i32 i32 i32)))
+                (data (i32.const 100) "greetinghello")
+                (func (export "run") (result i32)
+                    (call $log (i32.const 2) (i32.const 100) (i32.const 8) (i32.const 108) (i32.const 5))
+                    i32.const 0))
+            (core instance $i (instantiate $m
+                (with "libc" (instance $libc))
+                (with "logging" (instance (export "log" (func $log))))))
+            (func $run (result (result)) (canon lift (core func $i "run")))
+            (instance (export (interface "wasi:cli/run@0.2.0-rc-2023-12-05"))
+                (export "run" (func $run))))"#,
+    )?;
+    let output = get_wasmtime_command()?
+        .args(&[
+            "run",
+            "-Wcomponent-model",
+            "-Slogging",
+            wat.to_str().unwrap(),
+        ])
+        .env("WASMTIME_LOG", "wasi_logging=info")
+        .output()?;
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(output.status.success(), "{stderr}");
+    // Messages are emitted within a span identifying the program.
+    let line = stderr
+        .lines()
+        .find(|l| l.contains("hello"))
+        .unwrap_or_else(|| panic!("no log message in {stderr:?}"));
+    assert!(line.contains(" INFO "), "{line}");
+    assert!(line.contains("run{module="), "{line}");
+    assert!(line.contains("logging.wat"), "{line}");
+    assert!(line.contains("context=\"greeting\""), "{line}");
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(not(feature = "component-model"), ignore)]
+fn run_runtime_config() -> Result<()> {
+    let wat = "tests/all/cli_tests/runtime-config.wat";
+    let dir = tempfile::tempdir()?;
+    let file = dir.path().join("config.toml");
+    std::fs::write(
+        &file,
+        "a = \"file\"\nb = 1\n[secrets]\ntoken = \"file-token\"\n",
+    )?;
+    let file = file.to_str().unwrap();
+
+    // Values of `--config` override those of `--config-file`, and a secret
+    // of the file stays secret.
+    let stdout = run_wasmtime(&[
+        "run",
+        "-Wcomponent-model",
+        "--config-file",
+        file,
+        "--config",
+        "a=cli",
+        "--config",
+        "token=cli-token",
+        wat,
+    ])?;
+    assert_eq!(stdout, "");
+
+    // Secrets never show up in the logs.
+    let output = get_wasmtime_command()?
+        .args(&[
+            "run",
+            "-Wcomponent-model",
+            "--config-file",
+            file,
+            "--config",
+            "a=cli",
+            "--config",
+            "token=cli-token",
+            wat,
+        ])
+        .env("WASMTIME_LOG", "trace")
+        .output()?;
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(output.status.success(), "{stderr}");
+    assert!(stderr.contains(" TRACE "), "{stderr}");
+    assert!(!stderr.contains("cli-token"), "{stderr}");
+    assert!(!stderr.contains("file-token"), "{stderr}");
+
+    // Without `--config` the values of the file are used.
+    let output = run_wasmtime_for_output(
+        &["run", "-Wcomponent-model", "--config-file", file, wat],
+        None,
+    )?;
+    assert!(!output.status.success());
+
+    // The interface is only available when enabled.
+    let output = run_wasmtime_for_output(&["run", "-Wcomponent-model", wat], None)?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(stderr.contains("wasi:config/runtime"), "{stderr}");
+    let output =
+        run_wasmtime_for_output(&["run", "-Wcomponent-model", "-Sruntime-config", wat], None)?;
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(!stderr.contains("wasi:config/runtime"), "{stderr}");
+    assert!(stderr.contains("wasm trap"), "{stderr}");
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(not(feature = "wasi-keyvalue"), ignore)]
+fn run_wasi_keyvalue_component() -> Result<()> {
+    let wat = "tests/all/cli_tests/keyvalue.wat";
+    let output = run_wasmtime_for_output(&["run", "-Wcomponent-model", wat], None)?;
+    assert!(!output.status.success());
+
+    let stdout = run_wasmtime(&["run", "-Wcomponent-model", "-Skeyvalue", wat])?;
+    assert_eq!(stdout, "");
+
+    let output = run_wasmtime_for_output(
+        &["run", "-Skeyvalue", "tests/all/cli_tests/simple.wat"],
+        None,
+    )?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(
+        stderr.contains("Cannot enable wasi-keyvalue for core wasm modules"),
+        "{stderr}"
+    );
+    Ok(())
+}
+
+// Failures to load a graph are returned to a component as `wasi:nn/errors`
+// values instead of trapping.
+#[test]
+#[cfg_attr(not(all(feature = "component-model", feature = "wasi-nn")), ignore)]
+fn run_wasi_nn_component_errors() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let wat = dir.path().join("nn.wat");
+    std::fs::write(
+        &wat,
+        r#"(component
+            (import "wasi:nn/graph" (instance $graph
+                (type $encoding (enum "openvino" "onnx" "tensorflow" "pytorch" "tensorflowlite" "autodetect"))
+                (export $encoding' "graph-encoding" (type (eq $encoding)))
+                (type $target (enum "cpu" "gpu" "tpu"))
+                (export $target' "execution-target" (type (eq $target)))
+                (type $error (enum "invalid-argument" "invalid-encoding" "busy" "runtime-error" "unsupported-operation" "too-large" "not-found"))
+                (export $error' "error" (type (eq $error)))
+                (export $graph "graph" (type (sub resource)))
+                (export "load" (func
+                    (param "builder" (list (list u8)))
+                    (param "encoding" $encoding')
+                    (param "target" $target')
+                    (result (result (own $graph) (error $error')))))
+                (export "load-by-name" (func
+                    (param "name" string)
+                    (result (result (own $graph) (error $error')))))))
+            (alias export $graph "load" (func $load))
+            (alias export $graph "load-by-name" (func $load-by-name))
+            (core module $libc (memory (export "memory") 1))
+            (core instance $libc (instantiate $libc))
+            (core func $load (canon lower (func $load) (memory $libc "memory")))
+            (core func $load-by-name
+                (canon lower (func $load-by-name) (memory $libc "memory")))
+            (core module $m
+                (import "libc" "memory" (memory 1))
+                (import "nn" "load" (func $load (param i32 i32 i32 i32 i32)))
+                (import "nn" "load-by-name" (func $load-by-name (param i32 i32 i32)))
+                (data (i32.const 100) "missing")
+                (func $expect-error (param $code i32)
+                    (if (i32.ne (i32.load8_u (i32.const 16)) (i32.const 1))
+                        (then unreachable))
+                    (if (i32.ne (i32.load8_u (i32.const 20)) (local.get $code))
+                        (then unreachable)))
+                (func (export "run") (result i32)
+                    ;; OpenVINO expects two builders; this passes none.
+                    (call $load (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 16))
+                    (call $expect-error (i32.const 0))
+                    (call $load-by-name (i32.const 100) (i32.const 7) (i32.const 16))
+                    (call $expect-error (i32.const 6))
+                    i32.const 0))
+            (core instance $i (instantiate $m
+                (with "libc" (instance $libc))
+                (with "nn" (instance
+                    (export "load" (func $load))
+                    (export "load-by-name" (func $load-by-name))))))
+            (func $run (result (result)) (canon lift (core func $i "run")))
+            (instance (export (interface "wasi:cli/run@0.2.0-rc-2023-12-05"))
+                (export "run" (func $run))))"#,
+    )?;
+    let output = run_wasmtime_for_output(
+        &["run", "-Wcomponent-model", "-Snn", wat.to_str().unwrap()],
+        None,
+    )?;
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(output.status.success(), "{stderr}");
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "wasi-nn-tract")]
+fn run_wasi_nn_onnx_model() -> Result<()> {
+    let stdout = run_wasmtime(&["run", "-Snn", "tests/all/cli_tests/wasi-nn-relu.wat"])?;
+    assert_eq!(stdout, "");
+    Ok(())
+}
+
 #[test]
 fn memory_growth_failure() -> Result<()> {
     let output = get_wasmtime_command()?
@@ -1208,6 +1510,833 @@ fn float_args() -> Result<()> {
     Ok(())
 }
 
+#[cfg(feature = "cache")]
+#[test]
+fn cache_subcommand() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let config = dir.path().join("config.toml");
+    std::fs::write(
+        &config,
+        format!(
+            "[cache]\nenabled = true\ndirectory = {}\n",
+            toml::to_string(&dir.path().join("cache").to_str().unwrap())?
+        ),
+    )?;
+    let config = config.to_str().unwrap();
+    let cache_config = format!("-Ccache-config={config}");
+    for _ in 0..2 {
+        run_wasmtime(&["run", &cache_config, "tests/all/cli_tests/simple.wat"])?;
+    }
+
+    let stats = run_wasmtime(&["cache", "stats", "--config", config])?;
+    assert!(stats.contains("modules:   1\n"), "{stats}");
+    assert!(stats.contains("hits:      1\n"), "{stats}");
+    assert!(stats.contains("misses:    1\n"), "{stats}");
+    let list = run_wasmtime(&["cache", "list", "--config", config])?;
+    assert!(list.contains("opt_level: speed\n"), "{list}");
+
+    run_wasmtime(&["cache", "gc", "--config", config])?;
+    run_wasmtime(&["cache", "clear", "--config", config])?;
+    let stats = run_wasmtime(&["cache", "stats", "--config", config])?;
+    assert!(stats.contains("modules:   0\n"), "{stats}");
+    Ok(())
+}
+
+#[test]
+fn run_manifest() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    std::fs::copy(
+        "tests/all/cli_tests/print_env.wat",
+        dir.path().join("print_env.wat"),
+    )?;
+    let manifest = dir.path().join("app.toml");
+    std::fs::write(
+        &manifest,
+        r#"
+            version = 1
+            module = "print_env.wat"
+            env = { FOO = "bar" }
+
+            [wasm]
+            max-memory-size = 1048576
+        "#,
+    )?;
+    let manifest = manifest.to_str().unwrap();
+
+    // Options on the command line are combined with those of the manifest.
+    let stdout = run_wasmtime(&["run", "--env", "BAZ=1", "--manifest", manifest])?;
+    assert_eq!(stdout, "FOO=bar\nBAZ=1\n");
+
+    // Variables of the command line override those of the manifest.
+    let stdout = run_wasmtime(&["run", "--env", "FOO=baz", "--manifest", manifest])?;
+    assert_eq!(stdout, "FOO=baz\n");
+
+    // Options of the manifest are validated like those of the command line.
+    std::fs::write(
+        dir.path().join("bad.toml"),
+        "version = 1\nmodule = \"print_env.wat\"\n[wasm]\nbogus = 1\n",
+    )?;
+    let bad = dir.path().join("bad.toml");
+    let output = run_wasmtime_for_output(&["run", "--manifest", bad.to_str().unwrap()], None)?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(
+        stderr.contains("unknown -W / --wasm option: bogus"),
+        "{stderr}"
+    );
+    Ok(())
+}
+
+#[test]
+fn repl() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let wat = dir.path().join("counter.wat");
+    std::fs::write(
+        &wat,
+        r#"(module
+            (memory (export "memory") 1)
+            (global $g (export "counter") (mut i32) (i32.const 0))
+            (data (i32.const 16) "hello")
+            (func (export "bump") (param i32) (result i32)
+                global.get $g
+                local.get 0
+                i32.add
+                global.set $g
+                global.get $g)
+            (func (export "load") (param i32) (result i32)
+                local.get 0
+                i32.load))"#,
+    )?;
+    let commands = dir.path().join("commands");
+    std::fs::write(
+        &commands,
+        "call bump 2\n\
+         call bump 3\n\
+         global counter 10\n\
+         global counter\n\
+         read memory 16 5\n\
+         write memory 0x20 \"2a 00 00 00\"\n\
+         call load 32\n\
+         call load 65536\n\
+         call missing\n\
+         call bump 1\n",
+    )?;
+    let output =
+        run_wasmtime_for_output(&["repl", wat.to_str().unwrap()], Some(commands.as_path()))?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    let lines = stdout.lines().collect::<Vec<_>>();
+    assert_eq!(lines[..3], ["2", "5", "10"]);
+    assert!(lines[3].contains("68 65 6c 6c 6f"), "{}", lines[3]);
+    assert!(lines[3].ends_with("|hello|"), "{}", lines[3]);
+    // The instance is still usable after a trap.
+    assert_eq!(lines[4..], ["42", "11"]);
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(stderr.contains("out of bounds memory access"), "{stderr}");
+    assert!(
+        stderr.contains("no func export named `missing` found"),
+        "{stderr}"
+    );
+    Ok(())
+}
+
+#[test]
+fn repl_run() -> Result<()> {
+    // A command whose entry point, like wasi-libc's `_start`, traps when
+    // it's called a second time in the same instance.
+    let dir = tempfile::tempdir()?;
+    let wat = dir.path().join("command.wat");
+    std::fs::write(
+        &wat,
+        r#"(module
+            (import "wasi_snapshot_preview1" "fd_write"
+                (func $fd_write (param i32 i32 i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (global $started (export "started") (mut i32) (i32.const 0))
+            (data (i32.const 0) "hello\n")
+            (func (export "_start")
+                (if (global.get $started) (then unreachable))
+                (global.set $started (i32.const 1))
+                (i32.store (i32.const 16) (i32.const 0))
+                (i32.store (i32.const 20) (i32.const 6))
+                (drop (call $fd_write (i32.const 1) (i32.const 16) (i32.const 1) (i32.const 24)))))"#,
+    )?;
+    let commands = dir.path().join("commands");
+    std::fs::write(&commands, "run\nrun\nglobal started\n")?;
+    let output =
+        run_wasmtime_for_output(&["repl", wat.to_str().unwrap()], Some(commands.as_path()))?;
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(!stderr.contains("error"), "{stderr}");
+    // The instance of the other commands isn't the one which ran.
+    assert_eq!(String::from_utf8(output.stdout)?, "hello\nhello\n0\n");
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(not(feature = "component-model"), ignore)]
+fn repl_run_component() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let wat = dir.path().join("command.wat");
+    std::fs::write(
+        &wat,
+        r#"(component
+            (core module $m
+                (global $runs (mut i32) (i32.const 0))
+                (func (export "run") (result i32)
+                    (if (global.get $runs) (then unreachable))
+                    (global.set $runs (i32.const 1))
+                    i32.const 0)
+                (func (export "runs") (result i32)
+                    global.get $runs))
+            (core instance $i (instantiate $m))
+            (func $run (result (result)) (canon lift (core func $i "run")))
+            (func $runs (result u32) (canon lift (core func $i "runs")))
+            (instance (export (interface "wasi:cli/run@0.2.0-rc-2023-12-05"))
+                (export "run" (func $run)))
+            (export "runs" (func $runs)))"#,
+    )?;
+    let commands = dir.path().join("commands");
+    std::fs::write(&commands, "run\nrun\ncall runs\n")?;
+    let output = run_wasmtime_for_output(
+        &["repl", "-Wcomponent-model", wat.to_str().unwrap()],
+        Some(commands.as_path()),
+    )?;
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(!stderr.contains("error"), "{stderr}");
+    assert_eq!(String::from_utf8(output.stdout)?, "0\n");
+    Ok(())
+}
+
+#[test]
+fn run_report() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let report = dir.path().join("report.json");
+    let report_arg = report.to_str().unwrap();
+
+    let output = run_wasmtime_for_output(
+        &[
+            "run",
+            "--report",
+            report_arg,
+            "-Wfuel=1000",
+            "tests/all/cli_tests/unreachable.wat",
+        ],
+        None,
+    )?;
+    assert!(!output.status.success());
+    let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&report)?)?;
+    assert_eq!(json["exit_code"], output.status.code().unwrap());
+    assert_eq!(json["trap"]["kind"], "UnreachableCodeReached");
+    assert_eq!(json["trap"]["backtrace"][0]["func_index"], 0);
+    assert!(json["fuel_consumed"].as_u64().unwrap() > 0);
+    assert!(json["compile_time"].is_number());
+
+    let output = run_wasmtime_for_output(
+        &[
+            "run",
+            "--report",
+            report_arg,
+            "tests/all/cli_tests/exit2_wasi_snapshot1.wat",
+        ],
+        None,
+    )?;
+    assert_eq!(output.status.code().unwrap(), 2);
+    let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&report)?)?;
+    assert_eq!(json["exit_code"], 2);
+    assert!(json["trap"].is_null());
+    assert!(json["error"].is_null());
+    assert!(json["fuel_consumed"].is_null());
+
+    // The report doesn't change how modules are compiled, so precompiled
+    // modules can still be loaded.
+    let cwasm = dir.path().join("unreachable.cwasm");
+    run_wasmtime(&[
+        "compile",
+        "tests/all/cli_tests/unreachable.wat",
+        "-o",
+        cwasm.to_str().unwrap(),
+    ])?;
+    let output = run_wasmtime_for_output(
+        &[
+            "run",
+            "--allow-precompiled",
+            "--report",
+            report_arg,
+            cwasm.to_str().unwrap(),
+        ],
+        None,
+    )?;
+    assert!(!output.status.success());
+    let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&report)?)?;
+    assert_eq!(json["trap"]["kind"], "UnreachableCodeReached");
+    Ok(())
+}
+
+#[test]
+fn wast_keep_going_report() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let script = dir.path().join("test.wast");
+    std::fs::write(
+        &script,
+        r#"(module (func (export "one") (result i32) i32.const 1))
+(assert_return (invoke "one") (i32.const 2))
+(assert_return (invoke "one") (i32.const 1))
+(module (func (export "two") (result i32) i64.const 2))
+(assert_return (invoke "one") (i32.const 1))
+"#,
+    )?;
+    let report = dir.path().join("report.json");
+
+    let output = run_wasmtime_for_output(
+        &[
+            "wast",
+            "--keep-going",
+            "--report",
+            report.to_str().unwrap(),
+            script.to_str().unwrap(),
+        ],
+        None,
+    )?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(stderr.contains("3 of 5 wast directives failed"), "{stderr}");
+
+    let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&report)?)?;
+    assert_eq!(json["directives"], 5);
+    assert_eq!(json["failures"], 3);
+    let directives = json["scripts"][0]["directives"].as_array().unwrap();
+    let kinds = directives
+        .iter()
+        .map(|d| d["kind"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            "module",
+            "assert_return",
+            "assert_return",
+            "module",
+            "assert_return"
+        ]
+    );
+    assert_eq!(directives[1]["line"], 2);
+    assert!(directives[1]["error"].is_string());
+    // The directive after the failing one still ran.
+    assert!(directives[2]["error"].is_null());
+    // A module which failed to be defined isn't replaced by the previous one.
+    assert!(directives[3]["error"].is_string());
+    let error = directives[4]["error"].as_str().unwrap();
+    assert!(error.contains("no previous instance found"), "{error}");
+
+    // Without `--keep-going` the script stops at the first failure.
+    let output = run_wasmtime_for_output(
+        &[
+            "wast",
+            "--report",
+            report.to_str().unwrap(),
+            "--report-format=junit",
+            script.to_str().unwrap(),
+        ],
+        None,
+    )?;
+    assert!(!output.status.success());
+    let junit = std::fs::read_to_string(&report)?;
+    assert!(junit.contains("tests=\"2\" failures=\"1\""), "{junit}");
+    Ok(())
+}
+
+#[cfg(feature = "explore")]
+#[test]
+fn explore_json() -> Result<()> {
+    let wasm = build_wasm("tests/all/cli_tests/simple.wat")?;
+    let output = run_wasmtime(&[
+        "explore",
+        "--format=json",
+        "-o",
+        "-",
+        wasm.path().to_str().unwrap(),
+    ])?;
+    let json: serde_json::Value = serde_json::from_str(&output)?;
+    assert!(json["wat"]["chunks"].as_array().unwrap().len() > 0);
+    let functions = json["asm"]["functions"].as_array().unwrap();
+    assert!(functions.len() > 0);
+    let instructions = functions[0]["instructions"].as_array().unwrap();
+    assert!(instructions
+        .iter()
+        .any(|inst| inst["wasm_offset"].is_number()));
+    Ok(())
+}
+
+#[cfg(all(feature = "explore", feature = "component-model"))]
+#[test]
+fn explore_component_json() -> Result<()> {
+    let wasm = build_wasm("tests/all/cli_tests/component-fused.wat")?;
+    let output = run_wasmtime(&[
+        "explore",
+        "--format=json",
+        "-o",
+        "-",
+        wasm.path().to_str().unwrap(),
+    ])?;
+    let json: serde_json::Value = serde_json::from_str(&output)?;
+    assert!(json.get("wat").is_none());
+    let modules = json["modules"].as_array().unwrap();
+    let core = modules.iter().filter(|m| m["adapter"] == false).count();
+    let adapters = modules.iter().filter(|m| m["adapter"] == true).count();
+    assert_eq!(core, 2);
+    assert_eq!(adapters, 1);
+    for module in modules {
+        assert!(module["name"].is_string());
+        assert!(module["wat"]["chunks"].as_array().unwrap().len() > 0);
+        assert!(module["asm"]["functions"].as_array().unwrap().len() > 0);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "objdump")]
+#[test]
+fn objdump() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let wat = dir.path().join("load.wat");
+    std::fs::write(
+        &wat,
+        r#"(module
+            (memory 1)
+            (func (export "load") (param i32) (result i32)
+                local.get 0
+                i32.load))"#,
+    )?;
+    let cwasm = dir.path().join("load.cwasm");
+    run_wasmtime(&[
+        "compile",
+        wat.to_str().unwrap(),
+        "-o",
+        cwasm.to_str().unwrap(),
+    ])?;
+
+    let output = run_wasmtime(&["objdump", cwasm.to_str().unwrap()])?;
+    assert!(output.contains("<wasm[0]::function[0]>:\n"), "{output}");
+    assert!(output.contains("trap: MemoryOutOfBounds"), "{output}");
+    assert!(output.contains("; @0x"), "{output}");
+
+    let output = run_wasmtime(&["objdump", "--filter=trampoline", cwasm.to_str().unwrap()])?;
+    assert!(!output.contains("<wasm[0]::function[0]>"), "{output}");
+    Ok(())
+}
+
+#[cfg(feature = "serve")]
+mod serve {
+    use super::get_wasmtime_command;
+    use anyhow::{bail, Context, Result};
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{SocketAddr, TcpStream};
+    use std::process::{Child, ExitStatus, Stdio};
+    use std::sync::{Arc, Mutex};
+    use std::thread::JoinHandle;
+    use std::time::{Duration, Instant};
+
+    const COUNTER: &str = "tests/all/cli_tests/serve-counter.wat";
+
+    const SLEEP: &str = "tests/all/cli_tests/serve-sleep.wat";
+
+    const SCHEME: &str = "tests/all/cli_tests/serve-scheme.wat";
+
+    const TLS_CERT: &str = "tests/all/cli_tests/serve-tls-cert.pem";
+
+    const TLS_KEY: &str = "tests/all/cli_tests/serve-tls-key.pem";
+
+    /// Writes a variant of the sleeping component which sleeps for `secs`.
+    fn sleeping_component(dir: &tempfile::TempDir, secs: u64) -> Result<String> {
+        let component = dir.path().join("sleep.wat");
+        let wat = std::fs::read_to_string(SLEEP)?;
+        let sleep = "(i64.const 1000000000)";
+        assert!(wat.contains(sleep));
+        std::fs::write(
+            &component,
+            wat.replace(sleep, &format!("(i64.const {})", secs * 1_000_000_000)),
+        )?;
+        Ok(component.to_str().unwrap().to_string())
+    }
+
+    /// A `wasmtime serve` process, killed when dropped.
+    pub struct WasmtimeServe {
+        child: Child,
+        pub addr: SocketAddr,
+        pub metrics_addr: SocketAddr,
+        stderr: Arc<Mutex<String>>,
+        stderr_thread: Option<JoinHandle<()>>,
+        requests: u64,
+    }
+
+    impl WasmtimeServe {
+        /// Starts serving `component` with `args` on a free port.
+        pub fn new(component: &str, args: &[&str]) -> Result<WasmtimeServe> {
+            let mut cmd = get_wasmtime_command()?;
+            cmd.args(["serve", "--addr=127.0.0.1:0", "--metrics-addr=127.0.0.1:0"])
+                .args(args)
+                .arg(component)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::piped());
+            let mut child = cmd.spawn()?;
+
+            // Find the addresses in the output, and then keep reading it so
+            // that the server never blocks writing to it.
+            let mut lines = BufReader::new(child.stderr.take().unwrap()).lines();
+            let mut stderr = String::new();
+            let mut addr = None;
+            let mut metrics_addr = None;
+            while addr.is_none() || metrics_addr.is_none() {
+                let line = match lines.next() {
+                    Some(line) => line?,
+                    None => {
+                        child.wait()?;
+                        bail!("`wasmtime serve` exited early:\n{stderr}");
+                    }
+                };
+                let url = line
+                    .strip_prefix("Serving HTTP on http://")
+                    .or_else(|| line.strip_prefix("Serving HTTP on https://"));
+                if let Some(url) = url {
+                    addr = Some(url.trim_end_matches('/').parse()?);
+                } else if let Some(url) = line.strip_prefix("Serving metrics on http://") {
+                    metrics_addr = Some(url.trim_end_matches("/metrics").parse()?);
+                }
+                stderr.push_str(&line);
+                stderr.push('\n');
+            }
+            let stderr = Arc::new(Mutex::new(stderr));
+            let output = stderr.clone();
+            let stderr_thread = std::thread::spawn(move || {
+                for line in lines {
+                    let mut output = output.lock().unwrap();
+                    output.push_str(&line.unwrap_or_default());
+                    output.push('\n');
+                }
+            });
+
+            Ok(WasmtimeServe {
+                child,
+                addr: addr.unwrap(),
+                metrics_addr: metrics_addr.unwrap(),
+                stderr,
+                stderr_thread: Some(stderr_thread),
+                requests: 0,
+            })
+        }
+
+        /// Sends a request to the server and returns the status of the
+        /// response once the server is done handling the request.
+        pub fn request(&mut self) -> Result<u16> {
+            let (status, _) = get(self.addr, "/")?;
+            self.requests += 1;
+            let expected = format!("\nwasmtime_serve_requests_total {}\n", self.requests);
+            let start = Instant::now();
+            while !self.metrics()?.contains(&expected) {
+                if start.elapsed() > Duration::from_secs(10) {
+                    bail!("timed out waiting for the request to be handled");
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            Ok(status)
+        }
+
+        pub fn metrics(&self) -> Result<String> {
+            let (status, body) = get(self.metrics_addr, "/metrics")?;
+            assert_eq!(status, 200);
+            Ok(body)
+        }
+
+        pub fn stderr(&self) -> String {
+            self.stderr.lock().unwrap().clone()
+        }
+
+        /// Waits until the metrics contain `line`.
+        pub fn wait_for_metric(&self, line: &str) -> Result<()> {
+            let start = Instant::now();
+            while !self.metrics()?.lines().any(|l| l == line) {
+                if start.elapsed() > Duration::from_secs(10) {
+                    bail!("timed out waiting for `{line}`");
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            Ok(())
+        }
+
+        /// Asks the server to shut down as on Ctrl-C, on Unix, and returns
+        /// its exit status and output once it exited.
+        pub fn shut_down(mut self) -> Result<(ExitStatus, String)> {
+            #[cfg(unix)]
+            unsafe {
+                assert_eq!(libc::kill(self.child.id() as i32, libc::SIGTERM), 0);
+            }
+            #[cfg(not(unix))]
+            self.child.kill()?;
+            let status = self.child.wait()?;
+            self.stderr_thread.take().unwrap().join().unwrap();
+            Ok((status, self.stderr()))
+        }
+    }
+
+    impl Drop for WasmtimeServe {
+        fn drop(&mut self) {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+
+    /// Sends a `GET` request for `path` and returns the status and body of
+    /// the response.
+    pub fn get(addr: SocketAddr, path: &str) -> Result<(u16, String)> {
+        let mut stream = TcpStream::connect(addr)?;
+        write!(
+            stream,
+            "GET {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+        )?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+        let status = response
+            .split(' ')
+            .nth(1)
+            .and_then(|s| s.parse().ok())
+            .with_context(|| format!("invalid response: {response:?}"))?;
+        let body = response
+            .split_once("\r\n\r\n")
+            .map(|(_, body)| body.to_string())
+            .unwrap_or_default();
+        Ok((status, body))
+    }
+
+    #[test]
+    fn fresh_instance_per_request() -> Result<()> {
+        let mut server = WasmtimeServe::new(COUNTER, &[])?;
+        for _ in 0..3 {
+            assert_eq!(server.request()?, 200);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn instance_reuse() -> Result<()> {
+        let mut server = WasmtimeServe::new(COUNTER, &["--instance-reuse=3"])?;
+        let statuses = (0..5)
+            .map(|_| server.request())
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(statuses, [200, 201, 202, 200, 201], "{}", server.stderr());
+        Ok(())
+    }
+
+    #[test]
+    fn max_idle_instances() -> Result<()> {
+        let mut server =
+            WasmtimeServe::new(COUNTER, &["--instance-reuse=3", "--max-idle-instances=0"])?;
+        for _ in 0..3 {
+            assert_eq!(server.request()?, 200);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn instance_reuse_discards_leaking_instances() -> Result<()> {
+        // A guest which keeps the request may still use it later, so its
+        // instance must not handle other requests.
+        let dir = tempfile::tempdir()?;
+        let component = dir.path().join("leak-request.wat");
+        let wat = std::fs::read_to_string(COUNTER)?;
+        std::fs::write(
+            &component,
+            wat.replace("(call $drop-request (local.get $request))", ""),
+        )?;
+
+        let mut server = WasmtimeServe::new(component.to_str().unwrap(), &["--instance-reuse=3"])?;
+        for _ in 0..3 {
+            assert_eq!(server.request()?, 200);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn metrics() -> Result<()> {
+        let mut server = WasmtimeServe::new(COUNTER, &[])?;
+        assert_eq!(server.request()?, 200);
+        let metrics = server.metrics()?;
+        for expected in [
+            "# HELP wasmtime_serve_requests_total Requests handled by the guest.\n\
+             # TYPE wasmtime_serve_requests_total counter\n\
+             wasmtime_serve_requests_total 1\n",
+            "# TYPE wasmtime_serve_requests_rejected_total counter\n\
+             wasmtime_serve_requests_rejected_total 0\n",
+            "\nwasmtime_serve_request_errors_total 0\n",
+            "\nwasmtime_serve_traps_total 0\n",
+            "# TYPE wasmtime_serve_requests_in_flight gauge\n\
+             wasmtime_serve_requests_in_flight 0\n",
+            "# TYPE wasmtime_serve_request_duration_seconds histogram\n\
+             wasmtime_serve_request_duration_seconds_bucket{le=\"0.001\"} ",
+            "\nwasmtime_serve_request_duration_seconds_bucket{le=\"10\"} 1\n\
+             wasmtime_serve_request_duration_seconds_bucket{le=\"+Inf\"} 1\n\
+             wasmtime_serve_request_duration_seconds_sum ",
+            "\nwasmtime_serve_request_duration_seconds_count 1\n",
+            "\nwasmtime_serve_instantiation_duration_seconds_count 1\n",
+        ] {
+            assert!(
+                metrics.contains(expected),
+                "{expected:?} not in:\n{metrics}"
+            );
+        }
+
+        let (status, _) = get(server.metrics_addr, "/")?;
+        assert_eq!(status, 404);
+        Ok(())
+    }
+
+    #[test]
+    fn max_concurrent_requests() -> Result<()> {
+        let server = WasmtimeServe::new(SLEEP, &["--max-concurrent-requests=1"])?;
+
+        // The first request is still in flight after its response was sent,
+        // so the second one is rejected without reaching the guest.
+        assert_eq!(get(server.addr, "/")?.0, 200);
+        assert_eq!(get(server.addr, "/")?.0, 503);
+        server.wait_for_metric("wasmtime_serve_requests_rejected_total 1")?;
+
+        // Once the guest returned, requests are accepted again.
+        server.wait_for_metric("wasmtime_serve_requests_total 1")?;
+        assert_eq!(get(server.addr, "/")?.0, 200);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn graceful_shutdown() -> Result<()> {
+        let server = WasmtimeServe::new(SLEEP, &[])?;
+
+        // Shutting down waits for the guest, which is still running after
+        // its response was sent.
+        let start = Instant::now();
+        assert_eq!(get(server.addr, "/")?.0, 200);
+        let (status, stderr) = server.shut_down()?;
+        assert!(start.elapsed() >= Duration::from_secs(1), "{stderr}");
+        assert!(status.success(), "{stderr}");
+        assert!(stderr.contains("Shutting down"), "{stderr}");
+        assert!(!stderr.contains("Timed out"), "{stderr}");
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn shutdown_timeout() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let component = sleeping_component(&dir, 60)?;
+        let server = WasmtimeServe::new(&component, &["--shutdown-timeout=100ms"])?;
+
+        assert_eq!(get(server.addr, "/")?.0, 200);
+        let start = Instant::now();
+        let (status, stderr) = server.shut_down()?;
+        assert!(start.elapsed() < Duration::from_secs(30), "{stderr}");
+        assert!(status.success(), "{stderr}");
+        assert!(
+            stderr.contains("Timed out waiting for in-flight requests to finish"),
+            "{stderr}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn instance_reset() -> Result<()> {
+        let mut server =
+            WasmtimeServe::new(COUNTER, &["--instance-reuse=3", "--instance-reset=reset"])?;
+        let statuses = (0..4)
+            .map(|_| server.request())
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(statuses, [200, 210, 210, 200], "{}", server.stderr());
+        Ok(())
+    }
+
+    #[test]
+    fn http_scheme() -> Result<()> {
+        let server = WasmtimeServe::new(SCHEME, &[])?;
+        assert_eq!(get(server.addr, "/")?.0, 200, "{}", server.stderr());
+        Ok(())
+    }
+
+    /// Connects to `addr` over TLS, trusting only the test certificate, and
+    /// offering `alpn` as the application protocols.
+    #[cfg(not(any(target_arch = "riscv64", target_arch = "s390x")))]
+    fn connect_tls(
+        addr: SocketAddr,
+        alpn: &[&[u8]],
+    ) -> Result<rustls::StreamOwned<rustls::ClientConnection, TcpStream>> {
+        let mut roots = rustls::RootCertStore::empty();
+        let certs = rustls_pemfile::certs(&mut BufReader::new(std::fs::File::open(TLS_CERT)?))?;
+        roots.add_parsable_certificates(&certs);
+        let mut config = rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        config.alpn_protocols = alpn.iter().map(|p| p.to_vec()).collect();
+        let conn =
+            rustls::ClientConnection::new(Arc::new(config), "localhost".try_into().unwrap())?;
+        let mut stream = rustls::StreamOwned::new(conn, TcpStream::connect(addr)?);
+        while stream.conn.is_handshaking() {
+            stream.conn.complete_io(&mut stream.sock)?;
+        }
+        Ok(stream)
+    }
+
+    #[test]
+    #[cfg(not(any(target_arch = "riscv64", target_arch = "s390x")))]
+    fn tls() -> Result<()> {
+        let server = WasmtimeServe::new(
+            SCHEME,
+            &[
+                &format!("--tls-cert={TLS_CERT}"),
+                &format!("--tls-key={TLS_KEY}"),
+            ],
+        )?;
+        assert!(
+            server.stderr().contains("Serving HTTP on https://"),
+            "{}",
+            server.stderr()
+        );
+
+        // HTTP/1.1 is used when the client doesn't offer HTTP/2, and the
+        // guest sees the `https` scheme.
+        let mut stream = connect_tls(server.addr, &[b"http/1.1"])?;
+        assert_eq!(stream.conn.alpn_protocol(), Some(&b"http/1.1"[..]));
+        write!(
+            stream,
+            "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+        )?;
+        let mut response = Vec::new();
+        // The server may close the connection without a `close_notify`.
+        match stream.read_to_end(&mut response) {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {}
+            Err(e) => return Err(e.into()),
+        }
+        let response = String::from_utf8(response)?;
+        assert!(
+            response.starts_with("HTTP/1.1 201 "),
+            "{response:?}\n{}",
+            server.stderr()
+        );
+
+        // HTTP/2 is preferred when the client offers both, and the server
+        // answers the connection preface with its settings.
+        let mut stream = connect_tls(server.addr, &[b"h2", b"http/1.1"])?;
+        assert_eq!(stream.conn.alpn_protocol(), Some(&b"h2"[..]));
+        stream.write_all(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n")?;
+        // An empty `SETTINGS` frame.
+        stream.write_all(&[0, 0, 0, 4, 0, 0, 0, 0, 0])?;
+        let mut header = [0; 9];
+        stream.read_exact(&mut header)?;
+        assert_eq!(header[3], 4, "expected a `SETTINGS` frame: {header:?}");
+        Ok(())
+    }
+}
+
 mod test_programs {
     use super::{get_wasmtime_command, run_wasmtime};
     use anyhow::Result;
@@ -0,0 +1,27 @@
+(component
+  (core module $m
+    (func (export "add") (param i32 i32) (result i32)
+      local.get 0
+      local.get 1
+      i32.add)
+  )
+  (core instance $m (instantiate $m))
+  (func $add (param "a" u32) (param "b" u32) (result u32)
+    (canon lift (core func $m "add")))
+
+  (component $c
+    (import "add" (func $add (param "a" u32) (param "b" u32) (result u32)))
+
+    (core func $add (canon lower (func $add)))
+    (core module $m2
+      (import "" "add" (func $add (param i32 i32) (result i32)))
+      (func (export "run") (result i32)
+        i32.const 1
+        i32.const 2
+        call $add)
+    )
+    (core instance $m2 (instantiate $m2 (with "" (instance (export "add" (func $add))))))
+  )
+
+  (instance $c (instantiate $c (with "add" (func $add))))
+)
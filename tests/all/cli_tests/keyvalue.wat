@@ -0,0 +1,71 @@
+;; A `wasi:cli/run` component which opens the bucket `b` with `wasi:keyvalue`,
+;; increments the key `n` by 5 and then by 2, and traps unless the result is 7.
+(component
+  (import "wasi:keyvalue/store@0.2.0-draft" (instance $store
+    (type $error' (variant (case "no-such-store") (case "access-denied") (case "other" string)))
+    (export $error "error" (type (eq $error')))
+    (export $bucket "bucket" (type (sub resource)))
+    (export "open" (func (param "identifier" string) (result (result (own $bucket) (error $error)))))
+  ))
+  (alias export $store "error" (type $error))
+  (alias export $store "bucket" (type $bucket))
+  (import "wasi:keyvalue/atomics@0.2.0-draft" (instance $atomics
+    (alias outer 1 $error (type $error'))
+    (export $error "error" (type (eq $error')))
+    (alias outer 1 $bucket (type $bucket'))
+    (export $bucket "bucket" (type (eq $bucket')))
+    (export "increment" (func
+      (param "bucket" (borrow $bucket))
+      (param "key" string)
+      (param "delta" u64)
+      (result (result u64 (error $error)))))
+  ))
+
+  (core module $libc
+    (memory (export "memory") 1)
+    (global $next (mut i32) (i32.const 1024))
+    (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+      (local $ret i32)
+      (local.set $ret (i32.and (i32.add (global.get $next) (i32.const 7)) (i32.const -8)))
+      (global.set $next (i32.add (local.get $ret) (local.get 3)))
+      (local.get $ret))
+  )
+  (core instance $libc (instantiate $libc))
+  (alias core export $libc "realloc" (core func $realloc))
+
+  (alias export $store "open" (func $open))
+  (alias export $atomics "increment" (func $increment))
+  (core func $open (canon lower (func $open) (memory $libc "memory") (realloc $realloc)))
+  (core func $increment
+    (canon lower (func $increment) (memory $libc "memory") (realloc $realloc)))
+
+  (core module $m
+    (import "libc" "memory" (memory 1))
+    (import "keyvalue" "open" (func $open (param i32 i32 i32)))
+    (import "keyvalue" "increment" (func $increment (param i32 i32 i32 i64 i32)))
+    (data (i32.const 100) "bn")
+
+    (func (export "run") (result i32)
+      (local $bucket i32)
+      (call $open (i32.const 100) (i32.const 1) (i32.const 16))
+      (if (i32.load8_u (i32.const 16)) (then unreachable))
+      (local.set $bucket (i32.load (i32.const 20)))
+
+      (call $increment (local.get $bucket) (i32.const 101) (i32.const 1) (i64.const 5) (i32.const 32))
+      (if (i32.load8_u (i32.const 32)) (then unreachable))
+      (call $increment (local.get $bucket) (i32.const 101) (i32.const 1) (i64.const 2) (i32.const 32))
+      (if (i32.load8_u (i32.const 32)) (then unreachable))
+      (if (i64.ne (i64.load (i32.const 40)) (i64.const 7)) (then unreachable))
+      i32.const 0)
+  )
+  (core instance $i (instantiate $m
+    (with "libc" (instance $libc))
+    (with "keyvalue" (instance
+      (export "open" (func $open))
+      (export "increment" (func $increment))))
+  ))
+
+  (func $run (result (result)) (canon lift (core func $i "run")))
+  (instance (export (interface "wasi:cli/run@0.2.0-rc-2023-12-05"))
+    (export "run" (func $run)))
+)
@@ -0,0 +1,75 @@
+;; A `wasi:cli/run` component which traps unless `wasi:config/runtime` has the
+;; value `cli` for `a`, the value `cli-token` for `token` and exactly two values
+;; listed by `get-all`.
+(component
+  (import "wasi:config/runtime@0.2.0-draft" (instance $runtime
+    (type $config-error' (variant (case "upstream" string) (case "io" string)))
+    (export $config-error "config-error" (type (eq $config-error')))
+    (export "get" (func (param "key" string) (result (result (option string) (error $config-error)))))
+    (export "get-all" (func (result (result (list (tuple string string)) (error $config-error)))))
+  ))
+
+  (core module $libc
+    (memory (export "memory") 1)
+    (global $next (mut i32) (i32.const 1024))
+    (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+      (local $ret i32)
+      (local.set $ret (i32.and (i32.add (global.get $next) (i32.const 7)) (i32.const -8)))
+      (global.set $next (i32.add (local.get $ret) (local.get 3)))
+      (local.get $ret))
+  )
+  (core instance $libc (instantiate $libc))
+  (alias core export $libc "realloc" (core func $realloc))
+
+  (alias export $runtime "get" (func $get))
+  (alias export $runtime "get-all" (func $get-all))
+  (core func $get (canon lower (func $get) (memory $libc "memory") (realloc $realloc)))
+  (core func $get-all (canon lower (func $get-all) (memory $libc "memory") (realloc $realloc)))
+
+  (core module $m
+    (import "libc" "memory" (memory 1))
+    (import "config" "get" (func $get (param i32 i32 i32)))
+    (import "config" "get-all" (func $get-all (param i32)))
+    (data (i32.const 100) "a")
+    (data (i32.const 101) "token")
+    (data (i32.const 110) "cli")
+    (data (i32.const 120) "cli-token")
+
+    ;; Traps unless the value of `key` is `expected`.
+    (func $expect (param $key i32) (param $key-len i32) (param $expected i32) (param $len i32)
+      (local $ptr i32)
+      (call $get (local.get $key) (local.get $key-len) (i32.const 16))
+      ;; `ok` and `some`
+      (if (i32.load8_u (i32.const 16)) (then unreachable))
+      (if (i32.eqz (i32.load8_u (i32.const 20))) (then unreachable))
+      (if (i32.ne (i32.load (i32.const 28)) (local.get $len)) (then unreachable))
+      (local.set $ptr (i32.load (i32.const 24)))
+      (block $done
+        (loop $compare
+          (br_if $done (i32.eqz (local.get $len)))
+          (if (i32.ne (i32.load8_u (local.get $ptr)) (i32.load8_u (local.get $expected)))
+            (then unreachable))
+          (local.set $ptr (i32.add (local.get $ptr) (i32.const 1)))
+          (local.set $expected (i32.add (local.get $expected) (i32.const 1)))
+          (local.set $len (i32.sub (local.get $len) (i32.const 1)))
+          (br $compare))))
+
+    (func (export "run") (result i32)
+      (call $expect (i32.const 100) (i32.const 1) (i32.const 110) (i32.const 3))
+      (call $expect (i32.const 101) (i32.const 5) (i32.const 120) (i32.const 9))
+      (call $get-all (i32.const 16))
+      (if (i32.load8_u (i32.const 16)) (then unreachable))
+      (if (i32.ne (i32.load (i32.const 24)) (i32.const 2)) (then unreachable))
+      i32.const 0)
+  )
+  (core instance $i (instantiate $m
+    (with "libc" (instance $libc))
+    (with "config" (instance
+      (export "get" (func $get))
+      (export "get-all" (func $get-all))))
+  ))
+
+  (func $run (result (result)) (canon lift (core func $i "run")))
+  (instance (export (interface "wasi:cli/run@0.2.0-rc-2023-12-05"))
+    (export "run" (func $run)))
+)
@@ -0,0 +1,136 @@
+;; A `wasi:http/proxy` component which responds to each request with the status
+;; `200 + n`, where `n` is the number of requests its instance handled before,
+;; and whose `reset` export sets `n` to 10.
+(component
+  (import "wasi:http/types@0.2.0-rc-2023-12-05" (instance $types
+    (export $fields "fields" (type (sub resource)))
+    (export $incoming-request "incoming-request" (type (sub resource)))
+    (export $outgoing-response "outgoing-response" (type (sub resource)))
+    (export $response-outparam "response-outparam" (type (sub resource)))
+
+    (type $dns-error-payload' (record
+      (field "rcode" (option string))
+      (field "info-code" (option u16))))
+    (export $dns-error-payload "DNS-error-payload" (type (eq $dns-error-payload')))
+    (type $tls-alert-received-payload' (record
+      (field "alert-id" (option u8))
+      (field "alert-message" (option string))))
+    (export $tls-alert-received-payload "TLS-alert-received-payload"
+      (type (eq $tls-alert-received-payload')))
+    (type $field-size-payload' (record
+      (field "field-name" (option string))
+      (field "field-size" (option u32))))
+    (export $field-size-payload "field-size-payload" (type (eq $field-size-payload')))
+    (type $error-code' (variant
+      (case "DNS-timeout")
+      (case "DNS-error" $dns-error-payload)
+      (case "destination-not-found")
+      (case "destination-unavailable")
+      (case "destination-IP-prohibited")
+      (case "destination-IP-unroutable")
+      (case "connection-refused")
+      (case "connection-terminated")
+      (case "connection-timeout")
+      (case "connection-read-timeout")
+      (case "connection-write-timeout")
+      (case "connection-limit-reached")
+      (case "TLS-protocol-error")
+      (case "TLS-certificate-error")
+      (case "TLS-alert-received" $tls-alert-received-payload)
+      (case "HTTP-request-denied")
+      (case "HTTP-request-length-required")
+      (case "HTTP-request-body-size" (option u64))
+      (case "HTTP-request-method-invalid")
+      (case "HTTP-request-URI-invalid")
+      (case "HTTP-request-URI-too-long")
+      (case "HTTP-request-header-section-size" (option u32))
+      (case "HTTP-request-header-size" (option $field-size-payload))
+      (case "HTTP-request-trailer-section-size" (option u32))
+      (case "HTTP-request-trailer-size" $field-size-payload)
+      (case "HTTP-response-incomplete")
+      (case "HTTP-response-header-section-size" (option u32))
+      (case "HTTP-response-header-size" $field-size-payload)
+      (case "HTTP-response-body-size" (option u64))
+      (case "HTTP-response-trailer-section-size" (option u32))
+      (case "HTTP-response-trailer-size" $field-size-payload)
+      (case "HTTP-response-transfer-coding" (option string))
+      (case "HTTP-response-content-coding" (option string))
+      (case "HTTP-response-timeout")
+      (case "HTTP-upgrade-failed")
+      (case "HTTP-protocol-error")
+      (case "loop-detected")
+      (case "configuration-error")
+      (case "internal-error" (option string))))
+    (export $error-code "error-code" (type (eq $error-code')))
+
+    (type $response (result (own $outgoing-response) (error $error-code)))
+    (export "[constructor]fields" (func (result (own $fields))))
+    (export "[constructor]outgoing-response"
+      (func (param "headers" (own $fields)) (result (own $outgoing-response))))
+    (export "[method]outgoing-response.set-status-code"
+      (func (param "self" (borrow $outgoing-response)) (param "status-code" u16) (result (result))))
+    (export "[static]response-outparam.set"
+      (func (param "param" (own $response-outparam)) (param "response" $response)))
+  ))
+  (alias export $types "incoming-request" (type $incoming-request))
+  (alias export $types "response-outparam" (type $response-outparam))
+
+  (core module $libc (memory (export "memory") 1))
+  (core instance $libc (instantiate $libc))
+  (alias core export $libc "memory" (core memory $memory))
+
+  (core func $new-fields (canon lower (func $types "[constructor]fields")))
+  (core func $new-response (canon lower (func $types "[constructor]outgoing-response")))
+  (core func $set-status-code
+    (canon lower (func $types "[method]outgoing-response.set-status-code")))
+  (core func $set-response
+    (canon lower (func $types "[static]response-outparam.set") (memory $memory)))
+  (core func $drop-request (canon resource.drop $incoming-request))
+
+  (core module $main
+    (import "types" "new-fields" (func $new-fields (result i32)))
+    (import "types" "new-response" (func $new-response (param i32) (result i32)))
+    (import "types" "set-status-code" (func $set-status-code (param i32 i32) (result i32)))
+    (import "types" "set-response"
+      (func $set-response (param i32 i32 i32 i32 i64 i32 i32 i32 i32)))
+    (import "types" "drop-request" (func $drop-request (param i32)))
+
+    (global $count (mut i32) (i32.const 0))
+
+    (func (export "handle") (param $request i32) (param $out i32)
+      (local $response i32)
+      (call $drop-request (local.get $request))
+      (local.set $response (call $new-response (call $new-fields)))
+      (drop (call $set-status-code
+        (local.get $response)
+        (i32.add (i32.const 200) (global.get $count))))
+      (global.set $count (i32.add (global.get $count) (i32.const 1)))
+      ;; `ok(response)`
+      (call $set-response
+        (local.get $out)
+        (i32.const 0) (local.get $response) (i32.const 0) (i64.const 0)
+        (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 0)))
+
+    (func (export "reset")
+      (global.set $count (i32.const 10)))
+  )
+  (core instance $main (instantiate $main
+    (with "types" (instance
+      (export "new-fields" (func $new-fields))
+      (export "new-response" (func $new-response))
+      (export "set-status-code" (func $set-status-code))
+      (export "set-response" (func $set-response))
+      (export "drop-request" (func $drop-request))
+    ))
+  ))
+
+  (func $handle
+    (param "request" (own $incoming-request))
+    (param "response-out" (own $response-outparam))
+    (canon lift (core func $main "handle")))
+  (func $reset (canon lift (core func $main "reset")))
+
+  (instance $incoming-handler (export "handle" (func $handle)))
+  (export "wasi:http/incoming-handler@0.2.0-rc-2023-12-05" (instance $incoming-handler))
+  (export "reset" (func $reset))
+)
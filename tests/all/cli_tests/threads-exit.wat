@@ -0,0 +1,26 @@
+(module
+  (import "" "memory" (memory $shmem 1 1 shared))
+  (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+  (import "wasi" "thread-spawn"
+    (func $__wasi_thread_spawn (param i32) (result i32)))
+
+  (func (export "_start")
+    ;; The spawned thread exits with status 3 (or traps if its argument is
+    ;; non-zero) while this thread waits for a notification which never
+    ;; comes, so it must be stopped for the program to end.
+    (if (i32.lt_s (call $__wasi_thread_spawn (i32.const 0)) (i32.const 0))
+      (then unreachable))
+    (loop $again
+      (drop (memory.atomic.wait32 (i32.const 128) (i32.const 0) (i64.const -1)))
+      (br_if $again (i32.eqz (i32.atomic.load (i32.const 128))))
+    )
+  )
+
+  (func (export "wasi_thread_start") (param $tid i32) (param $start_arg i32)
+    (if (local.get $start_arg)
+      (then unreachable))
+    (call $proc_exit (i32.const 3))
+  )
+
+  (export "memory" (memory $shmem))
+)
@@ -0,0 +1,51 @@
+(module
+  (import "" "memory" (memory $shmem 1 1 shared))
+  (import "wasi_snapshot_preview1" "fd_write"
+    (func $__wasi_fd_write (param i32 i32 i32 i32) (result i32)))
+  (import "wasi" "thread-spawn"
+    (func $__wasi_thread_spawn (param i32) (result i32)))
+
+  (func (export "_start")
+    ;; The first thread blocks until it is released below, so with a maximum
+    ;; of one thread the second spawn must fail.
+    (if (i32.lt_s (call $__wasi_thread_spawn (i32.const 0)) (i32.const 0))
+      (then unreachable))
+    (if (i32.lt_s (call $__wasi_thread_spawn (i32.const 0)) (i32.const 0))
+      (then (call $print (i32.const 32) (i32.const 13))))
+
+    ;; Release the first thread and wait for it to finish.
+    (i32.atomic.store (i32.const 132) (i32.const 1))
+    (drop (memory.atomic.notify (i32.const 132) (i32.const 1)))
+    (loop $again
+      (drop (memory.atomic.wait32 (i32.const 128) (i32.const 0) (i64.const 1000000)))
+      (br_if $again (i32.eqz (i32.atomic.load (i32.const 128))))
+    )
+
+    (call $print (i32.const 64) (i32.const 5))
+  )
+
+  (func (export "wasi_thread_start") (param $tid i32) (param $start_arg i32)
+    (loop $again
+      (drop (memory.atomic.wait32 (i32.const 132) (i32.const 0) (i64.const 1000000)))
+      (br_if $again (i32.eqz (i32.atomic.load (i32.const 132))))
+    )
+    (i32.atomic.store (i32.const 128) (i32.const 1))
+    (drop (memory.atomic.notify (i32.const 128) (i32.const 1)))
+  )
+
+  ;; A helper function for printing ptr-len strings.
+  (func $print (param $ptr i32) (param $len i32)
+    (i32.store (i32.const 8) (local.get $len))
+    (i32.store (i32.const 4) (local.get $ptr))
+        (drop (call $__wasi_fd_write
+          (i32.const 1)
+          (i32.const 4)
+          (i32.const 1)
+          (i32.const 0)))
+  )
+
+  (export "memory" (memory $shmem))
+
+  (data (i32.const 32) "Spawn failed\0a")
+  (data (i32.const 64) "Done\0a")
+)
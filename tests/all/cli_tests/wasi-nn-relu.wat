@@ -0,0 +1,53 @@
+;; Loads an ONNX model applying `Relu` to a `float[4]` through wasi-nn, runs
+;; it on `[-1, 2, -3, 4]` and traps unless the output is `[0, 2, 0, 4]`.
+(module
+  (import "wasi_ephemeral_nn" "load"
+    (func $load (param i32 i32 i32 i32 i32) (result i32)))
+  (import "wasi_ephemeral_nn" "init_execution_context"
+    (func $init_execution_context (param i32 i32) (result i32)))
+  (import "wasi_ephemeral_nn" "set_input"
+    (func $set_input (param i32 i32 i32) (result i32)))
+  (import "wasi_ephemeral_nn" "compute"
+    (func $compute (param i32) (result i32)))
+  (import "wasi_ephemeral_nn" "get_output"
+    (func $get_output (param i32 i32 i32 i32 i32) (result i32)))
+
+  (memory (export "memory") 1)
+
+  ;; The model, and a single graph builder referring to it.
+  (data (i32.const 256)
+    "\08\07\3a\36\0a\0c\0a\01\78\12\01\79\22\04\52\65\6c\75\12\04\72\65\6c\75"
+    "\5a\0f\0a\01\78\12\0a\0a\08\08\01\12\04\0a\02\08\04\62\0f\0a\01\79\12\0a"
+    "\0a\08\08\01\12\04\0a\02\08\04\42\02\10\0d")
+  (data (i32.const 512) "\00\01\00\00\3e\00\00\00")
+
+  ;; The input tensor: its dimensions, data and then the `tensor` record.
+  (data (i32.const 528) "\04\00\00\00")
+  (data (i32.const 544) "\00\00\80\bf\00\00\00\40\00\00\40\c0\00\00\80\40")
+  (data (i32.const 576)
+    "\10\02\00\00\01\00\00\00\01\00\00\00\20\02\00\00\10\00\00\00")
+
+  (func $check (param i32)
+    (if (local.get 0) (then unreachable)))
+
+  (func $expect (param $index i32) (param $value f32)
+    (if (f32.ne
+          (f32.load (i32.add (i32.const 768) (i32.mul (local.get $index) (i32.const 4))))
+          (local.get $value))
+      (then unreachable)))
+
+  (func (export "_start")
+    ;; `onnx` on the `cpu`.
+    (call $check (call $load (i32.const 512) (i32.const 1) (i32.const 1) (i32.const 0) (i32.const 32)))
+    (call $check (call $init_execution_context (i32.load (i32.const 32)) (i32.const 36)))
+    (call $check (call $set_input (i32.load (i32.const 36)) (i32.const 0) (i32.const 576)))
+    (call $check (call $compute (i32.load (i32.const 36))))
+    (call $check (call $get_output
+      (i32.load (i32.const 36)) (i32.const 0) (i32.const 768) (i32.const 16) (i32.const 40)))
+    (if (i32.ne (i32.load (i32.const 40)) (i32.const 16))
+      (then unreachable))
+    (call $expect (i32.const 0) (f32.const 0))
+    (call $expect (i32.const 1) (f32.const 2))
+    (call $expect (i32.const 2) (f32.const 0))
+    (call $expect (i32.const 3) (f32.const 4)))
+)
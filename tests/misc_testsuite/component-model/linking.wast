@@ -16,3 +16,17 @@
 (assert_unlinkable
   (component (import "i" (instance (export "x" (func)))))
   "expected func found nothing")
+
+;; components can be registered to be imported by later components
+(component
+  (core module $m (func (export "g") (result i32) i32.const 7))
+  (export "m" (core module $m))
+)
+(register "registered")
+(component
+  (import "registered" (instance $a (export "m" (core module (export "g" (func (result i32)))))))
+  (core instance $i (instantiate (module $a "m")))
+  (core func $g (alias core export $i "g"))
+  (func (export "g") (result u32) (canon lift (core func $g)))
+)
+(assert_return (invoke "g") (u32.const 7))